@@ -0,0 +1,92 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Loads the configuration file, whose values are overridden by command line options.
+
+use instapi::{download::FilenameTemplate, policy::SyncPolicy};
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use serde::Deserialize;
+
+/// Values of the configuration file. All of them are optional.
+///
+/// # Examples
+/// ```toml
+/// output_dir = "/home/user/Pictures/instagram"
+/// name_template = "{timestamp:%Y-%m-%d}_{shortcode}"
+/// jobs = 8
+/// account = "/home/user/.config/work-token.json"
+///
+/// [[filters.rules]]
+/// media_type = "VIDEO"
+/// action = "skip"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory to download media to if it isn't passed to the `download` or `sync` command.
+    pub output_dir: Option<PathBuf>,
+    /// Template of file names.
+    pub name_template: Option<String>,
+    /// Template of subdirectories to save media to.
+    pub layout: Option<String>,
+    /// Number of files to download at the same time.
+    pub jobs: Option<usize>,
+    /// Rules that decide which media items to download, they replace the saved ones.
+    pub filters: Option<SyncPolicy>,
+    /// Path to the token file of the account used by default.
+    pub account: Option<PathBuf>,
+}
+
+impl Config {
+    /// Parses the template of file names.
+    pub fn name_template(&self) -> Result<Option<FilenameTemplate>, String> {
+        parse_template(self.name_template.as_deref(), "name_template")
+    }
+
+    /// Parses the template of subdirectories.
+    pub fn layout(&self) -> Result<Option<FilenameTemplate>, String> {
+        parse_template(self.layout.as_deref(), "layout")
+    }
+}
+
+/// Reads the configuration from `path` or from the default [path]. A missing default file
+/// means that nothing is configured, but a missing file at `path` is an error.
+///
+/// # Panics
+/// If `format!` panics.
+pub fn load(path: Option<&Path>) -> Result<Config, String> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match self::path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+            return Ok(Config::default())
+        }
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    };
+    toml::from_str(&contents)
+        .map_err(|e| format!("Invalid configuration {}: {}", path.display(), e))
+}
+
+/// Get path to the configuration file, e.g. `~/.config/instafetcher/config.toml`.
+/// Returns `None` if the configuration directory isn't available.
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(env!("CARGO_CRATE_NAME")).join("config.toml"))
+}
+
+fn parse_template(template: Option<&str>, key: &str) -> Result<Option<FilenameTemplate>, String> {
+    template
+        .map(|template| template.parse())
+        .transpose()
+        .map_err(|e| format!("Invalid {} in the configuration: {}", key, e))
+}
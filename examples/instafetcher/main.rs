@@ -2,94 +2,328 @@
 // Contacts: <nikita.dudko.95@gmail.com>
 // Licensed under the MIT License.
 
+mod config;
 mod media;
+mod progress;
 mod token;
 
 use instapi::{
     auth::{self, LongLivedToken, Secrets, ShortLivedToken},
-    user::{AccountType, Profile},
+    download::{
+        CollisionPolicy, DedupMode, ExistingFiles, FilenameTemplate, ThumbnailMode,
+        DEFAULT_CONCURRENCY,
+    },
+    export::Column,
+    settings::AccountSettings,
+    user::{AccountType, MediaQuery, MediaType, Profile},
 };
+use config::Config;
 
-use std::{fs, process};
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    process,
+    time::Duration,
+};
 use std::{
     collections::HashMap,
     ffi::OsStr,
     path::{Path, PathBuf},
 };
-use clap::Parser;
-use url::Url;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[clap(about, author, version)]
 #[clap(name = env!("CARGO_CRATE_NAME"))]
 #[clap(arg_required_else_help = true)]
 struct Cli {
+    /// Configuration file, its values are overridden by the options
+    /// [default: ~/.config/instafetcher/config.toml]
+    #[clap(long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+
+    /// Token file of the account to use instead of the configured one
+    #[clap(long, value_name = "FILE", global = true)]
+    account: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
     /// Perform authorization and save a token
-    #[clap(short, long)]
-    log_in: bool,
+    Login,
+
+    /// Revoke and remove the saved token
+    Logout,
 
     /// Print the user profile information
-    #[clap(short, long)]
-    info: bool,
+    Info,
+
+    /// Print metadata of all user's media
+    Export {
+        /// Format of the metadata: json, csv or jsonl
+        #[clap(value_name = "FORMAT", parse(try_from_str = parse_export_format))]
+        format: media::ExportFormat,
+
+        /// Comma-separated fields to export to CSV or JSON Lines, e.g. "id,timestamp,caption"
+        #[clap(long, value_name = "FIELDS", use_value_delimiter = true)]
+        columns: Vec<Column>,
+    },
+
+    /// Print statistics of posting: types of media, posts per year and top hashtags
+    Stats,
+
+    /// Print an Atom feed of the latest posts
+    Feed {
+        /// Number of posts
+        #[clap(value_name = "N", default_value_t = 20)]
+        count: usize,
+    },
 
     /// Download all user's media files
-    #[clap(short, long, value_name = "DIR")]
-    #[clap(forbid_empty_values = true, parse(try_from_os_str = validate_output_dir))]
-    media: Option<PathBuf>,
+    Download {
+        /// Output directory, the configured one is used if it's omitted
+        #[clap(value_name = "DIR", forbid_empty_values = true)]
+        dir: Option<PathBuf>,
+
+        /// Print the number and size of the files instead of downloading them
+        #[clap(long)]
+        estimate: bool,
 
+        /// Download media of a single type: images, videos or albums
+        #[clap(long, value_name = "TYPE", parse(try_from_str = parse_media_type))]
+        only: Option<MediaType>,
+
+        /// Download media published at or after DATE, e.g. 2022-01-31 or 2022-01-31T12:00:00Z
+        #[clap(long, value_name = "DATE", parse(try_from_str = parse_date))]
+        since: Option<DateTime<Utc>>,
+
+        /// Download media published before DATE
+        #[clap(long, value_name = "DATE", parse(try_from_str = parse_date))]
+        until: Option<DateTime<Utc>>,
+
+        #[clap(flatten)]
+        options: DownloadArgs,
+    },
+
+    /// Download media published since the previous sync of the directory
+    Sync {
+        /// Synced directory, the configured output directory is used if it's omitted
+        #[clap(value_name = "DIR", forbid_empty_values = true)]
+        dir: Option<PathBuf>,
+
+        /// Move files of the media deleted remotely to the trash directory
+        #[clap(long)]
+        prune: bool,
+
+        #[clap(flatten)]
+        options: DownloadArgs,
+    },
+
+    /// Check files of a downloaded directory against its SHA256SUMS
+    Verify {
+        #[clap(value_name = "DIR")]
+        dir: PathBuf,
+    },
+}
+
+/// Options shared by the `download` and `sync` commands.
+#[derive(Args)]
+struct DownloadArgs {
     /// Don't download albums content
     #[clap(long)]
     no_albums: bool,
+
+    /// Template of file names, e.g. "{timestamp:%Y-%m-%d}_{shortcode}". Fields: id, username,
+    /// timestamp, type, shortcode, album_id
+    #[clap(long, value_name = "TEMPLATE")]
+    name_template: Option<FilenameTemplate>,
+
+    /// Template of subdirectories to save media to, e.g. "{timestamp:%Y}/{timestamp:%m}"
+    #[clap(long, value_name = "TEMPLATE")]
+    layout: Option<FilenameTemplate>,
+
+    /// What to do if names of items collide: error, skip, overwrite or suffix
+    #[clap(long, value_name = "POLICY", default_value = "suffix")]
+    #[clap(parse(try_from_str = parse_collision_policy))]
+    on_collision: CollisionPolicy,
+
+    /// What to do with files saved by previous downloads: skip or overwrite. Unlike
+    /// the library default (overwrite), they're skipped, so re-runs only fetch missing files
+    #[clap(long, value_name = "MODE", default_value = "skip")]
+    #[clap(parse(try_from_str = parse_existing_files))]
+    existing: ExistingFiles,
+
+    /// Download thumbnails of videos: off, alongside or only (instead of the videos)
+    #[clap(long, value_name = "MODE", default_value = "off")]
+    #[clap(parse(try_from_str = parse_thumbnail_mode))]
+    thumbnails: ThumbnailMode,
+
+    /// What to do with files of the same content: off, hardlink, symlink or skip
+    #[clap(long, value_name = "MODE", default_value = "off")]
+    #[clap(parse(try_from_str = parse_dedup_mode))]
+    dedup: DedupMode,
+
+    /// Directory of the files downloaded earlier, which are taken from it instead of the CDN
+    #[clap(long, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Number of files to download at the same time [default: 4]
+    #[clap(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Delay between requests to the same host in milliseconds
+    #[clap(long, value_name = "MS", default_value_t = 0)]
+    delay: u64,
+
+    /// Write SHA-256 checksums of the downloaded files to SHA256SUMS
+    #[clap(long)]
+    checksums: bool,
+
+    /// Write metadata of each media item to a JSON file next to it
+    #[clap(long)]
+    sidecars: bool,
+
+    /// Print each processed item instead of displaying progress bars. Bars are never
+    /// displayed if the standard error isn't a terminal
+    #[clap(long)]
+    no_progress: bool,
+
+    /// Save the given options (--no-albums, --name-template and --layout) as the account settings
+    #[clap(long)]
+    remember: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
+    let config = unwrap_or_exit(config::load(cli.config.as_deref()));
+    let account = cli.account.clone().or_else(|| config.account.clone());
+    let account = account.as_deref();
+    let mut settings = token::settings(account);
 
-    if cli.log_in {
-        run_or_exit(log_in);
-    }
-    if let Some(dir) = cli.media.as_deref() {
-        run_or_exit(|| media::download_all(dir, !cli.no_albums));
+    match &cli.command {
+        Command::Login => run_or_exit(|| log_in(account)),
+        Command::Logout => {
+            run_or_exit(|| token::delete(account).map_err(|e| format!("Couldn't log out: {}", e)))
+        }
+        Command::Info => run_or_exit(|| print_info(account)),
+        Command::Export { format, columns } => {
+            run_or_exit(|| media::export(*format, columns, account, &settings))
+        }
+        Command::Stats => run_or_exit(|| media::stats(account, &settings)),
+        Command::Feed { count } => run_or_exit(|| media::feed(*count, account, &settings)),
+        Command::Download { dir, estimate, only, since, until, options } => {
+            let dir = unwrap_or_exit(output_dir(dir.clone(), &config));
+            let mut options = unwrap_or_exit(options.apply(&mut settings, account, &config));
+            options.query = MediaQuery {
+                since: *since,
+                until: *until,
+                media_type: *only,
+                ..MediaQuery::default()
+            };
+            if *estimate {
+                run_or_exit(|| media::estimate(&dir, account, &settings, &options));
+            } else {
+                run_or_exit(|| media::download_all(&dir, account, &settings, &options));
+            }
+        }
+        Command::Sync { dir, prune, options } => {
+            let dir = unwrap_or_exit(output_dir(dir.clone(), &config));
+            let mut options = unwrap_or_exit(options.apply(&mut settings, account, &config));
+            options.prune = *prune;
+            run_or_exit(|| media::sync(&dir, account, &settings, &options));
+        }
+        Command::Verify { dir } => run_or_exit(|| media::verify(dir)),
     }
-    if cli.info {
-        run_or_exit(print_info);
+}
+
+impl DownloadArgs {
+    /// Applies the options to the account `settings`, saving them if they should be remembered,
+    /// and returns options of a download. Unset options and filters are taken from `config`,
+    /// they aren't remembered.
+    ///
+    /// # Panics
+    /// If `format!` panics.
+    fn apply(
+        &self,
+        settings: &mut AccountSettings,
+        account: Option<&Path>,
+        config: &Config,
+    ) -> Result<media::Options, String> {
+        settings.skip_albums |= self.no_albums;
+        if self.name_template.is_some() {
+            settings.name_template = self.name_template.clone();
+        }
+        if self.layout.is_some() {
+            settings.layout = self.layout.clone();
+        }
+        if self.remember {
+            token::save_settings(settings, account)
+                .map_err(|e| format!("Couldn't save the settings: {}", e))?;
+        }
+        if let Some(filters) = &config.filters {
+            settings.policy = filters.clone();
+        }
+        // Templates of the account take precedence over the configured ones.
+        if settings.name_template.is_none() {
+            settings.name_template = config.name_template()?;
+        }
+        if settings.layout.is_none() {
+            settings.layout = config.layout()?;
+        }
+        Ok(media::Options {
+            collisions: self.on_collision,
+            existing: self.existing,
+            thumbnails: self.thumbnails,
+            dedup: self.dedup,
+            cache: self.cache.clone(),
+            jobs: self.jobs.or(config.jobs).unwrap_or(DEFAULT_CONCURRENCY),
+            delay: Duration::from_millis(self.delay),
+            checksums: self.checksums,
+            sidecars: self.sidecars,
+            prune: false,
+            progress: !self.no_progress && io::stderr().is_terminal(),
+            query: MediaQuery::default(),
+        })
     }
 }
 
-/// Performs authorization, retrieves a long-lived token and saves it.
-///
-/// # Panics
-/// If invalid secrets provided.
-fn log_in() -> Result<(), String> {
-    let secrets = Secrets {
-        app_id: env!("INSTAGRAM_APP_ID")
-            .parse()
-            .expect("Instagram application ID must be an unsigned number"),
-        app_secret: env!("INSTAGRAM_APP_SECRET"),
-        oauth_uri: Url::parse(env!("INSTAGRAM_OAUTH_URI"))
-            .expect("Instagram OAuth redirect URI isn't valid"),
-    };
+/// Performs authorization, retrieves a long-lived token and saves it to the `account` file
+/// or to the default one.
+fn log_in(account: Option<&Path>) -> Result<(), String> {
+    let secrets = Secrets::builder()
+        .app_id(env!("INSTAGRAM_APP_ID"))
+        .app_secret(env!("INSTAGRAM_APP_SECRET"))
+        .oauth_uri(env!("INSTAGRAM_OAUTH_URI"))
+        .build();
+    if let Err(e) = secrets {
+        return Err(format!("Invalid application secrets: {}", e));
+    }
+    let secrets = secrets.unwrap();
 
-    let token_path = token::path();
+    let token_path = account.map_or_else(token::path, Path::to_path_buf);
     if token_path.exists() {
         println!("Warning: existing token will be overwritten");
     }
 
     let code = auth::request_code(&secrets);
     if let Err(e) = code {
-        return Err(format!("Couldn't request a code: {}", e));
+        return Err(e.to_string());
     }
 
     println!("Retrieving a short-lived token...");
     let short_lived_token = ShortLivedToken::new(&secrets, code.unwrap().as_str());
     if let Err(e) = short_lived_token {
-        return Err(format!("Couldn't retrieve the token: {}", e));
+        return Err(e.to_string());
     }
 
     println!("Exchanging the token for a long-lived one...");
     let long_lived_token = LongLivedToken::new(&secrets, short_lived_token.unwrap());
     if let Err(e) = long_lived_token {
-        return Err(format!("Couldn't exchange the token: {}", e));
+        return Err(e.to_string());
     }
 
     if let Err(e) = token::save(&long_lived_token.unwrap(), Some(token_path.as_path())) {
@@ -98,13 +332,17 @@ fn log_in() -> Result<(), String> {
     Ok(())
 }
 
-/// Loads a token and displays the basic user information.
-fn print_info() -> Result<(), String> {
-    let token = token::load(None);
+/// Loads a token from the `account` file (or from the default one)
+/// and displays the basic user information.
+fn print_info(account: Option<&Path>) -> Result<(), String> {
+    let token = token::load(account);
     if let Err(e) = token {
         return Err(format!("Couldn't load a token: {}", e));
     }
-    let profile = Profile::new(token.unwrap());
+    let profile = match Profile::new(token.unwrap()) {
+        Ok(profile) => profile,
+        Err(e) => return Err(format!("Couldn't initialize a client: {}", e)),
+    };
 
     println!("Retrieving the user profile information...");
     let info = profile.info();
@@ -121,7 +359,7 @@ fn print_info() -> Result<(), String> {
 
     println!(
         "\nUser ID: {}\nUsername: @{}\nAccount type: {}\nMedia count: {}",
-        profile.id(),
+        info.id(),
         info.username(),
         account_types.get(&info.account_type()).unwrap(),
         info.media_count(),
@@ -140,24 +378,108 @@ fn run_or_exit<F: Fn() -> Result<(), String>>(func: F) {
     }
 }
 
-/// If a directory exists, checks if it empty and readable, otherwise creates a new one.
+/// If `result` is `Err`, prints an error message and terminates the current process.
+///
+/// # Panics
+/// If failed to write to the standard output.
+fn unwrap_or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(1);
+    })
+}
+
+/// Checks `dir` using [validate_output_dir]. If `dir` is omitted, the configured
+/// output directory is checked instead.
+///
+/// # Panics
+/// If `format!` panics.
+fn output_dir(dir: Option<PathBuf>, config: &Config) -> Result<PathBuf, String> {
+    let dir = match dir.or_else(|| config.output_dir.clone()) {
+        Some(dir) => dir,
+        None => return Err("Pass a directory or configure output_dir".into()),
+    };
+    validate_output_dir(dir.as_os_str())
+        .map_err(|e| format!("Invalid output directory {}: {}", dir.display(), e))
+}
+
+/// If a directory exists, checks if it's a directory, otherwise creates a new one.
+/// The directory may contain files of previous downloads.
 ///
 /// # Panics
 /// If `format!` panics.
 fn validate_output_dir(path: &OsStr) -> Result<PathBuf, String> {
     let path = Path::new(path);
-    if path.exists() {
-        if path.is_file() {
-            return Err("it's a file".into());
-        }
-        match path.read_dir() {
-            Ok(mut contents) => if contents.next().is_some() {
-                return Err("directory must be empty".into());
-            },
-            Err(e) => return Err(format!("unable to read directory ({})", e)),
-        }
-    } else if let Err(e) = fs::create_dir(path) {
+    if path.is_file() {
+        return Err("it's a file".into());
+    }
+    if let Err(e) = fs::create_dir_all(path) {
         return Err(format!("failed to create directory ({})", e));
     }
     Ok(path.to_path_buf())
 }
+
+fn parse_collision_policy(policy: &str) -> Result<CollisionPolicy, String> {
+    match policy {
+        "error" => Ok(CollisionPolicy::Error),
+        "skip" => Ok(CollisionPolicy::Skip),
+        "overwrite" => Ok(CollisionPolicy::Overwrite),
+        "suffix" => Ok(CollisionPolicy::Suffix),
+        _ => Err("expected error, skip, overwrite or suffix".into()),
+    }
+}
+
+fn parse_existing_files(mode: &str) -> Result<ExistingFiles, String> {
+    match mode {
+        "skip" => Ok(ExistingFiles::Skip),
+        "overwrite" => Ok(ExistingFiles::Overwrite),
+        _ => Err("expected skip or overwrite".into()),
+    }
+}
+
+fn parse_media_type(media_type: &str) -> Result<MediaType, String> {
+    match media_type {
+        "images" => Ok(MediaType::Image),
+        "videos" => Ok(MediaType::Video),
+        "albums" => Ok(MediaType::CarouselAlbum),
+        _ => Err("expected images, videos or albums".into()),
+    }
+}
+
+/// Parses an RFC 3339 date or a day in the `YYYY-MM-DD` format, which is taken at midnight UTC.
+fn parse_date(date: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|day| Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| "expected YYYY-MM-DD or an RFC 3339 date".into())
+}
+
+fn parse_thumbnail_mode(mode: &str) -> Result<ThumbnailMode, String> {
+    match mode {
+        "off" => Ok(ThumbnailMode::Off),
+        "alongside" => Ok(ThumbnailMode::Alongside),
+        "only" => Ok(ThumbnailMode::Only),
+        _ => Err("expected off, alongside or only".into()),
+    }
+}
+
+fn parse_export_format(format: &str) -> Result<media::ExportFormat, String> {
+    match format {
+        "json" => Ok(media::ExportFormat::Json),
+        "csv" => Ok(media::ExportFormat::Csv),
+        "jsonl" => Ok(media::ExportFormat::JsonLines),
+        _ => Err("expected json, csv or jsonl".into()),
+    }
+}
+
+fn parse_dedup_mode(mode: &str) -> Result<DedupMode, String> {
+    match mode {
+        "off" => Ok(DedupMode::Off),
+        "hardlink" => Ok(DedupMode::HardLink),
+        "symlink" => Ok(DedupMode::SymLink),
+        "skip" => Ok(DedupMode::Skip),
+        _ => Err("expected off, hardlink, symlink or skip".into()),
+    }
+}
@@ -4,95 +4,396 @@
 
 //! Functions to download media files.
 
-use crate::token;
+use crate::{progress::Bars, token};
 use instapi::{
     auth::LongLivedToken,
-    user::{Media, MediaType, Profile},
+    download::{
+        CollisionPolicy, ContentCache, DedupMode, DownloadOutcome, DownloadReport, Downloader,
+        ExistingFiles, ItemReport, Manifest, ManifestFormat, PruneMode, ThumbnailMode,
+    },
+    export::{self, Column, CsvExporter, Exporter, JsonExporter, JsonLinesExporter},
+    feed::Feed,
+    settings::AccountSettings,
+    stats::Stats,
+    user::{Media, MediaQuery, MediaType, Profile},
 };
 
 use std::{
     collections::HashMap,
-    error::Error,
-    fs::{self, File},
-    io,
+    fs,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
-use threadpool::ThreadPool;
 
-/// Loads a token, gathers media information and downloads contents to `output_dir`.
+/// Format of the exported metadata.
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    JsonLines,
+}
+
+/// Options of a download that aren't stored in the account settings.
+pub struct Options {
+    /// What to do with items whose names collide.
+    pub collisions: CollisionPolicy,
+    /// What to do with items whose files have been saved by previous downloads.
+    pub existing: ExistingFiles,
+    /// Whether thumbnails of videos are downloaded along with them or instead of them.
+    pub thumbnails: ThumbnailMode,
+    /// What to do with files whose content matches a file saved earlier.
+    pub dedup: DedupMode,
+    /// Directory of the cached contents.
+    pub cache: Option<PathBuf>,
+    /// Number of files downloaded at the same time.
+    pub jobs: usize,
+    /// Delay between requests to the same host.
+    pub delay: Duration,
+    /// Write checksums of the downloaded files to a manifest.
+    pub checksums: bool,
+    /// Write metadata of each item to a JSON file next to it.
+    pub sidecars: bool,
+    /// Move local copies of the media deleted remotely to the trash when syncing.
+    pub prune: bool,
+    /// Display progress bars instead of printing each processed item.
+    pub progress: bool,
+    /// Narrows down the downloaded media, e.g. by type. Doesn't apply to syncs.
+    pub query: MediaQuery,
+}
+
+/// Loads a token, gathers media information and downloads contents to `output_dir`
+/// according to the account `settings` and `options`.
 ///
 /// # Panics
-/// 1. If [token::load], [instapi::user::Profile::media], [download_album] or `format!` panics.
+/// 1. If [token::load] or `format!` panics.
 /// 2. If failed to write to the standard output.
-pub fn download_all(output_dir: &Path, include_albums: bool) -> Result<(), String> {
-    let token = token::load(None);
-    if let Err(e) = token {
-        return Err(format!("Couldn't load a token: {}", e));
+pub fn download_all(
+    output_dir: &Path,
+    account: Option<&Path>,
+    settings: &AccountSettings,
+    options: &Options,
+) -> Result<(), String> {
+    let profile = load_profile(account, settings)?;
+    let media = profile.media_iter_with(&options.query);
+    let bars = options.progress.then(|| Arc::new(Bars::new()));
+    let downloader = downloader(profile, output_dir, settings, options, bars.clone());
+    // Pages are gathered while the previous items are being downloaded.
+    println!("Downloading media...");
+    let report = downloader.download_iter(media);
+    if let Some(bars) = bars {
+        bars.finish();
+    }
+    for e in report.errors() {
+        eprintln!("Couldn't gather media: {}", e);
     }
-    let profile = Profile::new(token.unwrap());
+    finish(&downloader, &report, options)
+}
 
+/// Loads a token and prints metadata of all user's media in `format` to the standard output.
+/// Only the given `columns` are written to CSV and JSON Lines, all of them if it's empty.
+///
+/// # Panics
+/// 1. If [token::load] or `format!` panics.
+/// 2. If failed to write to the standard error.
+pub fn export(
+    format: ExportFormat,
+    columns: &[Column],
+    account: Option<&Path>,
+    settings: &AccountSettings,
+) -> Result<(), String> {
+    let profile = load_profile(account, settings)?;
+    // Messages go to the standard error, so they don't mix with the exported data.
+    eprintln!("Gathering information about the user's media...");
+    let media = profile.media().map_err(|e| format!("Couldn't gather the information: {}", e))?;
+    let columns = if columns.is_empty() { Column::ALL.to_vec() } else { columns.to_vec() };
+
+    let stdout = std::io::stdout();
+    let mut exporter: Box<dyn Exporter> = match format {
+        ExportFormat::Json => Box::new(JsonExporter::new(stdout.lock())),
+        ExportFormat::Csv => {
+            let mut exporter = CsvExporter::new(stdout.lock());
+            exporter.set_columns(columns);
+            Box::new(exporter)
+        }
+        ExportFormat::JsonLines => {
+            let mut exporter = JsonLinesExporter::new(stdout.lock());
+            exporter.set_columns(columns);
+            Box::new(exporter)
+        }
+    };
+    export::export(&media, exporter.as_mut()).map_err(|e| format!("Couldn't export: {}", e))?;
+    Ok(())
+}
+
+/// Loads a token and prints statistics of the user's posts.
+///
+/// # Panics
+/// 1. If [token::load] or `format!` panics.
+/// 2. If failed to write to the standard output.
+pub fn stats(account: Option<&Path>, settings: &AccountSettings) -> Result<(), String> {
+    let profile = load_profile(account, settings)?;
     println!("Gathering information about the user's media...");
-    let media = profile.media();
-    if let Err(e) = media {
-        return Err(format!("Couldn't gather the information: {}", e));
-    }
+    let media = profile.media().map_err(|e| format!("Couldn't gather the information: {}", e))?;
+    let stats = Stats::from_media(&media);
 
-    let pool = ThreadPool::new(num_cpus::get());
-    println!("Downloading media...");
-    for media in media.unwrap() {
-        if media.media_type() == MediaType::CarouselAlbum {
-            if include_albums {
-                download_album(&media, output_dir, &profile, &pool);
-            }
-            continue;
+    println!("\nPosts: {}", stats.posts);
+    for media_type in [MediaType::Image, MediaType::Video, MediaType::CarouselAlbum] {
+        let count = stats.by_type.get(&media_type).copied().unwrap_or_default();
+        println!("{}: {}", media_type, count);
+    }
+    println!("Average caption length: {:.0}", stats.average_caption_length());
+    println!("\nPosts per year:");
+    for (year, count) in &stats.per_year {
+        println!("{}: {}", year, count);
+    }
+    let hashtags = stats.top_hashtags(10);
+    if !hashtags.is_empty() {
+        println!("\nTop hashtags:");
+        for (hashtag, count) in hashtags {
+            println!("#{}: {}", hashtag, count);
         }
+    }
+    Ok(())
+}
+
+/// Loads a token and prints an Atom feed of the latest `count` posts to the standard output.
+///
+/// # Panics
+/// 1. If [token::load] or `format!` panics.
+/// 2. If failed to write to the standard error.
+pub fn feed(
+    count: usize,
+    account: Option<&Path>,
+    settings: &AccountSettings,
+) -> Result<(), String> {
+    let profile = load_profile(account, settings)?;
+    eprintln!("Gathering the latest media...");
+    let info = profile.info().map_err(|e| format!("Couldn't retrieve the profile: {}", e))?;
+    let media = profile
+        .media_recent(count)
+        .map_err(|e| format!("Couldn't gather the information: {}", e))?;
+    Feed::for_user(info.username())
+        .write_atom(&media, std::io::stdout().lock())
+        .map_err(|e| format!("Couldn't write the feed: {}", e))
+}
 
-        let output_dir = output_dir.to_path_buf();
-        pool.execute(move || {
-            print(&media, None);
-            if let Err(e) = download_file(&media, &output_dir) {
-                eprintln!("Failed to download media with ID {}: {}", media.id(), e);
-            }
-        });
+/// Prints the number and size of the files that [download_all] would save to `output_dir`,
+/// so free space can be checked first.
+///
+/// # Panics
+/// 1. If [token::load] or `format!` panics.
+/// 2. If failed to write to the standard output.
+pub fn estimate(
+    output_dir: &Path,
+    account: Option<&Path>,
+    settings: &AccountSettings,
+    options: &Options,
+) -> Result<(), String> {
+    let profile = load_profile(account, settings)?;
+    println!("Gathering information about the user's media...");
+    let media = profile
+        .media_with(&options.query)
+        .map_err(|e| format!("Couldn't gather the information: {}", e))?;
+    let downloader = downloader(profile, output_dir, settings, options, None);
+    println!("Requesting sizes of the files...");
+    let estimate = downloader
+        .estimate(media)
+        .map_err(|e| format!("Couldn't estimate the download: {}", e))?;
+
+    println!("Files: {}", estimate.files);
+    println!("Size: {:.1} MiB", estimate.bytes as f64 / (1024 * 1024) as f64);
+    if estimate.unknown_sizes > 0 {
+        println!("Files of unknown size: {}", estimate.unknown_sizes);
     }
-    pool.join();
     Ok(())
 }
 
-/// Gathers album information, creates a directory and downloads album contents to it.
+/// Works like [download_all], but downloads only the media published since
+/// the previous sync of `output_dir`, which is recorded in its state file.
+/// Prints the numbers of new, skipped and failed items.
 ///
 /// # Panics
-/// 1. If [print], [instapi::user::Profile::album] or [filename] panics.
+/// 1. If [token::load] or `format!` panics.
 /// 2. If failed to write to the standard output.
-fn download_album(
-    album: &Media,
+pub fn sync(
     output_dir: &Path,
-    profile: &Profile<LongLivedToken>,
-    pool: &ThreadPool
-) {
-    print(album, None);
+    account: Option<&Path>,
+    settings: &AccountSettings,
+    options: &Options,
+) -> Result<(), String> {
+    let profile = load_profile(account, settings)?;
+    let bars = options.progress.then(|| Arc::new(Bars::new()));
+    let downloader = downloader(profile, output_dir, settings, options, bars.clone());
+    println!("Downloading new media...");
+    let report = downloader.sync();
+    if let Some(bars) = bars {
+        bars.finish();
+    }
+    let report = report.map_err(|e| format!("Couldn't sync: {}", e))?;
+    for e in report.errors() {
+        eprintln!("Couldn't gather media: {}", e);
+    }
+    if report.items().is_empty() {
+        println!("No new media");
+    } else {
+        println!(
+            "\nNew: {}, skipped: {}, failed: {}",
+            report.succeeded().count(),
+            report.skipped().count(),
+            report.failed().count(),
+        );
+    }
 
-    let media = profile.album(album);
-    if let Err(e) = media {
-        eprintln!("Couldn't gather content information of album with ID {}: {}", album.id(), e);
-        return;
+    if options.prune {
+        println!("Looking for deleted media...");
+        let removed = downloader
+            .prune(PruneMode::Trash)
+            .map_err(|e| format!("Couldn't remove deleted media: {}", e))?;
+        for path in &removed {
+            println!("Moved to the trash: {}", path.display());
+        }
     }
+    finish(&downloader, &report, options)
+}
 
-    let output_dir = output_dir.join(filename(album));
-    if let Err(e) = fs::create_dir(&output_dir) {
-        eprintln!("Failed to create directory for album with ID {}: {}", album.id(), e);
-        return;
+/// Loads a token from the `account` file (or from the default one) and initializes a profile
+/// according to `settings`.
+///
+/// # Panics
+/// If [token::load] or `format!` panics.
+fn load_profile(
+    account: Option<&Path>,
+    settings: &AccountSettings,
+) -> Result<Profile<LongLivedToken>, String> {
+    let token = token::load(account);
+    if let Err(e) = token {
+        return Err(format!("Couldn't load a token: {}", e));
     }
+    let mut profile = match Profile::new(token.unwrap()) {
+        Ok(profile) => profile,
+        Err(e) => return Err(format!("Couldn't initialize a client: {}", e)),
+    };
+    settings.apply_to_profile(&mut profile);
+    Ok(profile)
+}
 
-    let album_id = album.id();
-    for media in media.unwrap() {
-        let output_dir = output_dir.clone();
-        pool.execute(move || {
-            print(&media, Some(album_id));
-            if let Err(e) = download_file(&media, &output_dir) {
-                eprintln!("Failed to download album media with ID {}: {}", media.id(), e);
-            }
-        });
+/// Constructs a downloader that saves files to `output_dir` and reports processed items,
+/// either using `bars` or by printing them.
+fn downloader(
+    profile: Profile<LongLivedToken>,
+    output_dir: &Path,
+    settings: &AccountSettings,
+    options: &Options,
+    bars: Option<Arc<Bars>>,
+) -> Downloader<LongLivedToken> {
+    let mut downloader = Downloader::new(profile, output_dir);
+    settings.apply_to_downloader(&mut downloader);
+    downloader.set_collision_policy(options.collisions);
+    downloader.set_existing_files(options.existing);
+    downloader.set_thumbnails(options.thumbnails);
+    downloader.set_dedup(options.dedup);
+    if let Some(dir) = &options.cache {
+        match ContentCache::open(dir) {
+            Ok(cache) => downloader.set_cache(Some(cache)),
+            Err(e) => eprintln!("Couldn't open the cache, it's not used: {}", e),
+        }
+    }
+    downloader.set_concurrency(options.jobs);
+    downloader.set_request_delay(options.delay);
+    downloader.set_checksums(options.checksums);
+    downloader.set_sidecars(options.sidecars);
+    match bars {
+        Some(bars) => {
+            let progress_bars = Arc::clone(&bars);
+            downloader.on_progress(move |progress| progress_bars.update(progress));
+            downloader.on_item(move |item| bars.finish_item(item));
+        }
+        None => downloader.on_item(report),
+    }
+    downloader
+}
+
+/// Writes checksums if they're requested and saves failures to the output directory.
+/// Returns an error if some items failed.
+///
+/// # Panics
+/// If `format!` panics or if failed to write to the output.
+fn finish(
+    downloader: &Downloader<LongLivedToken>,
+    report: &DownloadReport,
+    options: &Options,
+) -> Result<(), String> {
+    if options.checksums {
+        match downloader.update_manifest(report, ManifestFormat::Sha256Sum) {
+            Ok(path) => println!("Checksums are written to {}", path.display()),
+            Err(e) => eprintln!("Couldn't write the checksums: {}", e),
+        }
+    }
+
+    let failures = report.failures();
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    // Failures are saved, so they aren't lost among the output.
+    let path = downloader.destination().join("failures.json");
+    let saved = serde_json::to_string_pretty(&failures)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    match saved {
+        Ok(()) => Err(format!(
+            "{} media items failed to download, they're listed in {}",
+            failures.len(),
+            path.display(),
+        )),
+        Err(e) => Err(format!("{} media items failed to download ({})", failures.len(), e)),
+    }
+}
+
+/// Checks files in `dir` against the checksums written by [download_all].
+///
+/// # Panics
+/// If `format!` panics or if failed to write to the output.
+pub fn verify(dir: &Path) -> Result<(), String> {
+    let format = ManifestFormat::Sha256Sum;
+    let manifest = Manifest::load(dir.join(format.filename()), format)
+        .map_err(|e| format!("Couldn't load the checksums: {}", e))?;
+
+    let mismatches = manifest.verify(dir);
+    for mismatch in &mismatches {
+        eprintln!("{}", mismatch);
+    }
+    if mismatches.is_empty() {
+        println!("All {} files are intact", manifest.entries().len());
+        Ok(())
+    } else {
+        Err(format!("{} files don't match their checksums", mismatches.len()))
+    }
+}
+
+/// Prints the result of a processed media item.
+///
+/// # Panics
+/// If [print] panics or if failed to write to the standard output.
+fn report(item: &ItemReport) {
+    let media = item.media();
+    match item.outcome() {
+        DownloadOutcome::Downloaded(_) | DownloadOutcome::MetadataOnly => {
+            print(media, item.parent_id())
+        }
+        DownloadOutcome::Skipped => match item.duplicate_of() {
+            Some(original) => println!(
+                "Media with ID {} is skipped as a duplicate of {}",
+                media.id(),
+                original.display()
+            ),
+            None => println!("Media with ID {} is skipped", media.id()),
+        },
+        DownloadOutcome::Failed(e) => {
+            let kind = if item.parent_id().is_some() { "album media" } else { "media" };
+            eprintln!("Failed to download {} with ID {}: {}", kind, media.id(), e);
+        }
     }
 }
 
@@ -118,8 +419,8 @@ fn print(media: &Media, parent_id: Option<u64>) {
     buffer.push_str(format!(
         "\nType: {}\nOwner: @{}\nPublish date: {}",
         types.get(&media.media_type()).unwrap(),
-        media.username(),
-        media.timestamp().to_rfc2822(),
+        media.username().unwrap_or_default(),
+        media.timestamp().map_or_else(|| media.raw_timestamp().to_string(), |t| t.to_rfc2822()),
     ).as_str());
 
     if let Some(caption) = media.caption() {
@@ -129,42 +430,3 @@ fn print(media: &Media, parent_id: Option<u64>) {
 
     println!("{}", buffer);
 }
-
-/// Downloads `media`'s content to the `output_dir`. File name constructs using [filename].
-/// Extension retrieves from URL. Return path to the downloaded file.
-///
-/// # Panics
-/// If [filename] panics.
-fn download_file(media: &Media, output_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
-    let url = media.media_url();
-
-    let mut filename = filename(media);
-    if let Some(os_extension) = Path::new(url.path()).extension() {
-        if let Some(extension) = os_extension.to_str() {
-            filename.push('.');
-            filename.push_str(extension);
-        }
-    }
-
-    let filepath = output_dir.join(filename);
-    let mut file = File::create(&filepath)?;
-
-    let response = reqwest::blocking::get(url.clone())?.error_for_status()?;
-    let mut content = io::Cursor::new(response.bytes()?);
-    io::copy(&mut content, &mut file)?;
-
-    Ok(filepath)
-}
-
-/// Constructs a file name based on media's metadata.
-///
-/// # Panics
-/// If `format!` panics.
-fn filename(media: &Media) -> String {
-    format!(
-        "{}_{}_{}",
-        media.username(),
-        media.id(),
-        media.timestamp().format("%FT%H-%M-%S"),
-    )
-}
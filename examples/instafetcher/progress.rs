@@ -0,0 +1,79 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Progress bars of downloads.
+
+use instapi::download::{DownloadOutcome, ItemReport, Progress};
+
+use std::{collections::HashMap, sync::Mutex};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// Displays the number of processed items and a bar for each file being downloaded.
+/// Bars are drawn to the standard error.
+pub struct Bars {
+    multi: MultiProgress,
+    items: ProgressBar,
+    /// Bars of the files being downloaded by media IDs.
+    files: Mutex<HashMap<u64, ProgressBar>>,
+}
+
+impl Bars {
+    pub fn new() -> Self {
+        let multi = MultiProgress::new();
+        let items = multi.add(ProgressBar::new(0));
+        items.set_style(style("[{elapsed_precise}] [{bar:40}] {pos}/{len} items"));
+        Self { multi, items, files: Mutex::default() }
+    }
+
+    /// Updates the bar of the file that `progress` belongs to, adding it if it's new.
+    pub fn update(&self, progress: &Progress) {
+        self.items.set_length(progress.scheduled_items as u64);
+        let mut files = self.files.lock().unwrap();
+        let bar = files.entry(progress.media_id).or_insert_with(|| {
+            let bar = self.multi.add(ProgressBar::new(0));
+            bar.set_style(style("{msg:>20} [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec})"));
+            bar.set_message(progress.media_id.to_string());
+            bar
+        });
+        // The size is unknown if the server doesn't report it.
+        bar.set_length(progress.total.unwrap_or(progress.downloaded));
+        bar.set_position(progress.downloaded);
+    }
+
+    /// Removes the bar of the file of `item` and counts the item as processed.
+    /// Failures are printed above the bars.
+    pub fn finish_item(&self, item: &ItemReport) {
+        let media_id = item.media().id();
+        if let Some(bar) = self.files.lock().unwrap().remove(&media_id) {
+            bar.finish_and_clear();
+            self.multi.remove(&bar);
+        }
+        self.items.inc(1);
+        // Items that aren't downloaded don't report progress, so they may outrun the length.
+        if self.items.length().is_some_and(|length| length < self.items.position()) {
+            self.items.set_length(self.items.position());
+        }
+        if let DownloadOutcome::Failed(e) = item.outcome() {
+            let kind = if item.parent_id().is_some() { "album media" } else { "media" };
+            let message = format!("Failed to download {} with ID {}: {}", kind, media_id, e);
+            self.multi.println(message).ok();
+        }
+    }
+
+    /// Removes all bars once the download is complete.
+    pub fn finish(&self) {
+        for (_, bar) in self.files.lock().unwrap().drain() {
+            bar.finish_and_clear();
+        }
+        self.items.finish_and_clear();
+    }
+}
+
+/// Constructs a style of bars from the valid `template`.
+///
+/// # Panics
+/// If `template` is invalid.
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template).unwrap().progress_chars("=> ")
+}
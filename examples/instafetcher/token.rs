@@ -4,11 +4,13 @@
 
 //! Contains functions to load and preserve a long-lived token.
 
-use instapi::auth::{LongLivedToken, Token};
+use instapi::{
+    auth::{LongLivedToken, Token, TokenStore},
+    settings::AccountSettings,
+};
 use std::{
     error::Error,
     fs,
-    os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
 };
 use chrono::{Duration, Utc};
@@ -20,23 +22,19 @@ use chrono::{Duration, Utc};
 /// If `format!` panics or if failed to write to the standard output.
 pub fn load(path: Option<&Path>) -> Result<LongLivedToken, Box<dyn Error>> {
     const REFRESH_THRESHOLD_DAYS: i64 = 7;
-    const LOGIN_SUGGESTION: &str = "(use --log-in to perform authorization)";
+    const LOGIN_SUGGESTION: &str = "(use the login command to perform authorization)";
 
-    let path = match path {
-        Some(path) => path.to_path_buf(),
-        None => self::path(),
-    };
-    if !path.exists() {
+    let store = store(path);
+    if !store.exists() {
         let mut message = "file".to_string();
-        if let Some(str) = path.to_str() {
+        if let Some(str) = store.path().to_str() {
             message.push(' ');
             message.push_str(str);
         }
         return Err(format!("{} doesn't exist {}", message, LOGIN_SUGGESTION).into());
     }
 
-    let json = fs::read_to_string(&path)?;
-    let mut token: LongLivedToken = serde_json::from_str(json.as_str())?;
+    let mut token = store.load()?;
     if !token.is_valid() {
         return Err(format!("token has been expired {}", LOGIN_SUGGESTION).into());
     }
@@ -51,7 +49,7 @@ pub fn load(path: Option<&Path>) -> Result<LongLivedToken, Box<dyn Error>> {
 
         if let Err(e) = token.refresh() {
             eprintln!("Failed to refresh the token: {}", e);
-        } else if let Err(e) = save(&token, Some(path.as_path())) {
+        } else if let Err(e) = save(&token, Some(store.path())) {
             eprintln!("Failed to save the refreshed token: {}", e);
         }
     }
@@ -64,25 +62,11 @@ pub fn load(path: Option<&Path>) -> Result<LongLivedToken, Box<dyn Error>> {
 /// # Panics
 /// If failed to write to the standard output.
 pub fn save(token: &LongLivedToken, path: Option<&Path>) -> Result<(), Box<dyn Error>> {
-    let path = match path {
-        Some(path) => path.to_path_buf(),
-        None => self::path(),
-    };
-
-    let json = serde_json::to_string(token)?;
-    fs::write(&path, json)?;
-
-    if cfg!(unix) {
-        if let Ok(metadata) = fs::metadata(&path) {
-            let mut perms = metadata.permissions();
-            // Limit read-write access to the owner only.
-            perms.set_mode(0o600);
-            fs::set_permissions(&path, perms).ok();
-        }
-    }
+    let store = store(path);
+    store.save(token)?;
 
     print!("Token saved");
-    if let Some(str) = path.to_str() {
+    if let Some(str) = store.path().to_str() {
         print!(" to {}", str);
     }
     println!(
@@ -92,6 +76,48 @@ pub fn save(token: &LongLivedToken, path: Option<&Path>) -> Result<(), Box<dyn E
     Ok(())
 }
 
+/// Loads settings of the account which token located at `path` or at the default [path].
+/// Returns default settings if they can't be read.
+///
+/// # Panics
+/// If failed to write to the standard output.
+pub fn settings(path: Option<&Path>) -> AccountSettings {
+    store(path).load_settings().unwrap_or_else(|e| {
+        eprintln!("Failed to load the account settings: {}", e);
+        AccountSettings::default()
+    })
+}
+
+/// Saves settings of the account which token located at `path` or at the default [path].
+pub fn save_settings(
+    settings: &AccountSettings,
+    path: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    store(path).save_settings(settings)
+}
+
+/// Revokes a stored token and removes its file.
+///
+/// # Panics
+/// If failed to write to the standard output.
+pub fn delete(path: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let store = store(path);
+    if !store.exists() {
+        return Err("there is no saved token".into());
+    }
+
+    match store.load() {
+        Ok(token) => if let Err(e) = token.revoke() {
+            eprintln!("Failed to revoke the token: {}", e);
+        },
+        Err(e) => eprintln!("Failed to read the token: {}", e),
+    }
+    store.delete()?;
+
+    println!("Token removed");
+    Ok(())
+}
+
 /// Get path to the serialized long-lived token file. Creates configuration directory
 /// recursively if it doesn't exist. If the directory isn't available, returns file name only.
 ///
@@ -109,3 +135,11 @@ pub fn path() -> PathBuf {
     }
     path
 }
+
+/// Constructs a store for the token located at `path` or at the default [path].
+fn store(path: Option<&Path>) -> TokenStore {
+    TokenStore::new(match path {
+        Some(path) => path.to_path_buf(),
+        None => self::path(),
+    })
+}
@@ -4,13 +4,29 @@
 
 //! Authorization related stuff: tokens and application secrets.
 
+#[cfg(unix)]
+pub mod daemon;
+pub mod device;
+mod error;
+mod secrets;
+pub mod signed_request;
+mod store;
+
+pub use error::AuthError;
+pub use secrets::{SecretsBuilder, SecretsError};
+pub use store::TokenStore;
+
 use std::{collections::HashMap, io::{self, Write}};
 
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+/// Permissions requested during the authorization.
+const SCOPES: &str = "user_profile,user_media";
+
 /// Private information that specific for an Instagram application.
+/// Use [Secrets::builder] to validate the values.
 ///
 /// # Examples
 /// ```
@@ -31,19 +47,31 @@ pub struct Secrets {
 
 /// Represents an User Access Token.
 ///
-/// Use [ShortLivedToken::new] or [LongLivedToken::new] to construct a token.
+/// Use [ShortLivedToken::new], [LongLivedToken::new] or [AppToken::new] to construct a token.
 pub trait Token {
     /// Returns the user's app-scoped token.
     fn get(&self) -> &str;
     /// Get the user ID that a token belongs to.
-    fn user_id(&self) -> u64;
+    /// Returns `None` for tokens that aren't linked to any user.
+    fn user_id(&self) -> Option<u64>;
     /// Returns the date after which a token won't be valid.
     fn expiration_date(&self) -> &DateTime<Utc>;
+    /// Returns permissions granted by the user, like `user_media`.
+    /// `None` if they are unknown, e.g. when the API hasn't reported them.
+    fn scopes(&self) -> Option<&[String]> {
+        None
+    }
 
     /// Checks if a token isn't expired.
     fn is_valid(&self) -> bool {
         Utc::now() < *self.expiration_date()
     }
+
+    /// Checks if the `scope` permission has been granted.
+    /// Returns `None` if granted permissions are unknown.
+    fn has_scope(&self, scope: &str) -> Option<bool> {
+        self.scopes().map(|scopes| scopes.iter().any(|granted| granted == scope))
+    }
 }
 
 /// Serializable short-lived token, valid for 1 hour after retrieving.
@@ -53,32 +81,59 @@ pub struct ShortLivedToken {
     user_id: u64,
     #[serde(with = "chrono::serde::ts_seconds")]
     expiration_date: DateTime<Utc>,
+    #[serde(default)]
+    scopes: Option<Vec<String>>,
 }
 
 /// Serializable long-lived token that valid for 60 days, or 90 days for private accounts.
 ///
 /// Can be refreshed.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LongLivedToken {
     access_token: String,
     user_id: u64,
     #[serde(with = "chrono::serde::ts_seconds")]
     expiration_date: DateTime<Utc>,
+    // Scopes of tokens saved before they were tracked are unknown.
+    #[serde(default)]
+    scopes: Option<Vec<String>>,
+    #[serde(default)]
+    token_type: String,
+    #[serde(default)]
+    expires_in: u32,
+}
+
+/// Serializable app access token that never expires.
+///
+/// Used by endpoints that don't act on behalf of a user, such as oEmbed or token debugging.
+#[derive(Serialize, Deserialize)]
+pub struct AppToken {
+    access_token: String,
+    app_id: u64,
 }
 
 /// Abstractions over JSON responses.
 mod response {
     use serde::Deserialize;
 
+    #[derive(Deserialize)]
+    pub(super) struct AppToken {
+        pub(super) access_token: String,
+    }
+
     #[derive(Deserialize)]
     pub(super) struct ShortLivedToken {
         pub(super) access_token: String,
         pub(super) user_id: u64,
+        /// Comma-separated list of granted permissions.
+        pub(super) permissions: Option<String>,
     }
 
     #[derive(Deserialize)]
     pub(super) struct LongLivedToken {
         pub(super) access_token: String,
+        #[serde(default)]
+        pub(super) token_type: String,
         /// Represented in seconds.
         pub(super) expires_in: u32,
     }
@@ -88,10 +143,16 @@ impl ShortLivedToken {
     /// Constructs a new instance by exchanging `code` for a short-lived User Access Token.
     /// `code` can be retrieved using the [request_code] function.
     ///
+    /// Failures are wrapped into [AuthError::ShortLivedExchange]
+    /// or [AuthError::CodeAlreadyUsed].
+    ///
     /// # Panics
-    /// If a [Client][reqwest::blocking::Client] can't be initialized or if `format!` panics while
-    /// constructing an URL.
-    pub fn new(secrets: &Secrets, code: &str) -> reqwest::Result<Self> {
+    /// If `format!` panics while constructing an URL.
+    pub fn new(secrets: &Secrets, code: &str) -> crate::Result<Self> {
+        Self::exchange(secrets, code).map_err(|e| AuthError::short_lived_exchange(e).into())
+    }
+
+    fn exchange(secrets: &Secrets, code: &str) -> crate::Result<Self> {
         let app_id = secrets.app_id.to_string();
         let params: HashMap<_, _> = [
             ("client_id", app_id.as_str()),
@@ -101,13 +162,12 @@ impl ShortLivedToken {
             ("code", code),
         ].iter().cloned().collect();
 
-        let client = reqwest::blocking::Client::new();
+        let client = reqwest::blocking::Client::builder().build()?;
         let response = client
             .post(format!("{}/oauth/access_token", crate::AUTH_BASE_URL))
             .form(&params)
-            .send()?
-            .error_for_status()?;
-        Ok(response.json::<response::ShortLivedToken>()?.into())
+            .send()?;
+        Ok(crate::error::check(response)?.json::<response::ShortLivedToken>()?.into())
     }
 }
 
@@ -115,12 +175,15 @@ impl Token for ShortLivedToken {
     fn get(&self) -> &str {
         &self.access_token
     }
-    fn user_id(&self) -> u64 {
-        self.user_id
+    fn user_id(&self) -> Option<u64> {
+        Some(self.user_id)
     }
     fn expiration_date(&self) -> &DateTime<Utc> {
         &self.expiration_date
     }
+    fn scopes(&self) -> Option<&[String]> {
+        self.scopes.as_deref()
+    }
 }
 
 impl From<response::ShortLivedToken> for ShortLivedToken {
@@ -130,19 +193,26 @@ impl From<response::ShortLivedToken> for ShortLivedToken {
             access_token: response.access_token,
             user_id: response.user_id,
             expiration_date: Utc::now() + Duration::hours(AVAILABILITY_HOURS),
+            scopes: response.permissions.as_deref().map(split_scopes),
         }
     }
 }
 
 impl LongLivedToken {
     /// Constructs a long-lived User Access Token by exchanging a short-lived token.
-    /// `short_lived_token` must be valid.
+    /// `short_lived_token` must be valid. Failures are wrapped into
+    /// [AuthError::LongLivedExchange].
     ///
     /// # Panics
     /// If `format!` panics while constructing an URL.
     pub fn new(secrets: &Secrets, short_lived_token: ShortLivedToken) -> crate::Result<Self> {
+        Self::exchange(secrets, short_lived_token)
+            .map_err(|e| AuthError::LongLivedExchange(e).into())
+    }
+
+    fn exchange(secrets: &Secrets, short_lived_token: ShortLivedToken) -> crate::Result<Self> {
         if !short_lived_token.is_valid() {
-            return Err("short-lived token has been expired".into());
+            return Err(Box::new(crate::Error::TokenExpired(None)));
         }
 
         let url = Url::parse_with_params(format!("{}/access_token", crate::BASE_URL).as_str(), [
@@ -150,23 +220,30 @@ impl LongLivedToken {
             ("access_token", short_lived_token.get()),
             ("grant_type", "ig_exchange_token"),
         ])?;
-        let response = reqwest::blocking::get(url)?.error_for_status()?;
+        let response = crate::error::check(reqwest::blocking::get(url)?)?;
 
         let token: response::LongLivedToken = response.json()?;
         Ok(Self {
             access_token: token.access_token,
             user_id: short_lived_token.user_id,
             expiration_date: Utc::now() + Duration::seconds(token.expires_in.into()),
+            scopes: short_lived_token.scopes,
+            token_type: token.token_type,
+            expires_in: token.expires_in,
         })
     }
 
-    /// Refreshes a valid token.
+    /// Refreshes a valid token. Failures are wrapped into [AuthError::Refresh].
     ///
     /// # Panics
     /// If `format!` panics while constructing an URL.
     pub fn refresh(&mut self) -> crate::Result<()> {
+        self.request_refresh().map_err(|e| AuthError::Refresh(e).into())
+    }
+
+    fn request_refresh(&mut self) -> crate::Result<()> {
         if !self.is_valid() {
-            return Err("token has been expired".into());
+            return Err(Box::new(crate::Error::TokenExpired(None)));
         }
 
         let url = Url::parse_with_params(
@@ -176,34 +253,125 @@ impl LongLivedToken {
                 ("grant_type", "ig_refresh_token"),
             ]
         )?;
-        let response = reqwest::blocking::get(url)?.error_for_status()?;
+        let response = crate::error::check(reqwest::blocking::get(url)?)?;
 
         let token: response::LongLivedToken = response.json()?;
         self.access_token = token.access_token;
         self.expiration_date = Utc::now() + Duration::seconds(token.expires_in.into());
+        self.token_type = token.token_type;
+        self.expires_in = token.expires_in;
+        Ok(())
+    }
+
+    /// Revokes the app authorization granted by the user, consuming the token.
+    /// Remove a stored copy of the token using [TokenStore::delete].
+    ///
+    /// # Panics
+    /// If `format!` panics.
+    pub fn revoke(self) -> crate::Result<()> {
+        if !self.is_valid() {
+            // An expired token can't be used anymore, so there is nothing to revoke.
+            return Ok(());
+        }
+
+        let url = Url::parse_with_params(
+            format!("{}/{}/me/permissions", crate::BASE_URL, crate::API_VERSION).as_str(),
+            [("access_token", self.access_token.as_str())],
+        )?;
+        crate::client::Client::new()?.delete(url)?;
         Ok(())
     }
 }
 
+impl LongLivedToken {
+    /// Returns the token type reported by the last exchange or refresh, usually `bearer`.
+    /// Empty for tokens saved before the type was tracked.
+    pub fn token_type(&self) -> &str {
+        &self.token_type
+    }
+
+    /// Returns the lifetime in seconds reported by the last exchange or refresh.
+    /// Zero for tokens saved before the lifetime was tracked.
+    pub fn expires_in(&self) -> u32 {
+        self.expires_in
+    }
+}
+
 impl Token for LongLivedToken {
     fn get(&self) -> &str {
         &self.access_token
     }
-    fn user_id(&self) -> u64 {
-        self.user_id
+    fn user_id(&self) -> Option<u64> {
+        Some(self.user_id)
     }
     fn expiration_date(&self) -> &DateTime<Utc> {
         &self.expiration_date
     }
+    fn scopes(&self) -> Option<&[String]> {
+        self.scopes.as_deref()
+    }
+}
+
+impl AppToken {
+    /// Retrieves an app access token using the client credentials grant.
+    ///
+    /// # Panics
+    /// If `format!` panics.
+    pub fn new(secrets: &Secrets) -> crate::Result<Self> {
+        let url = Url::parse_with_params(
+            format!("{}/oauth/access_token", crate::FACEBOOK_BASE_URL).as_str(),
+            [
+                ("client_id", secrets.app_id.to_string().as_str()),
+                ("client_secret", secrets.app_secret),
+                ("grant_type", "client_credentials"),
+            ]
+        )?;
+        let response = crate::error::check(reqwest::blocking::get(url)?)?;
+
+        Ok(Self {
+            access_token: response.json::<response::AppToken>()?.access_token,
+            app_id: secrets.app_id,
+        })
+    }
+
+    /// Constructs a token in the `app_id|app_secret` form without making a request.
+    pub fn from_secrets(secrets: &Secrets) -> Self {
+        Self {
+            access_token: format!("{}|{}", secrets.app_id, secrets.app_secret),
+            app_id: secrets.app_id,
+        }
+    }
+}
+
+impl Token for AppToken {
+    fn get(&self) -> &str {
+        &self.access_token
+    }
+    /// Returns `None` as an app token isn't linked to any user.
+    fn user_id(&self) -> Option<u64> {
+        None
+    }
+    fn expiration_date(&self) -> &DateTime<Utc> {
+        &chrono::MAX_DATETIME
+    }
+    /// Returns no permissions as an app token isn't granted any by a user.
+    fn scopes(&self) -> Option<&[String]> {
+        Some(&[])
+    }
 }
 
 /// Interactively forwards the user to the authorization page and requests a code.
 ///
-/// Returns the trimmed authorization code.
+/// Returns the trimmed authorization code. Failures are wrapped into [AuthError::CodeRequest].
+/// On headless machines, use [device::DeviceLogin] instead.
 ///
 /// # Panics
 /// If [auth_url] panics or if failed to write to the standard output.
 pub fn request_code(secrets: &Secrets) -> crate::Result<String> {
+    read_code(secrets).map_err(|e| AuthError::CodeRequest(e).into())
+}
+
+fn read_code(secrets: &Secrets) -> crate::Result<String> {
     let auth_url = auth_url(secrets)?;
 
     println!("Opening the authorization page...");
@@ -227,17 +395,71 @@ pub fn request_code(secrets: &Secrets) -> crate::Result<String> {
     Ok(code)
 }
 
+/// Performs the whole interactive authorization: requests a code using [request_code]
+/// and exchanges it for a long-lived token. If the code has already been used
+/// (e.g. a stale one has been pasted), the flow is restarted up to 3 times
+/// and `on_retry` is called with the error before each restart.
+///
+/// # Examples
+/// ```no_run
+/// # fn example(secrets: instapi::auth::Secrets) -> Result<(), Box<dyn std::error::Error>> {
+/// let token = instapi::auth::log_in(&secrets, |e| eprintln!("{}", e))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn log_in<F>(secrets: &Secrets, mut on_retry: F) -> crate::Result<LongLivedToken>
+where
+    F: FnMut(&AuthError),
+{
+    const MAX_ATTEMPTS: u8 = 3;
+    let mut attempt = 1;
+    loop {
+        let code = request_code(secrets)?;
+        let e = match ShortLivedToken::new(secrets, &code) {
+            Ok(token) => return LongLivedToken::new(secrets, token),
+            Err(e) => e,
+        };
+        match e.downcast_ref() {
+            Some(error @ AuthError::CodeAlreadyUsed(_)) if attempt < MAX_ATTEMPTS => {
+                on_retry(error);
+                attempt += 1;
+            }
+            _ => return Err(e),
+        }
+    }
+}
+
 /// Returns an URL that refers to the Authorization Window.
+/// Fails with [SecretsError] if the redirect URI is rejected by [Secrets::validate].
 ///
 /// # Panics
 /// If `format!` panics.
-pub fn auth_url(secrets: &Secrets) -> Result<Url, url::ParseError> {
-    Url::parse_with_params(format!("{}/oauth/authorize", crate::AUTH_BASE_URL).as_str(), [
+pub fn auth_url(secrets: &Secrets) -> crate::Result<Url> {
+    secrets.validate()?;
+    Ok(Url::parse_with_params(format!("{}/oauth/authorize", crate::AUTH_BASE_URL).as_str(), [
         ("client_id", secrets.app_id.to_string().as_str()),
         ("redirect_uri", secrets.oauth_uri.as_str()),
-        ("scope", "user_profile,user_media"),
+        ("scope", SCOPES),
         ("response_type", "code"),
-    ])
+    ])?)
+}
+
+/// Works like [auth_url], but appends the `state` parameter that will be passed back to the
+/// redirect URI. Use an unguessable value and compare it to protect against CSRF attacks.
+pub fn auth_url_with_state(secrets: &Secrets, state: &str) -> crate::Result<Url> {
+    let mut url = auth_url(secrets)?;
+    url.query_pairs_mut().append_pair("state", state);
+    Ok(url)
+}
+
+/// Splits a comma-separated list of permissions.
+fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes
+        .split(',')
+        .map(str::trim)
+        .filter(|scope| !scope.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 #[cfg(test)]
@@ -249,9 +471,28 @@ mod tests {
         let secrets = Secrets {
             app_id: 0,
             app_secret: "",
+            oauth_uri: Url::parse("https://example.com/auth").unwrap(),
+        };
+        assert!(super::auth_url(&secrets).is_ok());
+
+        let insecure = Secrets { oauth_uri: Url::parse("http://example.com").unwrap(), ..secrets };
+        let error = super::auth_url(&insecure).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(SecretsError::InsecureOAuthUri(_))));
+
+        let url = auth_url_with_state(&secrets, "random").unwrap();
+        assert!(url.query_pairs().any(|(key, value)| key == "state" && value == "random"));
+    }
+
+    #[test]
+    fn app_token_from_secrets() {
+        let secrets = Secrets {
+            app_id: 1,
+            app_secret: "secret",
             oauth_uri: Url::parse("test:").unwrap(),
         };
-        assert!(super::auth_url(&secrets).is_ok())
+        let token = AppToken::from_secrets(&secrets);
+        assert_eq!(token.get(), "1|secret");
+        assert!(token.is_valid());
     }
 
     #[test]
@@ -261,6 +502,22 @@ mod tests {
         ShortLivedToken::from(response::ShortLivedToken {
               access_token: String::new(),
               user_id: 0,
+              permissions: None,
         });
     }
+
+    #[test]
+    fn granted_scopes() {
+        let token = ShortLivedToken::from(response::ShortLivedToken {
+            access_token: String::new(),
+            user_id: 0,
+            permissions: Some("user_profile, ".to_string()),
+        });
+        assert_eq!(token.has_scope("user_profile"), Some(true));
+        assert_eq!(token.has_scope("user_media"), Some(false));
+
+        let json = r#"{"access_token":"","user_id":0,"expiration_date":0}"#;
+        let token: LongLivedToken = serde_json::from_str(json).unwrap();
+        assert_eq!(token.has_scope("user_media"), None);
+    }
 }
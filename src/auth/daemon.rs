@@ -0,0 +1,273 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Sharing of a token between processes over a Unix socket.
+//!
+//! A single process runs [TokenServer] that owns a token and refreshes it, other processes
+//! receive the current token using [fetch]. The protocol is line-based: a client sends
+//! `GET <secret>`, and the server replies with `OK <token JSON>` or `ERR <message>`.
+
+use super::{LongLivedToken, Token, TokenStore};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    thread, time,
+};
+
+use chrono::{Duration, Utc};
+
+/// Owns a token and serves it to sibling processes.
+pub struct TokenServer {
+    store: TokenStore,
+    state: Mutex<State>,
+    secret: String,
+    refresh_threshold: Duration,
+    retry_interval: time::Duration,
+    timeout: time::Duration,
+}
+
+struct State {
+    token: LongLivedToken,
+    /// Set while a connection refreshes the token, so others don't refresh it too.
+    refreshing: bool,
+    /// When refreshing failed last time, to not retry on every request.
+    failed_at: Option<time::Instant>,
+}
+
+/// Requests are short, so longer lines are rejected without reading them entirely.
+const MAX_REQUEST_LENGTH: u64 = 1024;
+
+impl TokenServer {
+    /// Loads a token from `store`. Clients must know `secret` to receive the token.
+    /// The token is refreshed and saved to `store` when it expires in less than 7 days.
+    /// A failed refresh is retried not earlier than in a minute.
+    /// Clients that don't send a request or don't read the reply in 5 seconds
+    /// are disconnected.
+    pub fn new(store: TokenStore, secret: &str) -> crate::Result<Self> {
+        const REFRESH_THRESHOLD_DAYS: i64 = 7;
+        const RETRY_INTERVAL_SECS: u64 = 60;
+        const TIMEOUT_SECS: u64 = 5;
+        Ok(Self {
+            state: Mutex::new(State { token: store.load()?, refreshing: false, failed_at: None }),
+            store,
+            secret: secret.to_string(),
+            refresh_threshold: Duration::days(REFRESH_THRESHOLD_DAYS),
+            retry_interval: time::Duration::from_secs(RETRY_INTERVAL_SECS),
+            timeout: time::Duration::from_secs(TIMEOUT_SECS),
+        })
+    }
+
+    /// Sets how long the server waits for a client to send a request or to read the reply.
+    /// Zero is replaced with one millisecond.
+    pub fn set_timeout(&mut self, timeout: time::Duration) {
+        self.timeout = timeout.max(time::Duration::from_millis(1));
+    }
+
+    /// Binds a socket to `path` and handles connections until an I/O error occurs.
+    /// An existing socket file is replaced. Access to the socket is limited to the owner only.
+    /// Each connection is handled on its own thread, so a slow client doesn't delay others.
+    pub fn serve(&self, path: &Path) -> io::Result<()> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = stream?;
+                // A misbehaving client mustn't stop the server.
+                scope.spawn(move || self.handle(stream).ok());
+            }
+            Ok(())
+        })
+    }
+
+    /// Handles a single request. Fails if the client doesn't send it or doesn't read
+    /// the reply in time.
+    fn handle(&self, stream: UnixStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut request = String::new();
+        BufReader::new((&stream).take(MAX_REQUEST_LENGTH)).read_line(&mut request)?;
+
+        let reply = match request.trim_end().strip_prefix("GET ") {
+            Some(secret) if constant_time_eq(secret.as_bytes(), self.secret.as_bytes()) => {
+                match self.current_token() {
+                    Ok(json) => format!("OK {}", json),
+                    Err(e) => format!("ERR {}", e),
+                }
+            }
+            Some(_) => "ERR invalid secret".to_string(),
+            None => "ERR invalid request".to_string(),
+        };
+        writeln!(&stream, "{}", reply)
+    }
+
+    /// Returns the serialized token, refreshing it if needed.
+    /// The lock isn't held while refreshing, so other connections get the current token.
+    fn current_token(&self) -> crate::Result<String> {
+        let mut token = {
+            let mut state = self.lock_state()?;
+            let retry_due = state.failed_at.map_or(true, |at| at.elapsed() >= self.retry_interval);
+            let expiring = *state.token.expiration_date() - self.refresh_threshold < Utc::now();
+            if !expiring || state.refreshing || !retry_due {
+                return serialize(&state.token);
+            }
+            state.refreshing = true;
+            state.token.clone()
+        };
+
+        let refreshed = token.refresh();
+        let mut state = self.lock_state()?;
+        state.refreshing = false;
+        match refreshed {
+            Ok(()) => {
+                state.token = token;
+                state.failed_at = None;
+                self.store.save(&state.token)?;
+            }
+            Err(_) => state.failed_at = Some(time::Instant::now()),
+        }
+        serialize(&state.token)
+    }
+
+    fn lock_state(&self) -> crate::Result<MutexGuard<'_, State>> {
+        self.state.lock().map_err(|_| "token lock is poisoned".into())
+    }
+}
+
+/// Serializes a token, serving the current one if it's still valid even if refreshing failed.
+fn serialize(token: &LongLivedToken) -> crate::Result<String> {
+    if !token.is_valid() {
+        return Err(Box::new(crate::Error::TokenExpired(None)));
+    }
+    Ok(serde_json::to_string(token)?)
+}
+
+/// Requests a token from a [TokenServer] listening on `path`.
+/// Fails if the server doesn't reply in 30 seconds, which is enough to refresh the token.
+pub fn fetch(path: &Path, secret: &str) -> crate::Result<LongLivedToken> {
+    const TIMEOUT_SECS: u64 = 30;
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(time::Duration::from_secs(TIMEOUT_SECS)))?;
+    stream.set_write_timeout(Some(time::Duration::from_secs(TIMEOUT_SECS)))?;
+    writeln!(&stream, "GET {}", secret)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    let reply = reply.trim_end();
+
+    if let Some(json) = reply.strip_prefix("OK ") {
+        Ok(serde_json::from_str(json)?)
+    } else {
+        Err(reply.strip_prefix("ERR ").unwrap_or("invalid reply").into())
+    }
+}
+
+/// Returns the default socket path inside the runtime or temporary directory.
+pub fn default_path() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    dir.join(format!("{}-token.sock", env!("CARGO_PKG_NAME")))
+}
+
+/// Compares secrets in time that doesn't depend on the position of the first mismatch.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> LongLivedToken {
+        LongLivedToken {
+            access_token: "token".to_string(),
+            user_id: 1,
+            expiration_date: Utc::now() + Duration::days(30),
+            scopes: None,
+            token_type: String::new(),
+            expires_in: 0,
+        }
+    }
+
+    fn server(token: LongLivedToken) -> TokenServer {
+        TokenServer {
+            store: TokenStore::new("unused.json"),
+            state: Mutex::new(State { token, refreshing: false, failed_at: None }),
+            secret: "secret".to_string(),
+            refresh_threshold: Duration::days(7),
+            retry_interval: time::Duration::from_secs(60),
+            timeout: time::Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn serve_token() {
+        let server = server(token());
+
+        let request = |line: &str| {
+            let (client, server_stream) = UnixStream::pair().unwrap();
+            writeln!(&client, "{}", line).unwrap();
+            server.handle(server_stream).unwrap();
+
+            let mut reply = String::new();
+            BufReader::new(&client).read_line(&mut reply).unwrap();
+            reply
+        };
+
+        assert!(request("GET secret").starts_with("OK {\"access_token\":\"token\""));
+        assert_eq!(request("GET wrong"), "ERR invalid secret\n");
+        assert_eq!(request("PUT"), "ERR invalid request\n");
+    }
+
+    #[test]
+    fn back_off_failed_refresh() {
+        let server = server(LongLivedToken { expiration_date: Utc::now(), ..token() });
+        let error = server.current_token().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(crate::Error::TokenExpired(None))));
+
+        let failed_at = server.state.lock().unwrap().failed_at;
+        assert!(failed_at.is_some());
+        assert!(server.current_token().is_err());
+        let state = server.state.lock().unwrap();
+        assert_eq!(state.failed_at, failed_at);
+        assert!(!state.refreshing);
+    }
+
+    #[test]
+    fn idle_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.sock");
+        let store = TokenStore::new(dir.path().join("token.json"));
+        store.save(&token()).unwrap();
+        let mut server = TokenServer::new(store, "secret").unwrap();
+        server.set_timeout(time::Duration::from_millis(200));
+        let server_path = path.clone();
+        thread::spawn(move || server.serve(&server_path));
+        while !path.exists() {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+
+        // The idle client neither delays other clients nor keeps its connection forever.
+        let idle = UnixStream::connect(&path).unwrap();
+        assert_eq!(fetch(&path, "secret").unwrap().access_token, "token");
+        let mut reply = String::new();
+        BufReader::new(&idle).read_line(&mut reply).unwrap();
+        assert_eq!(reply, "");
+    }
+
+    #[test]
+    fn constant_time_eq() {
+        assert!(super::constant_time_eq(b"secret", b"secret"));
+        assert!(!super::constant_time_eq(b"secret", b"secreT"));
+        assert!(!super::constant_time_eq(b"secret", b"secret2"));
+    }
+}
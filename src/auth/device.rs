@@ -0,0 +1,202 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Authorization on headless machines where neither a browser nor a local port is available.
+//!
+//! The user opens the authorization URL on another device, and then pastes the code
+//! or the whole redirect URL to a source that is polled by [DeviceLogin::poll],
+//! e.g. a file created over SSH. Applications with their own event loop check the source
+//! by [DeviceLogin::poll_once] instead.
+
+use super::Secrets;
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+/// Describes a pending authorization.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::auth::{device::{self, DeviceLogin}, Secrets};
+/// use std::time::Duration;
+///
+/// # fn example(secrets: Secrets) -> Result<(), Box<dyn std::error::Error>> {
+/// let login = DeviceLogin::new(&secrets)?;
+/// println!("{}", login.instructions("~/instagram-code"));
+/// let code = login.poll(device::file_input("instagram-code"), Duration::from_secs(600))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeviceLogin {
+    auth_url: Url,
+    redirect_uri: Url,
+    interval: Duration,
+}
+
+impl DeviceLogin {
+    /// Constructs the authorization URL. Input is checked every 2 seconds.
+    pub fn new(secrets: &Secrets) -> crate::Result<Self> {
+        const POLL_INTERVAL_SECS: u64 = 2;
+        Ok(Self {
+            auth_url: super::auth_url(secrets)?,
+            redirect_uri: secrets.oauth_uri.clone(),
+            interval: Duration::from_secs(POLL_INTERVAL_SECS),
+        })
+    }
+
+    /// Sets how often the input is checked.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub fn auth_url(&self) -> &Url {
+        &self.auth_url
+    }
+
+    /// Returns instructions to show to the user, where `destination` describes
+    /// where the code must be pasted to.
+    pub fn instructions(&self, destination: &str) -> String {
+        format!(
+            "1. Open this link on any device with a browser:\n   {}\n\
+             2. Log in and allow access.\n\
+             3. You will be redirected to {}. Copy the full address of that page\n   \
+                (or the value of the `code` parameter) and paste it to {}.",
+            self.auth_url, self.redirect_uri, destination,
+        )
+    }
+
+    /// Calls `input` once and extracts the authorization code from its value.
+    /// Returns `None` if there is no code yet. Doesn't block unless `input` does.
+    pub fn poll_once<F>(&self, input: F) -> crate::Result<Option<String>>
+    where
+        F: FnOnce() -> crate::Result<Option<String>>,
+    {
+        Ok(input()?.as_deref().and_then(extract_code))
+    }
+
+    /// Calls `input` until it returns a non-empty value or `timeout` elapses,
+    /// then extracts the authorization code from it. `input` mustn't block.
+    /// Blocks the current thread, use [poll_cancellable][DeviceLogin::poll_cancellable]
+    /// to be able to stop it from another thread.
+    pub fn poll<F>(&self, input: F, timeout: Duration) -> crate::Result<String>
+    where
+        F: FnMut() -> crate::Result<Option<String>>,
+    {
+        self.poll_cancellable(input, timeout, &AtomicBool::new(false))
+    }
+
+    /// Works like [poll][DeviceLogin::poll], but fails once `cancelled` is set,
+    /// e.g. when the user aborts the login.
+    pub fn poll_cancellable<F>(
+        &self,
+        mut input: F,
+        timeout: Duration,
+        cancelled: &AtomicBool,
+    ) -> crate::Result<String>
+    where
+        F: FnMut() -> crate::Result<Option<String>>,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err("waiting for the authorization code has been cancelled".into());
+            }
+            if let Some(code) = self.poll_once(&mut input)? {
+                return Ok(code);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err("timed out waiting for the authorization code".into());
+            }
+            thread::sleep(self.interval.min(deadline - now));
+        }
+    }
+}
+
+/// Returns an input that reads `path` if it exists and removes the file after reading.
+pub fn file_input<P: Into<PathBuf>>(path: P) -> impl FnMut() -> crate::Result<Option<String>> {
+    let path = path.into();
+    move || {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        Ok(Some(content))
+    }
+}
+
+/// Extracts a code from either the code itself or the redirect URL containing it.
+/// The `#_` suffix that Instagram appends to the code is stripped.
+pub fn extract_code(input: &str) -> Option<String> {
+    let input = input.trim();
+    let code = match Url::parse(input) {
+        Ok(url) => url
+            .query_pairs()
+            .find(|(key, _)| key == "code")
+            .map(|(_, code)| code.into_owned())?,
+        Err(_) => input.to_string(),
+    };
+
+    let code = code.trim_end_matches("#_");
+    if code.is_empty() {
+        None
+    } else {
+        Some(code.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_code() {
+        assert_eq!(super::extract_code(" AQBx#_\n"), Some("AQBx".to_string()));
+        assert_eq!(
+            super::extract_code("https://example.com/auth/?code=AQBx#_"),
+            Some("AQBx".to_string()),
+        );
+        assert_eq!(super::extract_code("https://example.com/?error=access_denied"), None);
+        assert_eq!(super::extract_code(""), None);
+    }
+
+    #[test]
+    fn poll() {
+        let mut login = DeviceLogin::new(&Secrets {
+            app_id: 1,
+            app_secret: "secret",
+            oauth_uri: Url::parse("https://example.com").unwrap(),
+        }).unwrap();
+        login.set_interval(Duration::from_millis(1));
+
+        let mut inputs = vec![Some("AQBx".to_string()), Some(String::new()), None];
+        let code = login.poll(|| Ok(inputs.pop().flatten()), Duration::from_secs(1));
+        assert_eq!(code.unwrap(), "AQBx");
+        assert!(login.poll(|| Ok(None), Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn poll_once() {
+        let login = DeviceLogin::new(&Secrets {
+            app_id: 1,
+            app_secret: "secret",
+            oauth_uri: Url::parse("https://example.com").unwrap(),
+        }).unwrap();
+        assert_eq!(login.poll_once(|| Ok(None)).unwrap(), None);
+        assert_eq!(login.poll_once(|| Ok(Some("AQBx#_".to_string()))).unwrap().unwrap(), "AQBx");
+
+        // A cancelled login fails right away instead of waiting for the timeout.
+        let cancelled = AtomicBool::new(true);
+        let code = login.poll_cancellable(|| Ok(None), Duration::from_secs(600), &cancelled);
+        assert!(code.unwrap_err().to_string().contains("cancelled"));
+    }
+}
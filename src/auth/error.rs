@@ -0,0 +1,199 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Errors of the authorization flow, telling at which stage it failed.
+
+use crate::error::ApiError;
+use std::{error::Error, fmt};
+
+/// Describes at which stage of the authorization flow a failure occurred.
+/// The underlying error, e.g. [instapi::Error][crate::Error] or [reqwest::Error],
+/// is returned by [source][Error::source]. If the API rejected the request,
+/// its details are also available via [api_error][AuthError::api_error].
+///
+/// # Examples
+/// ```no_run
+/// use instapi::auth::{AuthError, ShortLivedToken};
+/// use std::error::Error;
+///
+/// # fn example(secrets: instapi::auth::Secrets) {
+/// if let Err(e) = ShortLivedToken::new(&secrets, "code") {
+///     match e.downcast_ref::<AuthError>() {
+///         Some(e) if e.is_transport() => println!("Check the connection: {}", e),
+///         Some(e) if e.is_recoverable_by_reauth() => println!("Log in again: {}", e),
+///         Some(e) => match e.source().and_then(|e| e.downcast_ref::<instapi::Error>()) {
+///             Some(instapi::Error::ApiSunset { replacement_hint, .. }) => {
+///                 println!("Unsupported API: {}", replacement_hint)
+///             }
+///             _ => println!("Failed: {}", e),
+///         },
+///         None => println!("Failed: {}", e),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AuthError {
+    /// Failed to open the authorization page or read the code.
+    CodeRequest(Box<dyn Error>),
+    /// Failed to exchange the code for a short-lived token.
+    ShortLivedExchange(Box<dyn Error>),
+    /// The code has already been exchanged. Codes can be used only once,
+    /// so request a new one, e.g. using [log_in][super::log_in].
+    CodeAlreadyUsed(ApiError),
+    /// Failed to exchange a short-lived token for a long-lived one.
+    LongLivedExchange(Box<dyn Error>),
+    /// Failed to refresh a long-lived token.
+    Refresh(Box<dyn Error>),
+}
+
+impl AuthError {
+    /// Returns the error reported by the API, if the request has been rejected.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::CodeAlreadyUsed(error) => Some(error),
+            _ => self.api().and_then(crate::Error::api_error),
+        }
+    }
+
+    /// Checks if the error can be resolved by performing the authorization again.
+    /// Exchanges rejected by the API always require a new code, while
+    /// [transport][AuthError::is_transport] and server errors don't.
+    pub fn is_recoverable_by_reauth(&self) -> bool {
+        let recoverable = self.api().is_some_and(crate::Error::is_recoverable_by_reauth);
+        match self {
+            Self::CodeAlreadyUsed(_) => true,
+            Self::ShortLivedExchange(_) | Self::LongLivedExchange(_) => {
+                recoverable || self.api_error().is_some_and(|error| error.status < 500)
+            }
+            _ => recoverable,
+        }
+    }
+
+    /// Checks if the request couldn't be sent or its response couldn't be received,
+    /// e.g. because of a network failure. Such errors may disappear on retry.
+    pub fn is_transport(&self) -> bool {
+        self.inner().is_some_and(|e| e.is::<reqwest::Error>())
+    }
+
+    /// Wraps an error of the code exchange, detecting reused codes.
+    pub(super) fn short_lived_exchange(error: Box<dyn Error>) -> Self {
+        match error.downcast_ref::<crate::Error>().and_then(crate::Error::api_error) {
+            Some(api_error) if is_code_used(api_error) => Self::CodeAlreadyUsed(api_error.clone()),
+            _ => Self::ShortLivedExchange(error),
+        }
+    }
+
+    fn api(&self) -> Option<&crate::Error> {
+        self.inner()?.downcast_ref()
+    }
+
+    fn inner(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CodeRequest(e)
+            | Self::ShortLivedExchange(e)
+            | Self::LongLivedExchange(e)
+            | Self::Refresh(e) => Some(e.as_ref()),
+            Self::CodeAlreadyUsed(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self {
+            Self::CodeRequest(_) => "couldn't request an authorization code",
+            Self::ShortLivedExchange(_) => "couldn't exchange the code for a short-lived token",
+            Self::CodeAlreadyUsed(_) => {
+                return f.write_str("authorization code has already been used, log in again");
+            }
+            Self::LongLivedExchange(_) => "couldn't exchange the token for a long-lived one",
+            Self::Refresh(_) => "couldn't refresh the token",
+        };
+        match self.inner() {
+            Some(e) => write!(f, "{}: {}", stage, e),
+            None => f.write_str(stage),
+        }
+    }
+}
+
+impl Error for AuthError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner()
+    }
+}
+
+/// Checks if the API rejected a code because it has been exchanged before.
+fn is_code_used(error: &ApiError) -> bool {
+    // See https://developers.facebook.com/docs/graph-api/guides/error-handling.
+    const CODE_USED_SUBCODE: i64 = 36009;
+    error.subcode == Some(CODE_USED_SUBCODE)
+        || error.message.to_lowercase().contains("code has been used")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_stage() {
+        let error = AuthError::Refresh(Box::new(crate::Error::TokenExpired(None)));
+        assert_eq!(error.to_string(), "couldn't refresh the token: token has been expired");
+        assert!(error.is_recoverable_by_reauth());
+        assert!(error.api_error().is_none());
+
+        assert!(!AuthError::CodeRequest("no input".into()).is_recoverable_by_reauth());
+    }
+
+    #[test]
+    fn expose_source() {
+        let error = AuthError::LongLivedExchange(Box::new(crate::Error::TokenExpired(None)));
+        let source = error.source().and_then(|e| e.downcast_ref::<crate::Error>());
+        assert!(matches!(source, Some(crate::Error::TokenExpired(None))));
+        assert!(!error.is_transport());
+        // Expired before the exchange, so there is no API error to classify.
+        assert!(error.is_recoverable_by_reauth());
+    }
+
+    #[test]
+    fn separate_transport_errors() {
+        // Nothing listens on the discard port, so the connection is refused.
+        let transport = reqwest::blocking::get("http://127.0.0.1:9").unwrap_err();
+        let error = AuthError::ShortLivedExchange(Box::new(transport));
+        assert!(error.is_transport());
+        assert!(!error.is_recoverable_by_reauth());
+
+        let server = ApiError {
+            status: 503,
+            error_type: None,
+            code: None,
+            subcode: None,
+            message: "Service unavailable".to_string(),
+        };
+        let error = AuthError::LongLivedExchange(Box::new(crate::Error::Api(server.clone())));
+        assert!(!error.is_recoverable_by_reauth());
+        let rejected = ApiError { status: 400, ..server };
+        let error = AuthError::LongLivedExchange(Box::new(crate::Error::Api(rejected)));
+        assert!(error.is_recoverable_by_reauth());
+    }
+
+    #[test]
+    fn detect_used_code() {
+        let used = ApiError {
+            status: 400,
+            error_type: Some("OAuthException".to_string()),
+            code: Some(400),
+            subcode: None,
+            message: "This authorization code has been used".to_string(),
+        };
+        let invalid = ApiError { message: "Invalid platform app".to_string(), ..used.clone() };
+
+        let wrap = |error: ApiError| {
+            AuthError::short_lived_exchange(Box::new(crate::Error::Api(error)))
+        };
+        assert!(matches!(wrap(used), AuthError::CodeAlreadyUsed(_)));
+        assert!(matches!(wrap(invalid), AuthError::ShortLivedExchange(_)));
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Validated construction of application secrets.
+
+use super::Secrets;
+use std::fmt;
+
+use url::{Host, Url};
+
+/// Constructs [Secrets], validating the values.
+///
+/// # Examples
+/// ```
+/// let secrets = instapi::auth::Secrets::builder()
+///     .app_id("759250753489257")
+///     .app_secret("584afbb84069420aae402315ffddd360")
+///     .oauth_uri("https://example.com/auth")
+///     .build()?;
+/// # Ok::<(), instapi::auth::SecretsError>(())
+/// ```
+#[derive(Default)]
+pub struct SecretsBuilder {
+    app_id: String,
+    app_secret: &'static str,
+    oauth_uri: String,
+}
+
+/// Describes why secrets are invalid.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SecretsError {
+    /// Application ID isn't a positive number.
+    InvalidAppId(String),
+    EmptyAppSecret,
+    /// Redirect URI isn't an absolute URL.
+    InvalidOAuthUri(url::ParseError),
+    /// Redirect URI doesn't use the `https` scheme and doesn't refer to a loopback address.
+    InsecureOAuthUri(Url),
+}
+
+impl Secrets {
+    /// Returns a builder that validates secrets.
+    pub fn builder() -> SecretsBuilder {
+        SecretsBuilder::default()
+    }
+
+    /// Checks if the redirect URI uses the `https` scheme.
+    /// Plain `http` is allowed only for `localhost` and loopback IP addresses.
+    pub fn validate(&self) -> Result<(), SecretsError> {
+        let is_loopback = match self.oauth_uri.host() {
+            Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        };
+        match self.oauth_uri.scheme() {
+            "https" => Ok(()),
+            "http" if is_loopback => Ok(()),
+            _ => Err(SecretsError::InsecureOAuthUri(self.oauth_uri.clone())),
+        }
+    }
+}
+
+impl SecretsBuilder {
+    /// Sets the application ID. Accepts a number or its string representation.
+    pub fn app_id<T: ToString>(mut self, app_id: T) -> Self {
+        self.app_id = app_id.to_string();
+        self
+    }
+
+    pub fn app_secret(mut self, app_secret: &'static str) -> Self {
+        self.app_secret = app_secret;
+        self
+    }
+
+    /// Sets the redirect URI that used upon the successful authorization.
+    pub fn oauth_uri(mut self, oauth_uri: &str) -> Self {
+        self.oauth_uri = oauth_uri.to_string();
+        self
+    }
+
+    /// Validates the values and constructs secrets.
+    pub fn build(self) -> Result<Secrets, SecretsError> {
+        let app_id = match self.app_id.trim().parse() {
+            Ok(id) if id != 0 => id,
+            _ => return Err(SecretsError::InvalidAppId(self.app_id)),
+        };
+        if self.app_secret.is_empty() {
+            return Err(SecretsError::EmptyAppSecret);
+        }
+
+        let oauth_uri = Url::parse(self.oauth_uri.trim()).map_err(SecretsError::InvalidOAuthUri)?;
+        let secrets = Secrets { app_id, app_secret: self.app_secret, oauth_uri };
+        secrets.validate()?;
+        Ok(secrets)
+    }
+}
+
+impl fmt::Display for SecretsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidAppId(id) => {
+                write!(f, "application ID must be a positive number, got \"{}\"", id)
+            }
+            Self::EmptyAppSecret => f.write_str("application secret must not be empty"),
+            Self::InvalidOAuthUri(e) => write!(f, "OAuth redirect URI isn't valid: {}", e),
+            Self::InsecureOAuthUri(uri) => {
+                write!(f, "OAuth redirect URI {} must use https unless it's loopback", uri)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecretsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> SecretsBuilder {
+        Secrets::builder().app_id(1).app_secret("secret").oauth_uri("https://example.com")
+    }
+
+    #[test]
+    fn build() {
+        assert!(builder().build().is_ok());
+        assert_eq!(
+            builder().app_id("0").build().err(),
+            Some(SecretsError::InvalidAppId("0".to_string()))
+        );
+        assert_eq!(builder().app_secret("").build().err(), Some(SecretsError::EmptyAppSecret));
+        assert!(matches!(
+            builder().oauth_uri("/auth").build(),
+            Err(SecretsError::InvalidOAuthUri(_))
+        ));
+    }
+
+    #[test]
+    fn insecure_loopback() {
+        for uri in ["http://localhost:8080/auth", "http://127.0.0.1/auth", "http://[::1]/auth"] {
+            assert!(builder().oauth_uri(uri).build().is_ok(), "{}", uri);
+        }
+        for uri in ["http://example.com", "http://192.168.0.1/auth", "ftp://localhost"] {
+            assert!(matches!(
+                builder().oauth_uri(uri).build(),
+                Err(SecretsError::InsecureOAuthUri(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Verification of the `signed_request` parameter sent to the Deauthorize Callback
+//! and Data Deletion Request URLs.
+
+use std::fmt;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Verified payload of a signed request.
+#[derive(Debug, Clone)]
+pub struct SignedRequest {
+    /// ID of the user who removed the app. Delete the stored tokens of this user.
+    pub user_id: u64,
+    pub issued_at: DateTime<Utc>,
+}
+
+/// Describes why a signed request has been rejected.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SignedRequestError {
+    /// The request doesn't consist of a signature and a payload encoded using base64url.
+    Malformed,
+    /// The signature doesn't match the payload.
+    InvalidSignature,
+    UnsupportedAlgorithm(String),
+}
+
+/// Abstractions over JSON responses.
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Payload {
+        pub(super) algorithm: String,
+        pub(super) issued_at: i64,
+        /// Can be represented as a string or a number.
+        pub(super) user_id: serde_json::Value,
+    }
+}
+
+/// Verifies `signed_request` using the application secret and decodes its payload.
+///
+/// # Examples
+/// ```
+/// use instapi::auth::signed_request;
+///
+/// let request = signed_request::parse("invalid", "584afbb84069420aae402315ffddd360");
+/// assert_eq!(request.err(), Some(signed_request::SignedRequestError::Malformed));
+/// ```
+pub fn parse(signed_request: &str, app_secret: &str) -> Result<SignedRequest, SignedRequestError> {
+    let (signature, payload) = signed_request
+        .trim()
+        .split_once('.')
+        .ok_or(SignedRequestError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| SignedRequestError::Malformed)?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(app_secret.as_bytes())
+        .map_err(|_| SignedRequestError::InvalidSignature)?;
+    // The signature is calculated over the encoded payload.
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| SignedRequestError::InvalidSignature)?;
+
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| SignedRequestError::Malformed)?;
+    let payload: response::Payload =
+        serde_json::from_slice(&payload).map_err(|_| SignedRequestError::Malformed)?;
+    if !payload.algorithm.eq_ignore_ascii_case("HMAC-SHA256") {
+        return Err(SignedRequestError::UnsupportedAlgorithm(payload.algorithm));
+    }
+
+    let user_id = match payload.user_id {
+        serde_json::Value::String(id) => id.parse().ok(),
+        serde_json::Value::Number(id) => id.as_u64(),
+        _ => None,
+    };
+    Ok(SignedRequest {
+        user_id: user_id.ok_or(SignedRequestError::Malformed)?,
+        issued_at: Utc
+            .timestamp_opt(payload.issued_at, 0)
+            .single()
+            .ok_or(SignedRequestError::Malformed)?,
+    })
+}
+
+impl fmt::Display for SignedRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => f.write_str("signed request is malformed"),
+            Self::InvalidSignature => f.write_str("signature of the request is invalid"),
+            Self::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "signing algorithm {} isn't supported", algorithm)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignedRequestError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &str = "6-YNM_hG8HJF9BDpiNvvyZROHtRKX7VH0UEiPfgXPcA.eyJhbGdvcml0aG0iOiJITUFDLVNIQTI\
+        1NiIsImlzc3VlZF9hdCI6MTY1MDAwMDAwMCwidXNlcl9pZCI6IjE3ODQxNDAwMDAwMDAwMDAwIn0";
+
+    #[test]
+    fn parse_valid() {
+        let request = parse(REQUEST, "secret").unwrap();
+        assert_eq!(request.user_id, 17841400000000000);
+        assert_eq!(request.issued_at.timestamp(), 1650000000);
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert_eq!(parse(REQUEST, "other").err(), Some(SignedRequestError::InvalidSignature));
+        assert_eq!(parse("no-dot", "secret").err(), Some(SignedRequestError::Malformed));
+    }
+}
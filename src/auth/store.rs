@@ -0,0 +1,134 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! File-based storage for long-lived tokens and account settings.
+
+use super::LongLivedToken;
+use crate::settings::AccountSettings;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Persists a serialized [LongLivedToken] to a JSON file.
+/// [AccountSettings] are kept in a sibling file with the `settings.json` extension.
+///
+/// # Examples
+/// ```no_run
+/// let store = instapi::auth::TokenStore::new("token.json");
+/// let token = store.load()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct TokenStore {
+    path: PathBuf,
+}
+
+impl TokenStore {
+    /// Constructs a store that keeps a token in the file located at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns path to the token file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Checks if the token file exists.
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Reads and deserializes a token. The token isn't checked for validity.
+    pub fn load(&self) -> crate::Result<LongLivedToken> {
+        let json = fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(json.as_str())?)
+    }
+
+    /// Serializes and writes `token`, overwriting the existing file.
+    /// On Unix systems read-write access is limited to the owner only.
+    pub fn save(&self, token: &LongLivedToken) -> crate::Result<()> {
+        let json = serde_json::to_string(token)?;
+        fs::write(&self.path, json)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Reads settings of the account. Returns default settings if they haven't been saved.
+    pub fn load_settings(&self) -> crate::Result<AccountSettings> {
+        match fs::read_to_string(self.settings_path()) {
+            Ok(json) => Ok(serde_json::from_str(json.as_str())?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(AccountSettings::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Serializes and writes `settings`, overwriting the existing ones.
+    pub fn save_settings(&self, settings: &AccountSettings) -> crate::Result<()> {
+        fs::write(self.settings_path(), serde_json::to_string_pretty(settings)?)?;
+        Ok(())
+    }
+
+    /// Removes the token and settings files. Does nothing if the files don't exist.
+    pub fn delete(&self) -> io::Result<()> {
+        remove_file(&self.path)?;
+        remove_file(&self.settings_path())
+    }
+
+    fn settings_path(&self) -> PathBuf {
+        self.path.with_extension("settings.json")
+    }
+}
+
+/// Removes a file, ignoring the error if it doesn't exist.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn save_load_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::new(dir.path().join("token.json"));
+        let token = LongLivedToken {
+            access_token: "token".to_string(),
+            user_id: 1,
+            expiration_date: Utc::now(),
+            scopes: None,
+            token_type: String::new(),
+            expires_in: 0,
+        };
+
+        store.save(&token).unwrap();
+        assert_eq!(store.load().unwrap().access_token, token.access_token);
+        store.delete().unwrap();
+        assert!(!store.exists());
+        // Deleting a missing file isn't an error.
+        assert!(store.delete().is_ok());
+    }
+
+    #[test]
+    fn settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::new(dir.path().join("token.json"));
+        assert!(!store.load_settings().unwrap().skip_albums);
+
+        store.save_settings(&AccountSettings { skip_albums: true, ..Default::default() }).unwrap();
+        assert!(store.load_settings().unwrap().skip_albums);
+        store.delete().unwrap();
+        assert!(!store.load_settings().unwrap().skip_albums);
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! HTTP client that performs API requests.
+
+use crate::Error;
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use reqwest::{
+    blocking::{RequestBuilder, Response},
+    header::{IF_RANGE, RANGE},
+};
+use sha2::Sha256;
+use url::Url;
+
+/// Callback that receives an authentication error.
+type AuthFailureCallback = dyn Fn(&Error) + Send + Sync;
+/// Callback that receives a warning message.
+type WarningCallback = dyn Fn(&str) + Send + Sync;
+
+/// Performs API requests and applies the client-wide settings to them.
+/// Token exchange requests don't use a client, as they already contain the app secret.
+///
+/// # Examples
+/// ```
+/// let mut client = instapi::client::Client::new()?;
+/// client.set_appsecret_proof(Some("584afbb84069420aae402315ffddd360"));
+/// # Ok::<(), reqwest::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::blocking::Client,
+    app_secret: Option<String>,
+    on_auth_failure: Option<Arc<AuthFailureCallback>>,
+    on_warning: Option<Arc<WarningCallback>>,
+}
+
+impl Client {
+    /// Constructs a client with the default settings.
+    /// Returns an error if a TLS backend can't be initialized.
+    pub fn new() -> reqwest::Result<Self> {
+        Ok(Self {
+            http: reqwest::blocking::Client::builder().build()?,
+            app_secret: None,
+            on_auth_failure: None,
+            on_warning: None,
+        })
+    }
+
+    /// Enables signing of requests using `app_secret`: an `appsecret_proof` parameter
+    /// (HMAC-SHA256 of the access token) is appended to each request that contains a token.
+    /// Pass `None` to disable signing.
+    pub fn set_appsecret_proof(&mut self, app_secret: Option<&str>) {
+        self.app_secret = app_secret.map(str::to_string);
+    }
+
+    /// Registers `callback` that invoked when the API rejects a token, i.e. when a request fails
+    /// with an [Error] that [recoverable by reauthorization][Error::is_recoverable_by_reauth].
+    /// The callback runs before the error is returned, so it can trigger a login prompt.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut client = instapi::client::Client::new()?;
+    /// client.on_auth_failure(|e| eprintln!("Please log in again: {}", e));
+    /// # Ok::<(), reqwest::Error>(())
+    /// ```
+    pub fn on_auth_failure<F>(&mut self, callback: F)
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.on_auth_failure = Some(Arc::new(callback));
+    }
+
+    /// Registers `callback` that invoked with a message when the API reports a condition
+    /// the application should act on, e.g. when an [endpoint has been shut down][Error::ApiSunset].
+    pub fn on_warning<F>(&mut self, callback: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_warning = Some(Arc::new(callback));
+    }
+
+    /// Sends a GET request. Unsuccessful responses are converted to [Error].
+    pub(crate) fn get(&self, url: Url) -> crate::Result<Response> {
+        self.send(self.http.get(self.sign(url)))
+    }
+
+    /// Sends a HEAD request to a file outside of the API, e.g. on the CDN, so the request
+    /// isn't signed. Unsuccessful responses are converted to [reqwest::Error].
+    pub(crate) fn head(&self, url: Url) -> crate::Result<Response> {
+        Ok(self.http.head(url).send()?.error_for_status()?)
+    }
+
+    /// Works like [head][Client::head], but sends a GET request to retrieve the file.
+    pub(crate) fn get_file(&self, url: Url) -> crate::Result<Response> {
+        Ok(self.http.get(url).send()?.error_for_status()?)
+    }
+
+    /// Requests the file content starting at byte `start`. Servers that don't support ranges
+    /// respond with the whole content, so the status should be checked. If `validator`
+    /// (an ETag or a Last-Modified date) is passed, the whole content is sent as well
+    /// if it's changed since.
+    pub(crate) fn get_file_from(
+        &self,
+        url: Url,
+        start: u64,
+        validator: Option<&str>,
+    ) -> crate::Result<Response> {
+        let mut request = self.http.get(url);
+        if start > 0 {
+            request = request.header(RANGE, format!("bytes={}-", start));
+            if let Some(validator) = validator {
+                request = request.header(IF_RANGE, validator);
+            }
+        }
+        Ok(request.send()?.error_for_status()?)
+    }
+
+    /// Sends a POST request with `form` as the body. Unsuccessful responses are converted
+    /// to [Error].
+    pub(crate) fn post(&self, url: Url, form: &[(&str, &str)]) -> crate::Result<Response> {
+        self.send(self.http.post(self.sign(url)).form(form))
+    }
+
+    /// Sends a DELETE request. Unsuccessful responses are converted to [Error].
+    pub(crate) fn delete(&self, url: Url) -> crate::Result<Response> {
+        self.send(self.http.delete(self.sign(url)))
+    }
+
+    fn send(&self, request: RequestBuilder) -> crate::Result<Response> {
+        let result = crate::error::check(request.send()?);
+        if let Some(e) = result.as_ref().err().and_then(|e| e.downcast_ref::<Error>()) {
+            match (&self.on_auth_failure, &self.on_warning) {
+                (Some(callback), _) if e.is_recoverable_by_reauth() => callback(e),
+                (_, Some(callback)) if matches!(e, Error::ApiSunset { .. }) => {
+                    callback(&e.to_string())
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    /// Appends the `appsecret_proof` parameter if signing is enabled
+    /// and `url` contains an access token, but isn't signed yet.
+    fn sign(&self, mut url: Url) -> Url {
+        let app_secret = match &self.app_secret {
+            Some(secret) => secret,
+            None => return url,
+        };
+
+        let mut token = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "access_token" => token = Some(value.into_owned()),
+                "appsecret_proof" => return url,
+                _ => {}
+            }
+        }
+
+        if let Some(token) = token {
+            let proof = appsecret_proof(app_secret, &token);
+            url.query_pairs_mut().append_pair("appsecret_proof", &proof);
+        }
+        url
+    }
+}
+
+/// Computes the hex-encoded HMAC-SHA256 of `access_token` using `app_secret` as a key.
+fn appsecret_proof(app_secret: &str, access_token: &str) -> String {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(app_secret.as_bytes()).unwrap();
+    mac.update(access_token.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appsecret_proof() {
+        // Reference value is calculated using OpenSSL.
+        assert_eq!(
+            super::appsecret_proof("secret", "token"),
+            "e941110e3d2bfe82621f0e3e1434730d7305d106c5f68c87165d0b27a4611a4a",
+        );
+    }
+
+    #[test]
+    fn sign() {
+        let mut client = Client::new().unwrap();
+        let url = Url::parse("https://example.com/?access_token=token").unwrap();
+        assert_eq!(client.sign(url.clone()), url);
+
+        client.set_appsecret_proof(Some("secret"));
+        let signed = client.sign(url);
+        assert_eq!(signed.query_pairs().filter(|(key, _)| key == "appsecret_proof").count(), 1);
+        // Already signed URL isn't changed.
+        assert_eq!(client.sign(signed.clone()), signed);
+    }
+}
@@ -0,0 +1,1456 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Downloading of media files, including album contents.
+
+#[cfg(any(feature = "zip", feature = "tar-zstd"))]
+mod archive;
+mod cache;
+mod collision;
+mod dedup;
+mod estimate;
+mod manifest;
+mod mirror;
+mod progress;
+mod queue;
+mod report;
+mod sidecar;
+mod sink;
+mod state;
+mod template;
+mod throttle;
+
+#[cfg(any(feature = "zip", feature = "tar-zstd"))]
+pub use archive::ArchiveFormat;
+pub use cache::ContentCache;
+pub use collision::{CollisionPolicy, ExistingFiles};
+pub use dedup::DedupMode;
+pub use estimate::Estimate;
+pub use manifest::{Manifest, ManifestFormat, Mismatch};
+pub use mirror::PruneMode;
+pub use progress::Progress;
+pub use report::{DownloadOutcome, DownloadReport, Failure, ItemReport};
+#[cfg(feature = "s3")]
+pub use sink::S3Sink;
+#[cfg(feature = "webdav")]
+pub use sink::WebDavSink;
+pub use sink::{LocalSink, StorageSink};
+pub use state::SyncState;
+pub use template::{FilenameTemplate, TemplateError};
+
+use crate::{
+    auth::Token,
+    client::Client,
+    fairness::Turns,
+    policy::{Action, SyncPolicy},
+    user::{self, Media, MediaType, Profile},
+    util,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+use collision::{Names, SavedFiles};
+use dedup::Contents;
+use reqwest::{
+    blocking::Response,
+    header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+    StatusCode,
+};
+use progress::{Counters, ProgressWriter};
+use queue::Queue;
+use sink::Target;
+use threadpool::ThreadPool;
+use throttle::Throttle;
+use url::Url;
+
+/// Number of files that downloaded at the same time by default. Downloads are bound
+/// by the network rather than CPU, so it doesn't depend on the number of cores.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Number of files that may wait for a worker by default.
+pub const DEFAULT_QUEUE_SIZE: usize = 64;
+
+/// Which files of videos are downloaded.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum ThumbnailMode {
+    /// Only the videos themselves.
+    #[default]
+    Off,
+    /// Thumbnails are saved next to the videos as `<name>.thumbnail.<extension>`.
+    Alongside,
+    /// Thumbnails are saved instead of the videos, which is much cheaper.
+    /// Videos without thumbnails are skipped.
+    Only,
+}
+
+/// Constructs a file name (without an extension) of a media item
+/// given the ID of the album it's in.
+type Naming = dyn Fn(&Media, Option<u64>) -> String + Send + Sync;
+/// Receives the result of an item as soon as it's processed.
+type ItemCallback = dyn Fn(&ItemReport) + Send + Sync;
+type ProgressCallback = dyn Fn(&Progress) + Send + Sync;
+
+/// Downloads media files to a directory using a pool of threads.
+/// Album contents are gathered using the profile (unless they're embedded)
+/// and saved to a subdirectory named after the album.
+///
+/// Downloads may be interrupted at any point, e.g. by a panic or a killed process.
+/// Content is written to `.part` files, which are resumed by the next download,
+/// and a file appears under its final name, in the manifest and in the [SyncState]
+/// only once it's complete.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::LongLivedToken, download::Downloader, user::Profile};
+///
+/// # fn run(token: LongLivedToken) -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = Profile::new(token)?;
+/// let report = Downloader::new(profile.clone(), "backup").download_all(profile.media()?);
+/// for item in report.failed() {
+///     eprintln!("Couldn't download {}: {:?}", item.media().id(), item.outcome());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Downloader<T> {
+    profile: Profile<T>,
+    destination: PathBuf,
+    sink: Arc<dyn StorageSink>,
+    concurrency: usize,
+    queue_size: usize,
+    max_in_flight: usize,
+    request_delay: Duration,
+    retries: u32,
+    retry_backoff: Duration,
+    checksums: bool,
+    sidecars: bool,
+    skip_albums: bool,
+    collisions: CollisionPolicy,
+    existing: ExistingFiles,
+    thumbnails: ThumbnailMode,
+    dedup: DedupMode,
+    cache: Option<Arc<ContentCache>>,
+    policy: Arc<SyncPolicy>,
+    naming: Arc<Naming>,
+    layout: Option<FilenameTemplate>,
+    on_item: Option<Arc<ItemCallback>>,
+    on_progress: Option<Arc<ProgressCallback>>,
+}
+
+/// State of a single [download_all][Downloader::download_all] call.
+struct Batch {
+    pool: ThreadPool,
+    target: Arc<Target>,
+    /// Directories that have been created during the batch.
+    dirs: RefCell<HashSet<PathBuf>>,
+    tx: mpsc::Sender<(usize, ItemReport)>,
+    counters: Arc<Counters>,
+    throttle: Arc<Throttle>,
+    queue: Arc<Queue>,
+    names: RefCell<Names>,
+    contents: Arc<Contents>,
+    saved: Arc<SavedFiles>,
+}
+
+/// Everything a worker thread needs to download a file.
+struct Job {
+    client: Client,
+    target: Arc<Target>,
+    retries: u32,
+    retry_backoff: Duration,
+    checksums: bool,
+    sidecars: bool,
+    existing: ExistingFiles,
+    thumbnails: ThumbnailMode,
+    dedup: DedupMode,
+    cache: Option<Arc<ContentCache>>,
+    policy: Arc<SyncPolicy>,
+    on_item: Option<Arc<ItemCallback>>,
+    on_progress: Option<Arc<ProgressCallback>>,
+    counters: Arc<Counters>,
+    throttle: Arc<Throttle>,
+    contents: Arc<Contents>,
+    saved: Arc<SavedFiles>,
+}
+
+impl<T: Token> Downloader<T> {
+    /// Constructs a downloader that saves files to `destination`.
+    /// It uses [DEFAULT_CONCURRENCY] threads without delays between requests, retries
+    /// failed files twice, the default [policy][SyncPolicy] and [default_filename].
+    pub fn new<P: Into<PathBuf>>(profile: Profile<T>, destination: P) -> Self {
+        let destination = destination.into();
+        Self {
+            profile,
+            sink: Arc::new(LocalSink::new(destination.clone())),
+            destination,
+            concurrency: DEFAULT_CONCURRENCY,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            max_in_flight: usize::MAX,
+            request_delay: Duration::ZERO,
+            retries: 2,
+            retry_backoff: Duration::from_secs(1),
+            checksums: false,
+            sidecars: false,
+            skip_albums: false,
+            collisions: CollisionPolicy::default(),
+            existing: ExistingFiles::default(),
+            thumbnails: ThumbnailMode::default(),
+            dedup: DedupMode::default(),
+            cache: None,
+            policy: Arc::new(SyncPolicy::default()),
+            naming: Arc::new(|media, _| default_filename(media)),
+            layout: None,
+            on_item: None,
+            on_progress: None,
+        }
+    }
+
+    /// Returns the directory that files are downloaded to. It keeps the [SyncState]
+    /// and manifests even if files are put to another [sink][Downloader::set_sink].
+    pub fn destination(&self) -> &Path {
+        &self.destination
+    }
+
+    /// Sets the number of files that downloaded at the same time. At least one is used.
+    pub fn set_concurrency(&mut self, threads: usize) {
+        self.concurrency = threads.max(1);
+    }
+
+    /// Sets the number of files that may wait for a worker while items are being gathered
+    /// by [download_iter][Downloader::download_iter]. At least one file is queued.
+    pub fn set_queue_size(&mut self, size: usize) {
+        self.queue_size = size.max(1);
+    }
+
+    /// Limits the number of requests that sent to the same host at the same time.
+    /// Unlike [set_concurrency][Downloader::set_concurrency], it doesn't limit requests to
+    /// different hosts. At least one request is allowed.
+    pub fn set_max_in_flight(&mut self, requests: usize) {
+        self.max_in_flight = requests.max(1);
+    }
+
+    /// Sets the minimum delay between the starts of requests to the same host.
+    pub fn set_request_delay(&mut self, delay: Duration) {
+        self.request_delay = delay;
+    }
+
+    /// Sets how many times a file is downloaded again after a failure caused by the network
+    /// or by the server. Errors like a full disk or missing content aren't retried.
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Sets the delay before the first retry. It's doubled before each next one.
+    pub fn set_retry_backoff(&mut self, backoff: Duration) {
+        self.retry_backoff = backoff;
+    }
+
+    /// Computes SHA-256 checksums of downloaded files, so a [Manifest] can be
+    /// [written][Downloader::update_manifest] after the download.
+    pub fn set_checksums(&mut self, checksums: bool) {
+        self.checksums = checksums;
+    }
+
+    /// Writes metadata of each downloaded file, album and [metadata-only][Action::MetadataOnly]
+    /// item to a `<name>.json` sidecar file next to it, so the archive remains searchable
+    /// without the API. It includes the ID of the album the item is in.
+    pub fn set_sidecars(&mut self, sidecars: bool) {
+        self.sidecars = sidecars;
+    }
+
+    /// Don't download album contents, albums are reported as [skipped][DownloadOutcome::Skipped].
+    pub fn set_skip_albums(&mut self, skip_albums: bool) {
+        self.skip_albums = skip_albums;
+    }
+
+    /// Sets what to do with items whose names collide. Suffixes are appended by default.
+    pub fn set_collision_policy(&mut self, collisions: CollisionPolicy) {
+        self.collisions = collisions;
+    }
+
+    /// Sets what to do with items whose files have been saved by a previous download.
+    /// They're downloaded again and overwritten by default. Files are detected
+    /// in [local][StorageSink::local_dir] sinks only.
+    pub fn set_existing_files(&mut self, existing: ExistingFiles) {
+        self.existing = existing;
+    }
+
+    /// Sets whether thumbnails of videos are downloaded along with them or instead of them.
+    /// Thumbnails are available only if the
+    /// [thumbnail URL][crate::user::MediaField::ThumbnailUrl] has been requested.
+    pub fn set_thumbnails(&mut self, thumbnails: ThumbnailMode) {
+        self.thumbnails = thumbnails;
+    }
+
+    /// Sets what to do with files whose content matches a file saved earlier during
+    /// the same download, e.g. media posted both standalone and in an album.
+    /// Duplicates are listed by [DownloadReport::duplicates]. Links are created
+    /// in [local][StorageSink::local_dir] sinks only, other sinks keep duplicates as they are.
+    pub fn set_dedup(&mut self, dedup: DedupMode) {
+        self.dedup = dedup;
+    }
+
+    /// Sets the cache that files are taken from instead of the CDN if they have been
+    /// downloaded before. Downloaded files are added to it.
+    pub fn set_cache(&mut self, cache: Option<ContentCache>) {
+        self.cache = cache.map(Arc::new);
+    }
+
+    /// Sets the storage that files are put to instead of the destination, e.g. object storage.
+    /// Unless the sink is [local][StorageSink::local_dir], files are downloaded
+    /// to the destination first, so interrupted downloads are resumed from there, and moved
+    /// to the sink once they're complete. Paths in reports are relative to the root
+    /// of such sinks.
+    pub fn set_sink<S: StorageSink + 'static>(&mut self, sink: S) {
+        self.sink = Arc::new(sink);
+    }
+
+    pub fn set_policy(&mut self, policy: SyncPolicy) {
+        self.policy = Arc::new(policy);
+    }
+
+    /// Sets a function that constructs names of files and album directories without
+    /// extensions. Names are [sanitized][util::sanitize_filename].
+    pub fn set_naming<F>(&mut self, naming: F)
+    where
+        F: Fn(&Media) -> String + Send + Sync + 'static,
+    {
+        self.naming = Arc::new(move |media, _| naming(media));
+    }
+
+    /// Constructs names of files and album directories using `template`.
+    /// Names are [sanitized][util::sanitize_filename].
+    pub fn set_template(&mut self, template: FilenameTemplate) {
+        self.naming = Arc::new(move |media, album_id| template.render(media, album_id));
+    }
+
+    /// Sets a template of the subdirectories of the destination that items are saved to,
+    /// e.g. `{timestamp:%Y}/{timestamp:%m}`. Slashes separate directories, each of them
+    /// is [sanitized][util::sanitize_filename]. Album contents are saved to the directories
+    /// of their albums. By default all items are saved to the destination directly.
+    pub fn set_layout(&mut self, layout: Option<FilenameTemplate>) {
+        self.layout = layout;
+    }
+
+    /// Registers `callback` that invoked with the result of each item as soon as it's
+    /// processed. It may be invoked from several threads at once.
+    pub fn on_item<F>(&mut self, callback: F)
+    where
+        F: Fn(&ItemReport) + Send + Sync + 'static,
+    {
+        self.on_item = Some(Arc::new(callback));
+    }
+
+    /// Registers `callback` that is invoked after each chunk of a file is written, so
+    /// progress bars can be rendered. It may be invoked from several threads at once.
+    pub fn on_progress<F>(&mut self, callback: F)
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(callback));
+    }
+
+    /// Streams content of `media` to `writer` chunk by chunk, without applying the policy.
+    /// Returns the number of written bytes.
+    pub fn download_to<W: io::Write>(&self, media: &Media, writer: &mut W) -> crate::Result<u64> {
+        let mut response = self.profile.client().get_file(media.media_url().clone())?;
+        Ok(io::copy(&mut response, writer)?)
+    }
+
+    /// Downloads a single media item. Works like [download_all][Downloader::download_all].
+    pub fn download(&self, media: Media) -> DownloadReport {
+        self.download_all(std::iter::once(media))
+    }
+
+    /// Downloads contents of `media` and blocks until all of them are processed.
+    /// Failures don't stop the download, they're recorded in the report.
+    pub fn download_all<I: IntoIterator<Item = Media>>(&self, media: I) -> DownloadReport {
+        self.download_iter(media.into_iter().map(Ok))
+    }
+
+    /// Works like [download_all][Downloader::download_all], but items are downloaded as soon
+    /// as they're yielded, e.g. by [media_iter][crate::user::Profile::media_iter], so pages
+    /// are gathered while previous items are being downloaded. Gathering pauses while
+    /// the [queue][Downloader::set_queue_size] is full.
+    /// Errors of `media` are recorded in the report.
+    pub fn download_iter<I>(&self, media: I) -> DownloadReport
+    where
+        I: IntoIterator<Item = crate::Result<Media>>,
+    {
+        let target = self.target();
+        let root = target.root().to_path_buf();
+        self.process(target, media.into_iter().map(|media| Ok((media?, None, root.clone()))))
+    }
+
+    /// Downloads the items published since the previous sync to the destination and updates
+    /// the [SyncState] stored in it. Pagination stops once the items of the previous syncs
+    /// are reached, the items that have been downloaded already are skipped.
+    ///
+    /// Fails if the state can't be read or saved. Failed downloads are recorded in the report
+    /// and tried again during the next sync. If media can't be retrieved, the errors are
+    /// recorded in the report [errors][DownloadReport::errors] along with the items processed
+    /// before them. The processed items are recorded in the state, but the position of the sync
+    /// isn't moved, so the next sync gathers the rest of the items again.
+    pub fn sync(&self) -> crate::Result<DownloadReport> {
+        let path = self.destination.join(SyncState::FILENAME);
+        let mut state = SyncState::load(&path)?;
+
+        let media = self
+            .profile
+            .media_iter()
+            .take_while(|item| !matches!(item, Ok(item) if state.reaches_known(item)))
+            .filter(|item| !matches!(item, Ok(item) if state.is_known(item)));
+        let report = self.download_iter(media);
+        let root = self.target().root().to_path_buf();
+        // Items of the pages that failed to be retrieved would be considered known.
+        if report.errors().is_empty() {
+            state.update(&report, &root);
+        } else {
+            state.record(&report, &root);
+        }
+        fs::create_dir_all(&self.destination)?;
+        state.save(&path)?;
+        Ok(report)
+    }
+
+    /// Reconciles the destination with the user's media: local copies of the items
+    /// recorded by [sync][Downloader::sync] that no longer exist remotely are removed
+    /// according to `mode` along with their sidecars. Their checksums are removed
+    /// from manifests too.
+    /// Returns the paths of the removed files and album directories.
+    ///
+    /// Fails if the media can't be retrieved or the [sink][Downloader::set_sink] isn't
+    /// [local][StorageSink::local_dir]. Nothing is removed if no media is retrieved
+    /// while some items are recorded, as it's likely a failure of the API.
+    pub fn prune(&self, mode: PruneMode) -> crate::Result<Vec<PathBuf>> {
+        let target = self.target();
+        if !target.is_local() {
+            return Err("files can't be removed from a sink that isn't local".into());
+        }
+        let root = target.root();
+        let state_path = self.destination.join(SyncState::FILENAME);
+        let mut state = SyncState::load(&state_path)?;
+        let summary = self.profile.media_summary()?;
+        let mut remote: HashSet<u64> = summary.media().iter().map(Media::id).collect();
+        // Malformed items still exist remotely.
+        remote.extend(summary.errors().iter().filter_map(|e| e.id().parse::<u64>().ok()));
+        if remote.is_empty() && !state.paths.is_empty() {
+            return Err("no media retrieved, refusing to remove all files".into());
+        }
+
+        let deleted: Vec<(u64, String)> = state
+            .paths
+            .iter()
+            .filter(|(id, _)| !remote.contains(id))
+            .map(|(id, path)| (*id, path.clone()))
+            .collect();
+        let mut removed = Vec::new();
+        let result = deleted.into_iter().try_for_each(|(id, path)| {
+            mirror::remove(root, &path, mode)?;
+            mirror::remove(root, &format!("{}.json", path), mode)?;
+            state.paths.remove(&id);
+            state.downloaded.remove(&id);
+            removed.push(path);
+            io::Result::Ok(())
+        });
+
+        // Items removed before a failure are recorded anyway.
+        for format in [ManifestFormat::Json, ManifestFormat::Sha256Sum] {
+            let path = self.destination.join(format.filename());
+            if path.exists() {
+                let mut manifest = Manifest::load(&path, format)?;
+                removed.iter().for_each(|removed| manifest.remove(removed));
+                manifest.save(&path, format)?;
+            }
+        }
+        state.save(&state_path)?;
+        result?;
+        Ok(removed.iter().map(|path| root.join(path)).collect())
+    }
+
+    /// Downloads items that failed during a previous run, e.g. [persisted][Failure]
+    /// to a file. Fresh metadata is retrieved using the profile, album contents are saved
+    /// to the directories of their albums. Failed albums are downloaded entirely.
+    ///
+    /// Items whose metadata can't be retrieved, e.g. deleted ones, are recorded
+    /// in the report [errors][DownloadReport::errors] and the rest are downloaded.
+    pub fn retry_failures(&self, failures: &[Failure]) -> DownloadReport {
+        let target = self.target();
+        let root = target.root().to_path_buf();
+        // Directories of albums, which are retrieved once for all of their items.
+        let mut album_dirs: HashMap<u64, Result<PathBuf, String>> = HashMap::new();
+        let items = failures.iter().map(|failure| {
+            let dir = match failure.parent_id {
+                Some(parent_id) => {
+                    let dir = album_dirs.entry(parent_id).or_insert_with(|| {
+                        let album = self.profile.media_by_id(parent_id).map_err(|e| e.to_string())?;
+                        let album_parent = self.item_dir(&album, None, &root);
+                        Ok(self.album_dir(&album, None, &album_parent))
+                    });
+                    dir.clone()
+                        .map_err(|e| format!("couldn't retrieve album {}: {}", parent_id, e))?
+                }
+                None => root.clone(),
+            };
+            let media = self
+                .profile
+                .media_by_id(failure.media_id)
+                .map_err(|e| format!("couldn't retrieve item {}: {}", failure.media_id, e))?;
+            Ok((media, failure.parent_id, dir))
+        });
+        self.process(target, items)
+    }
+
+    /// Adds checksums of the files downloaded to the destination to the manifest stored
+    /// in it, creating the manifest if needed. Returns the path to the manifest.
+    pub fn update_manifest(
+        &self,
+        report: &DownloadReport,
+        format: ManifestFormat,
+    ) -> crate::Result<PathBuf> {
+        let path = self.destination.join(format.filename());
+        let mut manifest =
+            if path.exists() { Manifest::load(&path, format)? } else { Manifest::new() };
+        manifest.merge(report.manifest(self.target().root()));
+        manifest.save(&path, format)?;
+        Ok(path)
+    }
+
+    /// Checks files in the destination against the manifest stored in it.
+    /// Returns files that are missing or whose content has been changed.
+    pub fn verify(&self, format: ManifestFormat) -> crate::Result<Vec<Mismatch>> {
+        let manifest = Manifest::load(self.destination.join(format.filename()), format)?;
+        Ok(manifest.verify(&self.destination))
+    }
+
+    /// Processes media items along with IDs of their albums and directories to save them to
+    /// in `target`. Errors of gathering the items are recorded in the report.
+    fn process<I>(&self, target: Target, items: I) -> DownloadReport
+    where
+        I: IntoIterator<Item = crate::Result<(Media, Option<u64>, PathBuf)>>,
+    {
+        let mut errors = Vec::new();
+        // Names of the files saved by previous syncs are reserved for their items.
+        let names = match SyncState::load(self.destination.join(SyncState::FILENAME)) {
+            Ok(state) => Names::recorded(&state, target.root()),
+            Err(e) => {
+                errors.push(format!("couldn't read the sync state: {}", e));
+                Names::default()
+            }
+        };
+        let (tx, rx) = mpsc::channel();
+        let batch = Batch {
+            pool: ThreadPool::new(self.concurrency),
+            target: Arc::new(target),
+            dirs: RefCell::default(),
+            tx,
+            counters: Arc::new(Counters::default()),
+            throttle: Arc::new(Throttle::new(self.max_in_flight, self.request_delay)),
+            queue: Arc::new(Queue::new(self.queue_size)),
+            names: RefCell::new(names),
+            contents: Arc::default(),
+            saved: Arc::default(),
+        };
+        for item in items {
+            match item {
+                Ok((media, parent_id, dir)) => self.schedule(media, parent_id, &dir, &batch),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        // Results are received until all workers drop their senders.
+        drop(batch);
+        let mut items: Vec<(usize, ItemReport)> = rx.iter().collect();
+        items.sort_by_key(|(index, _)| *index);
+        DownloadReport::new(items.into_iter().map(|(_, item)| item).collect()).with_errors(errors)
+    }
+
+    /// Processes `media` according to the policy: reports it right away or downloads it on
+    /// the pool. Albums are expanded into their contents. Items are numbered in order
+    /// of scheduling, which keeps the order of items in the report.
+    fn schedule(&self, media: Media, parent_id: Option<u64>, dir: &Path, batch: &Batch) {
+        let index = batch.counters.scheduled_items.fetch_add(1, Ordering::Relaxed);
+        let report = |media, outcome| {
+            let item = ItemReport::new(media, parent_id, outcome, 0);
+            self.job(batch).notify(&item);
+            batch.tx.send((index, item)).ok();
+        };
+
+        let is_album = media.media_type() == MediaType::CarouselAlbum;
+        let action = self.policy.action(&media, None);
+        let dir = &self.item_dir(&media, parent_id, dir);
+        // Items that produce files or directories.
+        let needs_dir = match action {
+            Action::Skip => false,
+            Action::MetadataOnly => self.sidecars,
+            Action::Download => !is_album || !self.skip_albums,
+        };
+        let name = if needs_dir {
+            let name = util::sanitize_filename(&(self.naming)(&media, parent_id));
+            match batch.names.borrow_mut().take(dir, name, media.id(), self.collisions) {
+                Ok(Some(name)) => name,
+                Ok(None) => return report(media, DownloadOutcome::Skipped),
+                Err(e) => return report(media, DownloadOutcome::Failed(e)),
+            }
+        } else {
+            String::new()
+        };
+        // Directories are created here, so workers don't race to create the same ones.
+        if needs_dir {
+            if let Err(e) = batch.target.create_dirs(dir, &mut batch.dirs.borrow_mut()) {
+                return report(media, DownloadOutcome::Failed(e.to_string()));
+            }
+        }
+
+        match action {
+            Action::Skip => report(media, DownloadOutcome::Skipped),
+            Action::MetadataOnly if self.sidecars => {
+                match sidecar::put(&batch.target, &dir.join(name), &media, parent_id) {
+                    Ok(()) => report(media, DownloadOutcome::MetadataOnly),
+                    Err(e) => report(media, DownloadOutcome::Failed(e.to_string())),
+                }
+            }
+            Action::MetadataOnly => report(media, DownloadOutcome::MetadataOnly),
+            Action::Download if is_album && self.skip_albums => {
+                report(media, DownloadOutcome::Skipped)
+            }
+            Action::Download if is_album => {
+                let result = self.prepare_album(&media, parent_id, &dir.join(name), batch);
+                match result {
+                    Ok((album_dir, children)) => {
+                        report(media.clone(), DownloadOutcome::Downloaded(album_dir.clone()));
+                        for child in children {
+                            self.schedule(child, Some(media.id()), &album_dir, batch);
+                        }
+                    }
+                    Err(e) => report(media, DownloadOutcome::Failed(e.to_string())),
+                }
+            }
+            Action::Download => {
+                let job = self.job(batch);
+                let path = dir.join(name);
+                let tx = batch.tx.clone();
+                // Gathering pauses while the queue is full.
+                batch.queue.push();
+                let queue = Arc::clone(&batch.queue);
+                batch.pool.execute(move || {
+                    queue.pop();
+                    let item = job.process(media, parent_id, &path);
+                    job.notify(&item);
+                    tx.send((index, item)).ok();
+                });
+            }
+        }
+    }
+
+    /// Creates the directory of `album` at `album_dir` and gathers the album contents.
+    /// `parent_id` is the ID of the album this one is in, it's written to the sidecar.
+    fn prepare_album(
+        &self,
+        album: &Media,
+        parent_id: Option<u64>,
+        album_dir: &Path,
+        batch: &Batch,
+    ) -> crate::Result<(PathBuf, Vec<Media>)> {
+        let children = match album.embedded_children() {
+            Some(children) => children.to_vec(),
+            None => self.profile.album(album)?,
+        };
+        batch.target.create_dirs(album_dir, &mut batch.dirs.borrow_mut())?;
+        if self.sidecars {
+            sidecar::put(&batch.target, album_dir, album, parent_id)?;
+        }
+        Ok((album_dir.to_path_buf(), children))
+    }
+
+    /// Returns the directory inside of `dir` that `media` is saved to according to the layout.
+    /// Album contents are saved to `dir` itself.
+    fn item_dir(&self, media: &Media, parent_id: Option<u64>, dir: &Path) -> PathBuf {
+        match &self.layout {
+            Some(layout) if parent_id.is_none() => layout
+                .render(media, parent_id)
+                .split('/')
+                .filter(|component| !component.is_empty())
+                .fold(dir.to_path_buf(), |path, component| {
+                    path.join(util::sanitize_filename(component))
+                }),
+            _ => dir.to_path_buf(),
+        }
+    }
+
+    fn album_dir(&self, album: &Media, parent_id: Option<u64>, dir: &Path) -> PathBuf {
+        dir.join(util::sanitize_filename(&(self.naming)(album, parent_id)))
+    }
+
+    /// Returns the target that files are put to: the sink and the directories.
+    fn target(&self) -> Target {
+        Target::new(Arc::clone(&self.sink), &self.destination)
+    }
+
+    fn job(&self, batch: &Batch) -> Job {
+        Job {
+            client: self.profile.client().clone(),
+            target: Arc::clone(&batch.target),
+            retries: self.retries,
+            retry_backoff: self.retry_backoff,
+            checksums: self.checksums,
+            sidecars: self.sidecars,
+            existing: self.existing,
+            thumbnails: self.thumbnails,
+            dedup: self.dedup,
+            cache: self.cache.clone(),
+            policy: Arc::clone(&self.policy),
+            on_item: self.on_item.clone(),
+            on_progress: self.on_progress.clone(),
+            counters: Arc::clone(&batch.counters),
+            throttle: Arc::clone(&batch.throttle),
+            contents: Arc::clone(&batch.contents),
+            saved: Arc::clone(&batch.saved),
+        }
+    }
+}
+
+impl Job {
+    /// Downloads `media` to `stem` with an extension and puts it to the sink, then computes
+    /// the checksum of the file, writes the sidecar and downloads the thumbnail if they're
+    /// enabled.
+    fn process(&self, media: Media, parent_id: Option<u64>, stem: &Path) -> ItemReport {
+        let (result, attempts) = match content_url(&media, self.thumbnails) {
+            Some(url) => self.download_with_retries(&media, url, stem),
+            None => (Ok(Fetch::Skipped), 0),
+        };
+        let fetched = match result {
+            Ok(Fetch::Fetched(fetched)) => fetched,
+            Ok(Fetch::Skipped) => {
+                return ItemReport::new(media, parent_id, DownloadOutcome::Skipped, attempts)
+            }
+            // The size of the content has turned out to match a metadata-only rule.
+            Ok(Fetch::MetadataOnly) => {
+                let written = if self.sidecars {
+                    sidecar::put(&self.target, stem, &media, parent_id)
+                } else {
+                    Ok(())
+                };
+                let outcome = match written {
+                    Ok(()) => DownloadOutcome::MetadataOnly,
+                    Err(e) => DownloadOutcome::Failed(e.to_string()),
+                };
+                return ItemReport::new(media, parent_id, outcome, attempts);
+            }
+            Err(e) => {
+                return ItemReport::new(media, parent_id, DownloadOutcome::Failed(e), attempts)
+            }
+        };
+        let path = fetched.path.clone();
+        match self.finish_file(&media, parent_id, stem, fetched) {
+            Ok((checksum, duplicate_of)) => {
+                let outcome = match duplicate_of {
+                    Some(_) if self.dedup == DedupMode::Skip => DownloadOutcome::Skipped,
+                    _ => DownloadOutcome::Downloaded(path),
+                };
+                let mut item = ItemReport::new(media, parent_id, outcome, attempts);
+                if let Some(checksum) = checksum {
+                    item = item.with_checksum(checksum);
+                }
+                if let Some(original) = duplicate_of {
+                    item = item.with_duplicate_of(original);
+                }
+                item
+            }
+            Err(e) => {
+                ItemReport::new(media, parent_id, DownloadOutcome::Failed(e.to_string()), attempts)
+            }
+        }
+    }
+
+    /// Computes the checksum of the downloaded file and puts it to the sink, unless it's
+    /// a duplicate that should be skipped. Then replaces the file if it's a duplicate, writes
+    /// its sidecar and downloads the thumbnail next to it, if they're enabled.
+    /// Returns the checksum and the path of the file with the same content.
+    fn finish_file(
+        &self,
+        media: &Media,
+        parent_id: Option<u64>,
+        stem: &Path,
+        fetched: Fetched,
+    ) -> crate::Result<(Option<String>, Option<PathBuf>)> {
+        let checksum = if self.checksums || self.dedup != DedupMode::Off {
+            Some(manifest::sha256_file(&fetched.file)?)
+        } else {
+            None
+        };
+        let duplicate_of = match &checksum {
+            Some(checksum) if self.dedup != DedupMode::Off => {
+                self.contents.original(checksum, &fetched.path)
+            }
+            _ => None,
+        };
+        if duplicate_of.is_some() && self.dedup == DedupMode::Skip {
+            fetched.discard()?;
+            return Ok((None, duplicate_of));
+        }
+
+        self.store(&fetched)?;
+        if let Some(original) = &duplicate_of {
+            if self.target.is_local() {
+                dedup::replace(&fetched.path, original, self.dedup)?;
+            }
+        }
+        if self.sidecars {
+            sidecar::put(&self.target, &fetched.path, media, parent_id)?;
+        }
+        if let Some(url) = thumbnail_url(media, self.thumbnails) {
+            let thumbnail_stem = with_suffix(stem, ".thumbnail");
+            match self.download_with_retries(media, url, &thumbnail_stem).0 {
+                Ok(Fetch::Fetched(thumbnail)) => self.store(&thumbnail)?,
+                Ok(_) => {}
+                Err(e) => return Err(format!("couldn't download the thumbnail: {}", e).into()),
+            }
+        }
+        Ok((checksum.filter(|_| self.checksums), duplicate_of))
+    }
+
+    /// Puts the fetched file to the sink: a downloaded file is moved, a cached one is copied.
+    fn store(&self, fetched: &Fetched) -> crate::Result<()> {
+        if fetched.cached {
+            self.target.copy_file(&fetched.path, &fetched.file)
+        } else {
+            self.target.put_file(&fetched.path, &fetched.file)
+        }
+    }
+
+    /// Calls [download_file][Job::download_file] until it succeeds, fails with a permanent
+    /// error or runs out of retries. Returns the result and the number of attempts.
+    fn download_with_retries(
+        &self,
+        media: &Media,
+        url: &Url,
+        stem: &Path,
+    ) -> (Result<Fetch, String>, u32) {
+        let mut attempts = 1;
+        loop {
+            match self.download_file(media, url, stem) {
+                Err(e) if attempts <= self.retries && is_transient(e.as_ref()) => {
+                    // Partially downloaded content is kept, so the retry resumes it.
+                    thread::sleep(self.retry_backoff.saturating_mul(1 << (attempts - 1).min(16)));
+                    attempts += 1;
+                }
+                result => return (result.map_err(|e| e.to_string()), attempts),
+            }
+        }
+    }
+
+    /// Downloads the file of `media` at `url` for `stem` with an extension. The extension
+    /// is taken from the URL or, if it has none, from the content type. Returns the local file
+    /// that is ready to be put to the sink, or the action of the policy if the content size
+    /// doesn't allow downloading it. The file is skipped if it exists and
+    /// [ExistingFiles::Skip] is set.
+    ///
+    /// Content is written to a temporary file that is put to the sink only after the download
+    /// completes, so an interrupted download never leaves a partial file under the final name.
+    /// The temporary file is kept after a failure and the next download resumes from its end
+    /// using a `Range` request. The request is conditional on the validator of the content
+    /// saved by the first attempt (`If-Range`), so the file is downloaded again if the content
+    /// has been changed, the server refuses ranges or there is no validator.
+    fn download_file(
+        &self,
+        media: &Media,
+        url: &Url,
+        stem: &Path,
+    ) -> Result<Fetch, Box<dyn Error>> {
+        if self.existing == ExistingFiles::Skip && self.target.is_local() {
+            let extension = user::url_extension(url, None);
+            if self.saved.find(stem, extension.as_deref()).is_some() {
+                return Ok(Fetch::Skipped);
+            }
+        }
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(&cache::key(media, url)));
+        if let Some((cached_path, extension)) = cached {
+            let size = fs::metadata(&cached_path)?.len();
+            let action = self.policy.action(media, Some(size));
+            if action != Action::Download {
+                return Ok(Fetch::declined(action));
+            }
+            let path = with_extension(stem, extension.as_deref());
+            return Ok(Fetch::Fetched(Fetched { path, file: cached_path, cached: true }));
+        }
+
+        // Items may share a name if collisions overwrite files, so the ID is included.
+        let partial_path =
+            with_suffix(&self.target.local_path(stem), &format!(".{}.part", media.id()));
+        // Content is resumed only if the server can check that it hasn't been changed since.
+        let validator = PartialFile::validator(&partial_path);
+        let offset = match validator {
+            Some(_) => fs::metadata(&partial_path).map_or(0, |metadata| metadata.len()),
+            None => 0,
+        };
+        // The permit is held until the content is received.
+        let _permit = self.throttle.acquire(url.host_str().unwrap_or_default());
+        if offset == 0 && self.policy.has_size_rules() {
+            let action = self.head_action(media, url);
+            if action != Action::Download {
+                return Ok(Fetch::declined(action));
+            }
+        }
+
+        let response = self.client.get_file_from(url.clone(), offset, validator.as_deref());
+        let mut response = match response {
+            // The temporary file is complete already or it's larger than the content.
+            Err(e) if offset > 0 && is_range_not_satisfiable(e.as_ref()) => {
+                self.client.get_file_from(url.clone(), 0, None)?
+            }
+            result => result?,
+        };
+        // Servers that don't support ranges ignore the header and send the whole content,
+        // as well as servers whose content has been changed.
+        let (start, total) = match response.status() {
+            StatusCode::PARTIAL_CONTENT => response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_content_range)
+                .filter(|(start, _)| *start == offset)
+                .ok_or("unexpected Content-Range of a partial response")?,
+            _ => (0, response.content_length()),
+        };
+        let action = self.policy.action(media, total);
+        if action != Action::Download {
+            return Ok(Fetch::declined(action));
+        }
+
+        let content_type =
+            response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+        let extension = user::url_extension(url, content_type);
+        let path = with_extension(stem, extension.as_deref());
+        let partial = PartialFile::open(partial_path, start)?;
+        if start == 0 {
+            partial.set_validator(content_validator(&response))?;
+        }
+        // The body is streamed, so large videos aren't loaded into memory.
+        match &self.on_progress {
+            Some(callback) => {
+                let mut writer = ProgressWriter::new(
+                    &partial.file,
+                    &self.counters,
+                    callback.as_ref(),
+                    media.id(),
+                    start,
+                    total,
+                );
+                io::copy(&mut response, &mut writer)?;
+            }
+            None => {
+                io::copy(&mut response, &mut &partial.file)?;
+            }
+        }
+
+        let length = partial.len()?;
+        match total {
+            Some(total) if length != total => {
+                if length > total {
+                    // The temporary file can't be resumed, so the next download starts over.
+                    partial.remove()?;
+                }
+                Err(format!("expected {} bytes, but received {}", total, length).into())
+            }
+            _ => {
+                let file = partial.finish()?;
+                if let Some(cache) = &self.cache {
+                    // The file is downloaded anyway, so failures of the cache are ignored.
+                    let key = cache::key(media, url);
+                    File::open(&file)
+                        .and_then(|mut file| cache.insert(&key, extension.as_deref(), &mut file))
+                        .ok();
+                }
+                Ok(Fetch::Fetched(Fetched { path, file, cached: false }))
+            }
+        }
+    }
+
+    /// Checks the size of the file at `url` reported by a HEAD request against the policy,
+    /// so files that are too large aren't requested at all. Returns [Action::Download] if
+    /// the size can't be retrieved, as the policy is applied to the response of the download too.
+    fn head_action(&self, media: &Media, url: &Url) -> Action {
+        match head_size(&self.client, url) {
+            Some(size) => self.policy.action(media, Some(size)),
+            None => Action::Download,
+        }
+    }
+
+    /// Counts `item` as completed and passes it to the callback.
+    fn notify(&self, item: &ItemReport) {
+        self.counters.completed_items.fetch_add(1, Ordering::Relaxed);
+        if let Some(callback) = &self.on_item {
+            callback(item);
+        }
+    }
+}
+
+/// Constructs a file name based on media's metadata:
+/// `<owner's username>_<media ID>_<publish date>`.
+pub fn default_filename(media: &Media) -> String {
+    format!(
+        "{}_{}_{}",
+        media.username().unwrap_or_default(),
+        media.id(),
+        media.timestamp().map_or_else(
+            || "unknown-date".to_string(),
+            |timestamp| timestamp.format("%FT%H-%M-%S").to_string(),
+        ),
+    )
+}
+
+/// Downloads media of several accounts at once, like [download_iter][Downloader::download_iter]
+/// of each downloader running in parallel. Instead of finishing one account before starting
+/// the next, items are taken from the accounts in turns proportional to their weights, e.g.
+/// the numbers of items left to download, so pages are fetched and files are queued fairly
+/// and all accounts make steady progress within rate limits. Zero weights are treated as ones.
+///
+/// Media of each account is gathered by an iterator that its function returns on the thread
+/// of the account, as [media iterators][crate::user::Profile::media_iter] can't be sent
+/// between threads. Returns the reports in the same order as `downloads`.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::LongLivedToken, download::{self, Downloader}, user::Profile};
+///
+/// # fn run(tokens: Vec<LongLivedToken>) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut accounts = Vec::new();
+/// for token in tokens {
+///     let profile = Profile::new(token)?;
+///     let info = profile.info()?;
+///     let downloader = Downloader::new(profile.clone(), info.username());
+///     accounts.push((downloader, profile, info.media_count() as u32));
+/// }
+/// let downloads = accounts
+///     .iter()
+///     .map(|(downloader, profile, backlog)| (downloader, move || profile.media_iter(), *backlog))
+///     .collect();
+/// for report in download::download_weighted(downloads) {
+///     println!("{} files downloaded", report.succeeded().count());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn download_weighted<T, F, I>(downloads: Vec<(&Downloader<T>, F, u32)>) -> Vec<DownloadReport>
+where
+    T: Token + Send + Sync,
+    F: FnOnce() -> I + Send,
+    I: IntoIterator<Item = crate::Result<Media>>,
+{
+    let weights: Vec<_> = downloads.iter().map(|(_, _, weight)| *weight).collect();
+    let turns = Turns::new(&weights);
+    thread::scope(|scope| {
+        let threads: Vec<_> = downloads
+            .into_iter()
+            .enumerate()
+            .map(|(index, (downloader, media, _))| {
+                let turns = &turns;
+                scope.spawn(move || {
+                    let mut media = media().into_iter();
+                    let report = downloader
+                        .download_iter(std::iter::from_fn(|| turns.take(index, || media.next())));
+                    turns.leave(index);
+                    report
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    })
+}
+
+/// Appends `suffix` to the last component of `path`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Appends `extension` to `stem` if there is one.
+fn with_extension(stem: &Path, extension: Option<&str>) -> PathBuf {
+    match extension {
+        Some(extension) => with_suffix(stem, &format!(".{}", extension)),
+        None => stem.to_path_buf(),
+    }
+}
+
+/// Returns the URL of the file to download: the thumbnail of a video in the thumbnail-only
+/// mode, otherwise the media URL.
+fn content_url(media: &Media, thumbnails: ThumbnailMode) -> Option<&Url> {
+    match thumbnails {
+        ThumbnailMode::Only if media.media_type() == MediaType::Video => media.thumbnail_url(),
+        _ => Some(media.media_url()),
+    }
+}
+
+/// Returns the URL of the thumbnail that is saved next to a video, if it's enabled.
+fn thumbnail_url(media: &Media, thumbnails: ThumbnailMode) -> Option<&Url> {
+    match thumbnails {
+        ThumbnailMode::Alongside if media.media_type() == MediaType::Video => {
+            media.thumbnail_url()
+        }
+        _ => None,
+    }
+}
+
+/// Requests the size of the file at `url` without downloading it.
+/// Returns `None` if the request fails or the server doesn't report the size.
+fn head_size(client: &Client, url: &Url) -> Option<u64> {
+    let response = client.head(url.clone()).ok()?;
+    response.headers().get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// Checks if a download may succeed if it's retried. Errors of the file system and client
+/// errors of the server (except timeouts and throttling) are permanent.
+fn is_transient(e: &(dyn Error + 'static)) -> bool {
+    // Errors of reading a response body are wrapped into I/O errors by `io::copy`.
+    let e = match e.downcast_ref::<io::Error>() {
+        Some(io_error) => match io_error.get_ref() {
+            Some(inner) if inner.is::<reqwest::Error>() => inner,
+            _ => return false,
+        },
+        None => e,
+    };
+    match e.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) {
+        Some(status) if status.is_client_error() => matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+        ),
+        // Network and server errors, unexpected sizes of content.
+        _ => true,
+    }
+}
+
+/// Returns the value that identifies the version of the content: the strong ETag
+/// or the Last-Modified date. Weak ETags can't be used to resume downloads.
+fn content_validator(response: &Response) -> Option<&str> {
+    let headers = response.headers();
+    let etag = headers.get(ETAG).and_then(|value| value.to_str().ok());
+    etag.filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| headers.get(LAST_MODIFIED).and_then(|value| value.to_str().ok()))
+}
+
+/// Checks if the server refused a `Range` request.
+fn is_range_not_satisfiable(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status)
+        == Some(StatusCode::RANGE_NOT_SATISFIABLE)
+}
+
+/// Parses the value of the `Content-Range` header (`bytes <start>-<end>/<size>`).
+/// Returns the start of the range and the size of the whole content if it's known.
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, size) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.parse().ok()?;
+    match size {
+        "*" => Some((start, None)),
+        _ => Some((start, Some(size.parse().ok()?))),
+    }
+}
+
+/// Result of [download_file][Job::download_file].
+enum Fetch {
+    Fetched(Fetched),
+    /// The file exists already or the policy skips content of its size.
+    Skipped,
+    /// The policy keeps only metadata of content of its size.
+    MetadataOnly,
+}
+
+impl Fetch {
+    /// Converts an action of the policy other than [Action::Download].
+    fn declined(action: Action) -> Self {
+        match action {
+            Action::MetadataOnly => Self::MetadataOnly,
+            _ => Self::Skipped,
+        }
+    }
+}
+
+/// Complete content of a file that is ready to be put to the sink.
+struct Fetched {
+    /// Path of the file in the sink with an extension.
+    path: PathBuf,
+    /// Local file with the content.
+    file: PathBuf,
+    /// The local file belongs to the cache, so it's copied instead of moved.
+    cached: bool,
+}
+
+impl Fetched {
+    /// Removes the local file unless it belongs to the cache.
+    fn discard(self) -> io::Result<()> {
+        if self.cached {
+            return Ok(());
+        }
+        fs::remove_file(self.file)
+    }
+}
+
+/// Temporary file that content is written to until the download completes.
+/// It's kept after a failure, so the content can be appended to it later.
+/// The validator of the content is stored next to it, so it's resumed only
+/// if the content hasn't been changed.
+struct PartialFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl PartialFile {
+    /// Opens the file to append content after the first `length` bytes, discarding the rest.
+    fn open(path: PathBuf, length: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.set_len(length)?;
+        Ok(Self { path, file })
+    }
+
+    /// Returns the validator of the content of the file at `path` if it's been saved.
+    fn validator(path: &Path) -> Option<String> {
+        fs::read_to_string(validator_path(path)).ok()
+    }
+
+    /// Saves `validator` of the content or removes the previous one if it's `None`.
+    fn set_validator(&self, validator: Option<&str>) -> io::Result<()> {
+        match validator {
+            Some(validator) => fs::write(validator_path(&self.path), validator),
+            None => remove_validator(&self.path),
+        }
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// Flushes the content to disk and closes the file. Returns its path.
+    fn finish(self) -> io::Result<PathBuf> {
+        self.file.sync_all()?;
+        remove_validator(&self.path)?;
+        Ok(self.path)
+    }
+
+    fn remove(self) -> io::Result<()> {
+        remove_validator(&self.path)?;
+        fs::remove_file(&self.path)
+    }
+}
+
+/// Returns the path to the validator of the temporary file at `path`.
+fn validator_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".validator")
+}
+
+fn remove_validator(path: &Path) -> io::Result<()> {
+    match fs::remove_file(validator_path(path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        policy::Rule,
+        user::tests::{app_profile, media, video},
+    };
+
+    #[test]
+    fn sync_with_failed_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = Downloader::new(app_profile(), dir.path());
+        // The profile can't retrieve media, so the first page fails.
+        let report = downloader.sync().unwrap();
+        assert_eq!(report.errors().len(), 1);
+        let state = SyncState::load(dir.path().join(SyncState::FILENAME)).unwrap();
+        assert_eq!(state.last_seen_timestamp, None);
+        assert_eq!(state.last_seen_id, None);
+    }
+
+    #[test]
+    fn retry_unavailable_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = Downloader::new(app_profile(), dir.path());
+        let failure = |media_id, parent_id| Failure { media_id, parent_id, error: String::new() };
+
+        // The profile can't retrieve media, so every item is recorded as an error.
+        let failures = [failure(2, Some(1)), failure(3, Some(1)), failure(4, None)];
+        let report = downloader.retry_failures(&failures);
+        assert!(report.items().is_empty());
+        assert_eq!(report.errors().len(), 3);
+        assert!(report.errors()[1].starts_with("couldn't retrieve album 1"));
+        assert!(report.errors()[2].starts_with("couldn't retrieve item 4"));
+    }
+
+    #[test]
+    fn download_by_weight() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloaders = Vec::new();
+        for account in ["first", "second"] {
+            let mut downloader = Downloader::new(app_profile(), dir.path().join(account));
+            downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::MetadataOnly)] });
+            downloaders.push(downloader);
+        }
+        let log = std::sync::Mutex::new(Vec::new());
+        let media = |account: usize, ids: std::ops::Range<u64>| {
+            let log = &log;
+            move || {
+                ids.map(move |id| {
+                    log.lock().unwrap().push(account);
+                    Ok(media(id))
+                })
+            }
+        };
+
+        let reports = download_weighted(vec![
+            (&downloaders[0], media(0, 0..6), 3),
+            (&downloaders[1], media(1, 6..12), 1),
+        ]);
+        assert_eq!(reports[0].items().len(), 6);
+        assert_eq!(reports[1].items().len(), 6);
+        // The second account gets every fourth turn until the first one is done.
+        assert_eq!(log.into_inner().unwrap(), [0, 0, 1, 0, 0, 0, 1, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn report_by_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::MetadataOnly)] });
+        let report = downloader.download_all(vec![media(2), media(1)]);
+
+        let ids: Vec<_> = report.items().iter().map(|item| item.media().id()).collect();
+        assert_eq!(ids, [2, 1]);
+        assert!(report.items().iter().all(|item| item.outcome() == &DownloadOutcome::MetadataOnly));
+        assert!(report.is_success());
+        assert_eq!(report.succeeded().count(), 2);
+        assert!(report.failures().is_empty());
+    }
+
+    #[test]
+    fn layout() {
+        let mut downloader = Downloader::new(app_profile(), "backup");
+        downloader.set_layout(Some("{type}/../{id}//".parse().unwrap()));
+        let dir = downloader.item_dir(&media(1), None, Path::new("backup"));
+        assert_eq!(dir, Path::new("backup").join("image").join("_").join("1"));
+        assert_eq!(downloader.item_dir(&media(2), Some(1), Path::new("album")), Path::new("album"));
+    }
+
+    #[test]
+    fn thumbnails() {
+        let url = |media, mode| content_url(&media, mode).map(|url| url.to_string());
+        let thumbnail = Some("https://example.com/thumbnail.jpg".to_string());
+        assert_eq!(url(video(1), ThumbnailMode::Only), thumbnail);
+        let content = Some("https://example.com/".to_string());
+        assert_eq!(url(video(1), ThumbnailMode::Alongside), content);
+        assert_eq!(url(media(1), ThumbnailMode::Only), content);
+    }
+
+    #[test]
+    fn cached_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ContentCache::open(dir.path().join("cache")).unwrap();
+        cache.insert("1", Some("jpg"), &mut b"abc".as_slice()).unwrap();
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_cache(Some(cache));
+        downloader.set_naming(|_| "cached".to_string());
+
+        // The content is taken from the cache instead of the media URL.
+        let report = downloader.download(media(1));
+        assert!(report.is_success());
+        assert_eq!(fs::read_to_string(dir.path().join("cached.jpg")).unwrap(), "abc");
+    }
+
+    #[test]
+    fn metadata_only_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ContentCache::open(dir.path().join("cache")).unwrap();
+        cache.insert("1", Some("jpg"), &mut b"abc".as_slice()).unwrap();
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_cache(Some(cache));
+        downloader.set_naming(|_| "large".to_string());
+        downloader.set_sidecars(true);
+        let rule = Rule { larger_than: Some(2), ..Rule::new(Action::MetadataOnly) };
+        downloader.set_policy(SyncPolicy { rules: vec![rule] });
+
+        // The size is known only once the content is fetched.
+        let report = downloader.download(media(1));
+        assert_eq!(report.items()[0].outcome(), &DownloadOutcome::MetadataOnly);
+        assert!(dir.path().join("large.json").exists());
+        assert!(!dir.path().join("large.jpg").exists());
+    }
+
+    #[test]
+    fn names_of_previous_syncs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ContentCache::open(dir.path().join("cache")).unwrap();
+        cache.insert("1", Some("jpg"), &mut b"abc".as_slice()).unwrap();
+        cache.insert("2", Some("jpg"), &mut b"def".as_slice()).unwrap();
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_cache(Some(cache));
+        downloader.set_naming(|_| "same".to_string());
+
+        // The first batch is recorded the way a sync does it.
+        let first = downloader.download(media(1));
+        let mut state = SyncState::default();
+        state.update(&first, dir.path());
+        state.save(dir.path().join(SyncState::FILENAME)).unwrap();
+
+        let second = downloader.download(media(2));
+        let path = dir.path().join("same_1.jpg");
+        assert_eq!(second.items()[0].path(), Some(path.as_path()));
+        assert_eq!(fs::read_to_string(path).unwrap(), "def");
+        assert_eq!(fs::read_to_string(dir.path().join("same.jpg")).unwrap(), "abc");
+        // The item of the first batch keeps its name.
+        let again = downloader.download(media(1));
+        assert_eq!(again.items()[0].path(), Some(dir.path().join("same.jpg").as_path()));
+    }
+
+    #[test]
+    fn partial_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.part");
+        fs::write(&path, "abcdef").unwrap();
+        let partial = PartialFile::open(path.clone(), 3).unwrap();
+        io::Write::write_all(&mut &partial.file, b"x").unwrap();
+        assert_eq!(partial.len().unwrap(), 4);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abcx");
+        partial.remove().unwrap();
+        assert!(!path.exists());
+
+        let partial = PartialFile::open(path.clone(), 0).unwrap();
+        partial.set_validator(Some("\"abc\"")).unwrap();
+        assert_eq!(PartialFile::validator(&path).as_deref(), Some("\"abc\""));
+        partial.set_validator(None).unwrap();
+        assert_eq!(PartialFile::validator(&path), None);
+        partial.set_validator(Some("\"abc\"")).unwrap();
+        // The validator is removed along with the complete file.
+        assert_eq!(partial.finish().unwrap(), path);
+        assert_eq!(PartialFile::validator(&path), None);
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(!is_transient(&io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert!(is_transient(&*Box::<dyn Error>::from("expected 10 bytes, but received 5")));
+    }
+
+    #[test]
+    fn content_range() {
+        assert_eq!(parse_content_range("bytes 100-199/200"), Some((100, Some(200))));
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some((0, None)));
+        assert_eq!(parse_content_range("bytes */200"), None);
+        assert_eq!(parse_content_range("items 0-9/10"), None);
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Archives that downloads are written to instead of a directory tree.
+
+use super::{DownloadReport, Downloader, StorageSink, Target};
+use crate::{auth::Token, user::Media};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Format of an archive written by [download_archive][super::Downloader::download_archive].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum ArchiveFormat {
+    /// ZIP archive without compression, as media files are compressed already.
+    #[cfg(feature = "zip")]
+    Zip,
+    /// Tar archive compressed by Zstandard.
+    #[cfg(feature = "tar-zstd")]
+    TarZstd,
+}
+
+/// Archive that entries are appended to one by one.
+pub(super) enum ArchiveWriter {
+    #[cfg(feature = "zip")]
+    Zip(Box<zip::ZipWriter<File>>),
+    #[cfg(feature = "tar-zstd")]
+    TarZstd(tar::Builder<zstd::Encoder<'static, File>>),
+}
+
+impl<T: Token> Downloader<T> {
+    /// Downloads contents of `media` into an archive at `path` instead of the destination
+    /// directory, which is much faster on file systems with slow creation of small files.
+    /// The archive is written to a temporary file next to `path`, which replaces the existing
+    /// archive only once it's complete and is removed if the download is interrupted.
+    /// Works like [download_all][Downloader::download_all]
+    /// with the archive set as the [sink][Downloader::set_sink]: files are downloaded
+    /// to the destination first and moved into the archive one at a time.
+    pub fn download_archive<I, P>(
+        &self,
+        media: I,
+        path: P,
+        format: ArchiveFormat,
+    ) -> crate::Result<DownloadReport>
+    where
+        I: IntoIterator<Item = Media>,
+        P: AsRef<Path>,
+    {
+        let mut pending = PendingArchive::new(path.as_ref());
+        let archive = Arc::new(Mutex::new(ArchiveWriter::create(&pending.path, format)?));
+        let sink: Arc<dyn StorageSink> = archive.clone();
+        let target = Target::new(sink, &self.destination);
+        let report =
+            self.process(target, media.into_iter().map(|media| Ok((media, None, PathBuf::new()))));
+        let archive = Arc::try_unwrap(archive).map_err(|_| "archive is still in use")?;
+        archive.into_inner().unwrap_or_else(|e| e.into_inner()).finish()?;
+        pending.persist(path.as_ref())?;
+        Ok(report)
+    }
+}
+
+/// Temporary file of an archive that is removed on drop unless it's
+/// [persisted][PendingArchive::persist], so interrupted downloads don't leave
+/// truncated archives.
+struct PendingArchive {
+    path: PathBuf,
+    is_persisted: bool,
+}
+
+impl PendingArchive {
+    fn new(path: &Path) -> Self {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".part");
+        Self { path: path.with_file_name(name), is_persisted: false }
+    }
+
+    /// Replaces the file at `path` by the complete archive.
+    fn persist(&mut self, path: &Path) -> io::Result<()> {
+        fs::rename(&self.path, path)?;
+        self.is_persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingArchive {
+    fn drop(&mut self) {
+        if !self.is_persisted {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+impl ArchiveFormat {
+    /// Returns the conventional extension of archive files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "zip")]
+            Self::Zip => "zip",
+            #[cfg(feature = "tar-zstd")]
+            Self::TarZstd => "tar.zst",
+        }
+    }
+}
+
+impl ArchiveWriter {
+    /// Creates an archive at `path`, replacing the existing file.
+    pub(super) fn create(path: &Path, format: ArchiveFormat) -> crate::Result<Self> {
+        let file = File::create(path)?;
+        Ok(match format {
+            #[cfg(feature = "zip")]
+            ArchiveFormat::Zip => Self::Zip(Box::new(zip::ZipWriter::new(file))),
+            #[cfg(feature = "tar-zstd")]
+            ArchiveFormat::TarZstd => {
+                Self::TarZstd(tar::Builder::new(zstd::Encoder::new(file, 0)?))
+            }
+        })
+    }
+
+    /// Adds a directory. `path` is separated by slashes.
+    pub(super) fn add_dir(&mut self, path: &str) -> crate::Result<()> {
+        match self {
+            #[cfg(feature = "zip")]
+            Self::Zip(zip) => zip.add_directory(path, zip_options(0))?,
+            #[cfg(feature = "tar-zstd")]
+            Self::TarZstd(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                tar.append_data(&mut header, path, io::empty())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a file with `size` bytes of `content`, which are copied without buffering
+    /// the whole file. `path` is separated by slashes.
+    pub(super) fn add_file(
+        &mut self,
+        path: &str,
+        content: &mut dyn Read,
+        size: u64,
+    ) -> crate::Result<()> {
+        match self {
+            #[cfg(feature = "zip")]
+            Self::Zip(zip) => {
+                zip.start_file(path, zip_options(size))?;
+                if io::copy(&mut content.take(size), zip)? != size {
+                    return Err("content is shorter than its size".into());
+                }
+            }
+            #[cfg(feature = "tar-zstd")]
+            Self::TarZstd(tar) => {
+                // Sizes of tar entries are written before their content.
+                let mut header = tar::Header::new_gnu();
+                header.set_mode(0o644);
+                header.set_size(size);
+                tar.append_data(&mut header, path, content.take(size))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the index of the archive and flushes it.
+    pub(super) fn finish(self) -> crate::Result<()> {
+        match self {
+            #[cfg(feature = "zip")]
+            Self::Zip(zip) => zip.finish()?.flush()?,
+            #[cfg(feature = "tar-zstd")]
+            Self::TarZstd(tar) => tar.into_inner()?.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl StorageSink for Mutex<ArchiveWriter> {
+    /// Entries are written one at a time, so other threads wait for the lock while
+    /// `content` is copied.
+    fn put(&self, path: &str, mut content: Box<dyn Read + Send>, size: u64) -> crate::Result<()> {
+        self.lock().map_err(|_| "archive lock is poisoned")?.add_file(path, &mut content, size)
+    }
+
+    fn create_dir(&self, path: &str) -> crate::Result<()> {
+        self.lock().map_err(|_| "archive lock is poisoned")?.add_dir(&format!("{}/", path))
+    }
+}
+
+#[cfg(feature = "zip")]
+fn zip_options(size: u64) -> zip::write::SimpleFileOptions {
+    zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(size > u64::from(u32::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        policy::{Action, Rule, SyncPolicy},
+        user::tests::{app_profile, media},
+    };
+
+    const FORMATS: &[ArchiveFormat] = &[
+        #[cfg(feature = "zip")]
+        ArchiveFormat::Zip,
+        #[cfg(feature = "tar-zstd")]
+        ArchiveFormat::TarZstd,
+    ];
+
+    fn entry_names(path: &Path, format: ArchiveFormat) -> Vec<String> {
+        match format {
+            #[cfg(feature = "zip")]
+            ArchiveFormat::Zip => {
+                let zip = zip::ZipArchive::new(File::open(path).unwrap()).unwrap();
+                let mut names: Vec<_> = zip.file_names().map(str::to_string).collect();
+                names.sort();
+                names
+            }
+            #[cfg(feature = "tar-zstd")]
+            ArchiveFormat::TarZstd => {
+                let decoder = zstd::Decoder::new(File::open(path).unwrap()).unwrap();
+                let mut tar = tar::Archive::new(decoder);
+                let entries = tar.entries().unwrap();
+                entries.map(|entry| entry.unwrap().path().unwrap().display().to_string()).collect()
+            }
+        }
+    }
+
+    #[test]
+    fn write_entries() {
+        for &format in FORMATS {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join(format!("a.{}", format.extension()));
+            let mut archive = ArchiveWriter::create(&path, format).unwrap();
+            archive.add_dir("album/").unwrap();
+            archive.add_file("album/1.jpg", &mut b"abc".as_slice(), 3).unwrap();
+            archive.finish().unwrap();
+
+            assert_eq!(entry_names(&path, format), ["album/", "album/1.jpg"]);
+        }
+    }
+
+    #[test]
+    fn export_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::MetadataOnly)] });
+        downloader.set_sidecars(true);
+        downloader.set_layout(Some("{type}".parse().unwrap()));
+        downloader.set_naming(|_| "same".to_string());
+
+        for &format in FORMATS {
+            let path = dir.path().join(format!("a.{}", format.extension()));
+            let report = downloader.download_archive(vec![media(2), media(1)], &path, format);
+            assert!(report.unwrap().is_success());
+            let names = entry_names(&path, format);
+            assert_eq!(names, ["image/", "image/same.json", "image/same_1.json"]);
+        }
+    }
+
+    #[test]
+    fn interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::MetadataOnly)] });
+        downloader.set_sidecars(true);
+        downloader.set_naming(|_| panic!("interrupted"));
+
+        for &format in FORMATS {
+            let path = dir.path().join(format!("a.{}", format.extension()));
+            fs::write(&path, "previous").unwrap();
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                downloader.download_archive(vec![media(1)], &path, format)
+            }));
+            assert!(result.is_err());
+            // The previous archive is kept and the incomplete one is removed.
+            assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+            assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
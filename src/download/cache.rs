@@ -0,0 +1,116 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Local cache of downloaded contents, so repeated downloads don't request them again.
+
+use crate::user::Media;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use url::Url;
+
+/// Directory of downloaded files keyed by media IDs that [Downloader][super::Downloader]
+/// consults before requesting the CDN, e.g. when the same media is saved both to
+/// a directory and to an archive. Files are stored as `<media ID>.<extension>`,
+/// thumbnails as `<media ID>_thumbnail.<extension>`. Files are never evicted.
+pub struct ContentCache {
+    dir: PathBuf,
+    /// Paths of the cached files and their extensions by keys.
+    files: Mutex<HashMap<String, (PathBuf, Option<String>)>>,
+}
+
+impl ContentCache {
+    /// Opens the cache stored in `dir`, creating the directory if it doesn't exist.
+    /// Files cached earlier are indexed, the ones that are being written are ignored.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut files = HashMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) if path.is_file() && !name.ends_with(".part") => name.to_string(),
+                _ => continue,
+            };
+            let (key, extension) = match name.split_once('.') {
+                Some((key, extension)) => (key.to_string(), Some(extension.to_string())),
+                None => (name, None),
+            };
+            files.insert(key, (path, extension));
+        }
+        Ok(Self { dir, files: Mutex::new(files) })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path of the file cached under `key` and its extension.
+    pub(super) fn get(&self, key: &str) -> Option<(PathBuf, Option<String>)> {
+        let files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files.get(key).filter(|(path, _)| path.is_file()).cloned()
+    }
+
+    /// Caches `content` under `key`. The file is written under a temporary name first,
+    /// so other downloads never read it partially.
+    pub(super) fn insert<R: io::Read>(
+        &self,
+        key: &str,
+        extension: Option<&str>,
+        content: &mut R,
+    ) -> io::Result<()> {
+        let name = match extension {
+            Some(extension) => format!("{}.{}", key, extension),
+            None => key.to_string(),
+        };
+        let path = self.dir.join(name);
+        let partial_path = self.dir.join(format!("{}.part", key));
+        io::copy(content, &mut fs::File::create(&partial_path)?)?;
+        fs::rename(&partial_path, &path)?;
+
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files.insert(key.to_string(), (path, extension.map(str::to_string)));
+        Ok(())
+    }
+}
+
+/// Returns the key of the file of `media` at `url`: the media ID for its content
+/// and the ID with a suffix for its thumbnail.
+pub(super) fn key(media: &Media, url: &Url) -> String {
+    if url == media.media_url() {
+        media.id().to_string()
+    } else {
+        format!("{}_thumbnail", media.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::tests::video;
+
+    #[test]
+    fn insert_and_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let cache = ContentCache::open(dir).unwrap();
+        let media = video(1);
+        let key = key(&media, media.thumbnail_url().unwrap());
+        assert_eq!(key, "1_thumbnail");
+        assert!(cache.get(&key).is_none());
+
+        cache.insert(&key, Some("jpg"), &mut b"abc".as_slice()).unwrap();
+        cache.insert("2", None, &mut b"def".as_slice()).unwrap();
+        let reopened = ContentCache::open(dir).unwrap();
+        let (path, extension) = reopened.get(&key).unwrap();
+        assert_eq!(path, dir.join("1_thumbnail.jpg"));
+        assert_eq!(extension.as_deref(), Some("jpg"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "abc");
+        assert_eq!(reopened.get("2").unwrap().1, None);
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Handling of items whose names collide with other items or with existing files.
+
+use super::SyncState;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// What to do with an item whose name is taken by another item of the same download,
+/// e.g. if both are published in the same second and the template has no ID.
+///
+/// Items are compared with the other items of the same download and with the items
+/// recorded by previous [syncs][super::Downloader::sync] to the destination. Other files
+/// left by previous downloads are considered copies of the same items, so they're resumed
+/// or replaced.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum CollisionPolicy {
+    /// Report the item as failed.
+    Error,
+    /// Report the item as skipped.
+    Skip,
+    /// Save the item under the same name. Which of the items is kept is unspecified,
+    /// as they're downloaded in parallel.
+    Overwrite,
+    /// Append `_1`, `_2`, etc. to the name until it's free.
+    #[default]
+    Suffix,
+}
+
+/// What to do with an item whose file has been saved by a previous download,
+/// e.g. if a backup is run again into the same directory.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum ExistingFiles {
+    /// Download the item again and replace the file.
+    #[default]
+    Overwrite,
+    /// Report the item as skipped without requesting its content.
+    Skip,
+}
+
+/// Names of files and album directories taken during a download, without extensions,
+/// along with the IDs of the items that own them.
+#[derive(Default)]
+pub(super) struct Names {
+    taken: HashMap<PathBuf, u64>,
+}
+
+impl Names {
+    /// Reserves the paths of the files and album directories recorded in `state`
+    /// for their items, so other items of the next download don't overwrite them.
+    pub(super) fn recorded(state: &SyncState, destination: &Path) -> Self {
+        let mut taken = HashMap::new();
+        for (id, path) in &state.paths {
+            let path = destination.join(path);
+            // Names of directories may contain dots, so both forms are reserved.
+            taken.insert(path.with_extension(""), *id);
+            taken.insert(path, *id);
+        }
+        Self { taken }
+    }
+
+    /// Takes `name` in `dir` for the item with `id` according to `policy`. Returns the name
+    /// to use, `None` if the item should be skipped or an error if it should fail.
+    /// Names taken by the same item, e.g. during a previous sync, are reused.
+    pub(super) fn take(
+        &mut self,
+        dir: &Path,
+        name: String,
+        id: u64,
+        policy: CollisionPolicy,
+    ) -> Result<Option<String>, String> {
+        let owner = *self.taken.entry(dir.join(&name)).or_insert(id);
+        if owner == id {
+            return Ok(Some(name));
+        }
+        match policy {
+            CollisionPolicy::Error => {
+                Err(format!("name \"{}\" is taken by another item", name))
+            }
+            CollisionPolicy::Skip => Ok(None),
+            CollisionPolicy::Overwrite => Ok(Some(name)),
+            CollisionPolicy::Suffix => {
+                let name = (1..)
+                    .map(|n| format!("{}_{}", name, n))
+                    .find(|candidate| {
+                        !matches!(self.taken.get(&dir.join(candidate)), Some(owner) if *owner != id)
+                    })
+                    .unwrap();
+                self.taken.insert(dir.join(&name), id);
+                Ok(Some(name))
+            }
+        }
+    }
+}
+
+/// Names of the files in a directory by their names without the last extension.
+/// Files without extensions are listed under their full names.
+type Listing = HashMap<String, Vec<String>>;
+
+/// Files saved by previous downloads. Each directory is listed once per download
+/// when it's looked up first, so lookups don't depend on the number of files.
+#[derive(Default)]
+pub(super) struct SavedFiles {
+    listings: Mutex<HashMap<PathBuf, Arc<Listing>>>,
+}
+
+impl SavedFiles {
+    /// Returns the file saved at `stem` by a previous download. The file with `extension`
+    /// is looked up first, then a file with any extension. Temporary files, thumbnails
+    /// and sidecars aren't considered.
+    pub(super) fn find(&self, stem: &Path, extension: Option<&str>) -> Option<PathBuf> {
+        let name = stem.file_name()?.to_str()?;
+        let dir = stem.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let listing = self.listing(dir);
+        let files = listing.get(name)?;
+        let exact = extension.map(|extension| format!("{}.{}", name, extension));
+        let file = exact.filter(|exact| files.contains(exact)).or_else(|| files.first().cloned());
+        file.map(|file| dir.join(file))
+    }
+
+    fn listing(&self, dir: &Path) -> Arc<Listing> {
+        let mut listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        let listing = listings.entry(dir.to_path_buf()).or_insert_with(|| Arc::new(list(dir)));
+        Arc::clone(listing)
+    }
+}
+
+/// Lists files of `dir`. Extensions are separated by the last dot, so names of temporary
+/// files and sidecars keep the extensions of their media files.
+fn list(dir: &Path) -> Listing {
+    let mut listing = Listing::new();
+    let entries = fs::read_dir(dir).into_iter().flatten().filter_map(Result::ok);
+    for path in entries.map(|entry| entry.path()).filter(|path| path.is_file()) {
+        let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+        let name = match file_name.rsplit_once('.') {
+            Some((name, extension)) if !name.is_empty() && !extension.is_empty() => name,
+            _ => file_name,
+        };
+        listing.entry(name.to_string()).or_default().push(file_name.to_string());
+    }
+    listing.values_mut().for_each(|files| files.sort());
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take() {
+        let mut names = Names::default();
+        let dir = Path::new("dir");
+        let mut take = |name: &str, id, policy| names.take(dir, name.to_string(), id, policy);
+        assert_eq!(take("a", 1, CollisionPolicy::Error), Ok(Some("a".into())));
+        assert_eq!(take("a_1", 2, CollisionPolicy::Error), Ok(Some("a_1".into())));
+        assert_eq!(take("a", 3, CollisionPolicy::Suffix), Ok(Some("a_2".into())));
+        assert_eq!(take("a", 4, CollisionPolicy::Overwrite), Ok(Some("a".into())));
+        assert_eq!(take("a", 5, CollisionPolicy::Skip), Ok(None));
+        assert!(take("a", 6, CollisionPolicy::Error).is_err());
+        // The same item gets the same name.
+        assert_eq!(take("a", 1, CollisionPolicy::Error), Ok(Some("a".into())));
+        assert_eq!(take("a", 3, CollisionPolicy::Suffix), Ok(Some("a_2".into())));
+        // Names in other directories don't collide.
+        let other = names.take(&dir.join("b"), "a".into(), 2, CollisionPolicy::Error);
+        assert_eq!(other, Ok(Some("a".into())));
+    }
+
+    #[test]
+    fn recorded() {
+        let state = SyncState {
+            paths: [(1, "a.jpg".to_string()), (2, "album.2".to_string())].into(),
+            ..SyncState::default()
+        };
+        let dir = Path::new("dir");
+        let mut names = Names::recorded(&state, dir);
+        let mut take = |name: &str, id| names.take(dir, name.to_string(), id, Default::default());
+        assert_eq!(take("a", 1), Ok(Some("a".into())));
+        assert_eq!(take("a", 3), Ok(Some("a_1".into())));
+        assert_eq!(take("album.2", 4), Ok(Some("album.2_1".into())));
+    }
+
+    #[test]
+    fn existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        for name in ["a.jpg", "a.jpg.json", "b.1.part", "c.thumbnail.jpg", "cd.mp4"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let saved = SavedFiles::default();
+        let find = |name: &str, extension| saved.find(&dir.join(name), extension);
+        assert_eq!(find("a", Some("jpg")), Some(dir.join("a.jpg")));
+        assert_eq!(find("a", None), Some(dir.join("a.jpg")));
+        assert_eq!(find("b", None), None);
+        assert_eq!(find("c", Some("mp4")), None);
+    }
+}
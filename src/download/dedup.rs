@@ -0,0 +1,106 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Detection of files with the same content, e.g. media posted standalone and in an album.
+
+use crate::util;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// What to do with a file whose content matches a file saved earlier during the same download.
+/// Contents are compared by SHA-256 checksums.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum DedupMode {
+    /// Keep duplicates as they are.
+    #[default]
+    Off,
+    /// Replace duplicates with hard links to the first file.
+    HardLink,
+    /// Replace duplicates with relative symbolic links to the first file.
+    SymLink,
+    /// Remove duplicates and report them as skipped.
+    Skip,
+}
+
+/// Paths of the files saved during a download by their checksums.
+#[derive(Default)]
+pub(super) struct Contents {
+    paths: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl Contents {
+    /// Returns the path of the file with `checksum` saved earlier. If there is none,
+    /// `path` is recorded as the one with this content.
+    pub(super) fn original(&self, checksum: &str, path: &Path) -> Option<PathBuf> {
+        let mut paths = self.paths.lock().unwrap_or_else(|e| e.into_inner());
+        match paths.get(checksum) {
+            Some(original) => Some(original.clone()),
+            None => {
+                paths.insert(checksum.to_string(), path.to_path_buf());
+                None
+            }
+        }
+    }
+}
+
+/// Replaces the duplicate at `path` with a link to `original` or removes it.
+pub(super) fn replace(path: &Path, original: &Path, mode: DedupMode) -> io::Result<()> {
+    if mode == DedupMode::Off {
+        return Ok(());
+    }
+    fs::remove_file(path)?;
+    match mode {
+        DedupMode::HardLink => fs::hard_link(original, path),
+        DedupMode::SymLink => symlink(&util::relative_path(original, path), path),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn original() {
+        let contents = Contents::default();
+        assert_eq!(contents.original("ab", Path::new("a.jpg")), None);
+        assert_eq!(contents.original("cd", Path::new("b.jpg")), None);
+        assert_eq!(contents.original("ab", Path::new("c.jpg")), Some("a.jpg".into()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn replace_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::create_dir_all(dir.join("album")).unwrap();
+        fs::write(dir.join("a.jpg"), "abc").unwrap();
+        fs::write(dir.join("album").join("b.jpg"), "abc").unwrap();
+        fs::write(dir.join("c.jpg"), "abc").unwrap();
+
+        replace(&dir.join("album").join("b.jpg"), &dir.join("a.jpg"), DedupMode::SymLink).unwrap();
+        replace(&dir.join("c.jpg"), &dir.join("a.jpg"), DedupMode::Skip).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("album").join("b.jpg")).unwrap(), "abc");
+        assert!(!dir.join("c.jpg").exists());
+    }
+}
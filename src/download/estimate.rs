@@ -0,0 +1,161 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Planning of downloads before they start.
+
+use super::{content_url, head_size, thumbnail_url, throttle::Throttle, Downloader};
+use crate::{
+    auth::Token,
+    policy::Action,
+    user::{Media, MediaType},
+};
+use std::sync::{mpsc, Arc};
+
+use threadpool::ThreadPool;
+use url::Url;
+
+/// Number of files and their cumulative size that a download would produce,
+/// computed by [Downloader::estimate].
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct Estimate {
+    /// Number of files to download, including thumbnails.
+    pub files: usize,
+    /// Cumulative size of the files whose size is reported by the server.
+    pub bytes: u64,
+    /// Number of files whose size isn't reported, so they aren't counted in `bytes`.
+    pub unknown_sizes: usize,
+}
+
+/// Files of a single media item: its content followed by the thumbnail if it's enabled.
+struct Target {
+    media: Media,
+    urls: Vec<Url>,
+}
+
+impl Estimate {
+    fn add(&mut self, other: Estimate) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.unknown_sizes += other.unknown_sizes;
+    }
+
+    fn add_file(&mut self, size: Option<u64>) {
+        self.files += 1;
+        match size {
+            Some(size) => self.bytes += size,
+            None => self.unknown_sizes += 1,
+        }
+    }
+}
+
+impl<T: Token> Downloader<T> {
+    /// Computes the number and the size of files that [download_all][Downloader::download_all]
+    /// would save, so free space can be checked first. Sizes are requested using `HEAD`
+    /// requests on the pool of threads, limits of requests to the same host are respected.
+    /// Albums are expanded, items skipped by the policy aren't counted.
+    /// Files downloaded earlier, including partially, are counted entirely.
+    ///
+    /// Fails if album contents can't be gathered.
+    pub fn estimate<I: IntoIterator<Item = Media>>(&self, media: I) -> crate::Result<Estimate> {
+        let mut targets = Vec::new();
+        for item in media {
+            self.plan(item, &mut targets)?;
+        }
+
+        let pool = ThreadPool::new(self.concurrency);
+        let throttle = Arc::new(Throttle::new(self.max_in_flight, self.request_delay));
+        let (tx, rx) = mpsc::channel();
+        for target in targets {
+            let client = self.profile.client().clone();
+            let policy = Arc::clone(&self.policy);
+            let throttle = Arc::clone(&throttle);
+            let tx = tx.clone();
+            pool.execute(move || {
+                let sizes: Vec<_> = target
+                    .urls
+                    .iter()
+                    .map(|url| {
+                        let _permit = throttle.acquire(url.host_str().unwrap_or_default());
+                        head_size(&client, url)
+                    })
+                    .collect();
+                // The thumbnail is downloaded only along with the content.
+                let mut estimate = Estimate::default();
+                if policy.action(&target.media, sizes[0]) == Action::Download {
+                    sizes.into_iter().for_each(|size| estimate.add_file(size));
+                }
+                tx.send(estimate).ok();
+            });
+        }
+
+        // Estimates are received until all workers drop their senders.
+        drop(tx);
+        Ok(rx.iter().fold(Estimate::default(), |mut total, estimate| {
+            total.add(estimate);
+            total
+        }))
+    }
+
+    /// Adds files of `media` to `targets`, expanding albums into their contents.
+    fn plan(&self, media: Media, targets: &mut Vec<Target>) -> crate::Result<()> {
+        if self.policy.action(&media, None) != Action::Download {
+            return Ok(());
+        }
+        if media.media_type() == MediaType::CarouselAlbum {
+            if self.skip_albums {
+                return Ok(());
+            }
+            let children = match media.embedded_children() {
+                Some(children) => children.to_vec(),
+                None => self.profile.album(&media)?,
+            };
+            return children.into_iter().try_for_each(|child| self.plan(child, targets));
+        }
+
+        let urls: Vec<Url> = content_url(&media, self.thumbnails)
+            .into_iter()
+            .chain(thumbnail_url(&media, self.thumbnails))
+            .cloned()
+            .collect();
+        if !urls.is_empty() {
+            targets.push(Target { media, urls });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        download::ThumbnailMode,
+        policy::{Rule, SyncPolicy},
+        user::tests::{app_profile, media, video},
+    };
+
+    #[test]
+    fn plan() {
+        let mut downloader = Downloader::new(app_profile(), "backup");
+        downloader.set_thumbnails(ThumbnailMode::Alongside);
+        let mut targets = Vec::new();
+        downloader.plan(video(1), &mut targets).unwrap();
+        downloader.plan(media(2), &mut targets).unwrap();
+        let urls: Vec<_> = targets.iter().map(|target| target.urls.len()).collect();
+        assert_eq!(urls, [2, 1]);
+
+        downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::Skip)] });
+        targets.clear();
+        downloader.plan(video(1), &mut targets).unwrap();
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn add() {
+        let mut estimate = Estimate::default();
+        estimate.add_file(Some(10));
+        estimate.add_file(None);
+        estimate.add(Estimate { files: 1, bytes: 5, unknown_sizes: 0 });
+        assert_eq!(estimate, Estimate { files: 3, bytes: 15, unknown_sizes: 1 });
+    }
+}
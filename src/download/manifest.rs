@@ -0,0 +1,211 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Checksum manifests of downloaded files.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    fs::File,
+    io,
+    path::{Component, Path},
+};
+
+use sha2::{Digest, Sha256};
+
+/// Format of a [Manifest] file.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum ManifestFormat {
+    /// JSON object that maps paths to checksums.
+    Json,
+    /// Lines of `<checksum>  <path>`, which can be checked using `sha256sum -c`.
+    Sha256Sum,
+}
+
+/// SHA-256 checksums of files by their paths relative to a directory.
+/// Paths are separated by slashes on all platforms.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Manifest {
+    entries: BTreeMap<String, String>,
+}
+
+/// A file that doesn't match its checksum in a [Manifest].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Mismatch {
+    Missing(String),
+    /// The content has been changed or corrupted.
+    Modified(String),
+    Unreadable { path: String, error: String },
+}
+
+impl ManifestFormat {
+    /// Returns the conventional name of a manifest file.
+    pub fn filename(self) -> &'static str {
+        match self {
+            Self::Json => "manifest.json",
+            Self::Sha256Sum => "SHA256SUMS",
+        }
+    }
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns checksums by paths.
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+
+    /// Adds or replaces the checksum of `path`.
+    pub fn insert<P: Into<String>, C: Into<String>>(&mut self, path: P, checksum: C) {
+        self.entries.insert(path.into(), checksum.into());
+    }
+
+    /// Removes the checksum of `path` and, if it's a directory, of the files inside of it.
+    pub fn remove(&mut self, path: &str) {
+        let prefix = format!("{}/", path);
+        self.entries.retain(|entry, _| entry != path && !entry.starts_with(&prefix));
+    }
+
+    /// Adds all entries of `other`, replacing checksums of the same paths.
+    pub fn merge(&mut self, other: Manifest) {
+        self.entries.extend(other.entries);
+    }
+
+    /// Parses contents of a manifest file.
+    pub fn parse(text: &str, format: ManifestFormat) -> crate::Result<Self> {
+        let entries = match format {
+            ManifestFormat::Json => serde_json::from_str(text)?,
+            ManifestFormat::Sha256Sum => text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    let (checksum, path) =
+                        line.split_once(' ').ok_or_else(|| format!("invalid line: {}", line))?;
+                    // The mode is marked by a space (text) or an asterisk (binary).
+                    let path = path.strip_prefix([' ', '*']).unwrap_or(path);
+                    Ok((path.to_string(), checksum.to_ascii_lowercase()))
+                })
+                .collect::<crate::Result<_>>()?,
+        };
+        Ok(Self { entries })
+    }
+
+    /// Reads the manifest from `path`.
+    pub fn load<P: AsRef<Path>>(path: P, format: ManifestFormat) -> crate::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?, format)
+    }
+
+    /// Writes the manifest to `path`, replacing its contents.
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ManifestFormat) -> io::Result<()> {
+        fs::write(path, self.to_text(format))
+    }
+
+    /// Formats the manifest as contents of a file.
+    pub fn to_text(&self, format: ManifestFormat) -> String {
+        match format {
+            // Serialization of string maps can't fail.
+            ManifestFormat::Json => serde_json::to_string_pretty(&self.entries).unwrap() + "\n",
+            ManifestFormat::Sha256Sum => self
+                .entries
+                .iter()
+                .map(|(path, checksum)| format!("{}  {}\n", checksum, path))
+                .collect(),
+        }
+    }
+
+    /// Computes checksums of the files in `dir` and returns the ones that don't match.
+    pub fn verify<P: AsRef<Path>>(&self, dir: P) -> Vec<Mismatch> {
+        let dir = dir.as_ref();
+        self.entries
+            .iter()
+            .filter_map(|(path, checksum)| match sha256_file(&dir.join(path)) {
+                Ok(actual) if actual.eq_ignore_ascii_case(checksum) => None,
+                Ok(_) => Some(Mismatch::Modified(path.clone())),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    Some(Mismatch::Missing(path.clone()))
+                }
+                Err(e) => Some(Mismatch::Unreadable { path: path.clone(), error: e.to_string() }),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "{}: missing", path),
+            Self::Modified(path) => write!(f, "{}: checksum doesn't match", path),
+            Self::Unreadable { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+/// Computes the SHA-256 checksum of a file as a lowercase hex string.
+pub(super) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Converts `path` relative to `base` to the form used in manifests.
+pub(super) fn relative_path(path: &Path, base: &Path) -> Option<String> {
+    let components = path
+        .strip_prefix(base)
+        .ok()?
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let mut manifest = Manifest::new();
+        manifest.insert("album/b.jpg", "AB12");
+        manifest.insert("a.mp4", "cd34");
+
+        let sums = manifest.to_text(ManifestFormat::Sha256Sum);
+        assert_eq!(sums, "cd34  a.mp4\nAB12  album/b.jpg\n");
+        let parsed = Manifest::parse("cd34 *a.mp4\n\nab12  album/b.jpg", ManifestFormat::Sha256Sum);
+        assert_eq!(parsed.unwrap().entries()["album/b.jpg"], "ab12");
+
+        let json = manifest.to_text(ManifestFormat::Json);
+        assert_eq!(Manifest::parse(&json, ManifestFormat::Json).unwrap(), manifest);
+
+        manifest.remove("album");
+        assert_eq!(manifest.entries().keys().collect::<Vec<_>>(), ["a.mp4"]);
+    }
+
+    #[test]
+    fn verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(dir.join("a.txt"), "abc").unwrap();
+        fs::write(dir.join("b.txt"), "abc").unwrap();
+
+        let checksum = sha256_file(&dir.join("a.txt")).unwrap();
+        assert_eq!(checksum, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        let mut manifest = Manifest::new();
+        manifest.insert("a.txt", checksum.to_uppercase());
+        manifest.insert("b.txt", "0");
+        manifest.insert("c.txt", "0");
+
+        let mismatches = manifest.verify(dir);
+        assert_eq!(
+            mismatches,
+            [Mismatch::Modified("b.txt".into()), Mismatch::Missing("c.txt".into())]
+        );
+        assert_eq!(relative_path(&dir.join("x").join("y"), dir).as_deref(), Some("x/y"));
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Removal of local copies of media deleted remotely.
+
+use std::{fs, io, path::Path};
+
+/// What to do with local copies of the media that have been deleted remotely.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum PruneMode {
+    /// Move files to the [trash][PruneMode::TRASH_DIR] directory inside of the destination,
+    /// keeping their relative paths, so they can be restored.
+    Trash,
+    Delete,
+}
+
+impl PruneMode {
+    /// Name of the trash directory.
+    pub const TRASH_DIR: &'static str = ".trash";
+}
+
+/// Removes the file or directory at `path` relative to `base`.
+/// Does nothing if it doesn't exist, e.g. if it's been removed manually.
+pub(super) fn remove(base: &Path, path: &str, mode: PruneMode) -> io::Result<()> {
+    let source = base.join(path);
+    if !source.exists() {
+        return Ok(());
+    }
+
+    match mode {
+        PruneMode::Delete if source.is_dir() => fs::remove_dir_all(source),
+        PruneMode::Delete => fs::remove_file(source),
+        PruneMode::Trash => {
+            let target = base.join(PruneMode::TRASH_DIR).join(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // A copy trashed earlier is replaced.
+            if target.is_dir() {
+                fs::remove_dir_all(&target)?;
+            }
+            fs::rename(source, target)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trash() {
+        let base = tempfile::tempdir().unwrap();
+        let base = base.path();
+        fs::create_dir_all(base.join("album")).unwrap();
+        fs::write(base.join("album").join("1.jpg"), "").unwrap();
+        fs::write(base.join("2.jpg"), "").unwrap();
+
+        remove(base, "album", PruneMode::Trash).unwrap();
+        remove(base, "2.jpg", PruneMode::Delete).unwrap();
+        remove(base, "3.jpg", PruneMode::Delete).unwrap();
+
+        assert!(base.join(PruneMode::TRASH_DIR).join("album").join("1.jpg").exists());
+        assert_eq!(fs::read_dir(base).unwrap().count(), 1);
+    }
+}
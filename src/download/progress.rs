@@ -0,0 +1,117 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Progress of downloads.
+
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// Progress of a file and of the whole download reported by
+/// [Downloader::on_progress][super::Downloader::on_progress].
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Progress {
+    /// ID of the media item whose file is being downloaded.
+    pub media_id: u64,
+    /// Bytes of the file downloaded so far, including the ones of a resumed download.
+    pub downloaded: u64,
+    /// Size of the file if it's reported by the server.
+    pub total: Option<u64>,
+    /// Bytes of all files downloaded so far.
+    pub overall_downloaded: u64,
+    /// Number of processed items.
+    pub completed_items: usize,
+    /// Number of items known so far. It grows as album contents are gathered.
+    pub scheduled_items: usize,
+}
+
+/// Counters shared between the workers of a single download.
+#[derive(Default)]
+pub(super) struct Counters {
+    pub(super) scheduled_items: AtomicUsize,
+    pub(super) completed_items: AtomicUsize,
+    overall_downloaded: AtomicU64,
+}
+
+/// Writer that reports progress after each written chunk.
+pub(super) struct ProgressWriter<'a, W, F> {
+    inner: W,
+    counters: &'a Counters,
+    callback: F,
+    media_id: u64,
+    total: Option<u64>,
+    downloaded: u64,
+}
+
+impl<'a, W: Write, F: Fn(&Progress)> ProgressWriter<'a, W, F> {
+    pub(super) fn new(
+        inner: W,
+        counters: &'a Counters,
+        callback: F,
+        media_id: u64,
+        downloaded: u64,
+        total: Option<u64>,
+    ) -> Self {
+        Self { inner, counters, callback, media_id, total, downloaded }
+    }
+}
+
+impl<W: Write, F: Fn(&Progress)> Write for ProgressWriter<'_, W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.downloaded += written as u64;
+        let overall_downloaded = self
+            .counters
+            .overall_downloaded
+            .fetch_add(written as u64, Ordering::Relaxed) + written as u64;
+
+        (self.callback)(&Progress {
+            media_id: self.media_id,
+            downloaded: self.downloaded,
+            total: self.total,
+            overall_downloaded,
+            completed_items: self.counters.completed_items.load(Ordering::Relaxed),
+            scheduled_items: self.counters.scheduled_items.load(Ordering::Relaxed),
+        });
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn report_chunks() {
+        let counters = Counters::default();
+        counters.scheduled_items.store(2, Ordering::Relaxed);
+        let reports = RefCell::new(Vec::new());
+        let mut output = Vec::new();
+
+        let mut writer = ProgressWriter::new(
+            &mut output,
+            &counters,
+            |progress: &Progress| reports.borrow_mut().push(*progress),
+            1,
+            0,
+            Some(6),
+        );
+        writer.write_all(b"abc").unwrap();
+        writer.write_all(b"def").unwrap();
+
+        let reports = reports.into_inner();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].downloaded, 6);
+        assert_eq!(reports[1].total, Some(6));
+        assert_eq!(reports[1].overall_downloaded, 6);
+        assert_eq!(reports[1].scheduled_items, 2);
+        assert_eq!(output, b"abcdef");
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Bound of files waiting for a worker, so gathering doesn't run far ahead of downloads.
+
+use std::sync::{Condvar, Mutex};
+
+/// Counts files that are scheduled, but haven't been started by a worker yet.
+pub(super) struct Queue {
+    capacity: usize,
+    queued: Mutex<usize>,
+    started: Condvar,
+}
+
+impl Queue {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), queued: Mutex::new(0), started: Condvar::new() }
+    }
+
+    /// Blocks until there is room for a file, then counts it as queued.
+    pub(super) fn push(&self) {
+        let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+        while *queued >= self.capacity {
+            queued = self.started.wait(queued).unwrap_or_else(|e| e.into_inner());
+        }
+        *queued += 1;
+    }
+
+    /// Counts a queued file as started.
+    pub(super) fn pop(&self) {
+        let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+        *queued = queued.saturating_sub(1);
+        self.started.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread, time::Duration};
+
+    #[test]
+    fn bound() {
+        let queue = Arc::new(Queue::new(1));
+        queue.push();
+        let other = Arc::clone(&queue);
+        let handle = thread::spawn(move || other.push());
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        queue.pop();
+        handle.join().unwrap();
+    }
+}
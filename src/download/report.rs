@@ -0,0 +1,188 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Results of downloads.
+
+use super::manifest::{self, Manifest};
+use crate::user::Media;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Results of all items processed by [Downloader][super::Downloader], including album
+/// contents. Items are in the order they have been passed, album contents follow their album.
+pub struct DownloadReport {
+    items: Vec<ItemReport>,
+    /// Errors of retrieving the items to download.
+    errors: Vec<String>,
+}
+
+/// Result of a single media item.
+pub struct ItemReport {
+    media: Media,
+    /// ID of the album the item is in.
+    parent_id: Option<u64>,
+    outcome: DownloadOutcome,
+    attempts: u32,
+    checksum: Option<String>,
+    /// Path of the file with the same content saved earlier.
+    duplicate_of: Option<PathBuf>,
+}
+
+/// What has happened to a media item.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum DownloadOutcome {
+    /// The content has been saved to the file or, for albums, to the directory.
+    Downloaded(PathBuf),
+    /// Only the metadata is kept according to the policy.
+    MetadataOnly,
+    /// The item is skipped by the policy or by the settings of the downloader.
+    Skipped,
+    /// Contains the reason of the last failed attempt.
+    Failed(String),
+}
+
+/// Failed item that can be persisted and [downloaded again][super::Downloader::retry_failures]
+/// later. Only IDs are stored, as media URLs expire.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct Failure {
+    pub media_id: u64,
+    /// ID of the album the item is in.
+    pub parent_id: Option<u64>,
+    pub error: String,
+}
+
+impl DownloadReport {
+    pub(super) fn new(items: Vec<ItemReport>) -> Self {
+        Self { items, errors: Vec::new() }
+    }
+
+    pub(super) fn with_errors(mut self, errors: Vec<String>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    pub fn items(&self) -> &[ItemReport] {
+        &self.items
+    }
+
+    /// Returns items that are [downloaded][DownloadOutcome::Downloaded] or whose
+    /// [metadata only][DownloadOutcome::MetadataOnly] is kept.
+    pub fn succeeded(&self) -> impl Iterator<Item = &ItemReport> {
+        self.items.iter().filter(|item| {
+            matches!(item.outcome, DownloadOutcome::Downloaded(_) | DownloadOutcome::MetadataOnly)
+        })
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &ItemReport> {
+        self.items.iter().filter(|item| item.outcome == DownloadOutcome::Skipped)
+    }
+
+    /// Returns items that failed to download after all attempts.
+    pub fn failed(&self) -> impl Iterator<Item = &ItemReport> {
+        self.items.iter().filter(|item| matches!(item.outcome, DownloadOutcome::Failed(_)))
+    }
+
+    /// Returns items whose content matches a file saved earlier during the download,
+    /// if [deduplication][super::Downloader::set_dedup] is enabled.
+    pub fn duplicates(&self) -> impl Iterator<Item = &ItemReport> {
+        self.items.iter().filter(|item| item.duplicate_of.is_some())
+    }
+
+    /// Returns errors that occurred while the items were retrieved, e.g. failed pages
+    /// passed to [download_iter][super::Downloader::download_iter].
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Returns checksums of the downloaded files whose paths are inside of `base`.
+    /// Checksums are computed only if [enabled][super::Downloader::set_checksums].
+    pub fn manifest<P: AsRef<Path>>(&self, base: P) -> Manifest {
+        let mut result = Manifest::new();
+        for item in &self.items {
+            let path = item.path().and_then(|path| manifest::relative_path(path, base.as_ref()));
+            if let (Some(path), Some(checksum)) = (path, &item.checksum) {
+                result.insert(path, checksum.as_str());
+            }
+        }
+        result
+    }
+
+    /// Returns failed items in the form that can be persisted.
+    pub fn failures(&self) -> Vec<Failure> {
+        self.items
+            .iter()
+            .filter_map(|item| match &item.outcome {
+                DownloadOutcome::Failed(error) => Some(Failure {
+                    media_id: item.media.id(),
+                    parent_id: item.parent_id,
+                    error: error.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns `true` if no item failed and all items have been retrieved.
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none() && self.errors.is_empty()
+    }
+}
+
+impl ItemReport {
+    pub(super) fn new(
+        media: Media,
+        parent_id: Option<u64>,
+        outcome: DownloadOutcome,
+        attempts: u32,
+    ) -> Self {
+        Self { media, parent_id, outcome, attempts, checksum: None, duplicate_of: None }
+    }
+
+    pub(super) fn with_checksum(mut self, checksum: String) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    pub(super) fn with_duplicate_of(mut self, original: PathBuf) -> Self {
+        self.duplicate_of = Some(original);
+        self
+    }
+
+    pub fn media(&self) -> &Media {
+        &self.media
+    }
+
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    pub fn outcome(&self) -> &DownloadOutcome {
+        &self.outcome
+    }
+
+    /// Returns the SHA-256 checksum of the downloaded file as a lowercase hex string.
+    pub fn checksum(&self) -> Option<&str> {
+        self.checksum.as_deref()
+    }
+
+    /// Returns the path of the file with the same content saved earlier. The file of this item
+    /// is a link to it or, if duplicates are skipped, it's removed.
+    pub fn duplicate_of(&self) -> Option<&Path> {
+        self.duplicate_of.as_deref()
+    }
+
+    /// Returns the number of download attempts. It's zero for items that aren't downloaded.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Returns the path of the downloaded file or album directory.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.outcome {
+            DownloadOutcome::Downloaded(path) => Some(path),
+            _ => None,
+        }
+    }
+}
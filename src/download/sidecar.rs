@@ -0,0 +1,56 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Metadata files that accompany downloaded media.
+
+use super::sink::Target;
+use crate::{export, user::Media};
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the sidecar of a file or album directory: `<name>.json`.
+pub(super) fn path(content: &Path) -> PathBuf {
+    let mut path = content.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Puts the sidecar of the file or album directory at `content` to `target`.
+pub(super) fn put(
+    target: &Target,
+    content: &Path,
+    media: &Media,
+    parent_id: Option<u64>,
+) -> crate::Result<()> {
+    target.put_bytes(&path(content), to_string(media, parent_id)?.into_bytes())
+}
+
+/// Formats metadata of `media` and the ID of its album as JSON.
+/// Fields are the same as the ones of [JsonExporter][crate::export::JsonExporter].
+pub(super) fn to_string(media: &Media, parent_id: Option<u64>) -> serde_json::Result<String> {
+    let mut json = export::to_json(media);
+    json["parent_id"] = serde_json::json!(parent_id);
+    serde_json::to_string_pretty(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{download::LocalSink, user::tests::media};
+    use std::{fs, sync::Arc};
+
+    #[test]
+    fn put_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = dir.path().join("1.jpg");
+        assert_eq!(path(&content), dir.path().join("1.jpg.json"));
+
+        let target = Target::new(Arc::new(LocalSink::new(dir.path())), dir.path());
+        put(&target, &content, &media(2), Some(1)).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_slice(&fs::read(path(&content)).unwrap()).unwrap();
+        assert_eq!(json["id"], 2);
+        assert_eq!(json["parent_id"], 1);
+        assert_eq!(json["media_type"], "IMAGE");
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Storages that downloads are put to, like a local directory or object storage.
+
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "webdav")]
+mod webdav;
+
+#[cfg(feature = "s3")]
+pub use s3::S3Sink;
+#[cfg(feature = "webdav")]
+pub use webdav::WebDavSink;
+
+use super::manifest;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Storage that downloaded files are put to, e.g. a directory, an archive or object storage.
+/// [Downloader][super::Downloader] puts files to a [LocalSink] by default, another sink is set
+/// by [set_sink][super::Downloader::set_sink]. Files are put from several threads at once.
+///
+/// # Examples
+/// Print paths instead of storing files:
+/// ```
+/// use instapi::download::StorageSink;
+/// use std::io::Read;
+///
+/// struct PrintSink;
+///
+/// impl StorageSink for PrintSink {
+///     fn put(
+///         &self,
+///         path: &str,
+///         mut content: Box<dyn Read + Send>,
+///         size: u64,
+///     ) -> Result<(), Box<dyn std::error::Error>> {
+///         std::io::copy(&mut content, &mut std::io::sink())?;
+///         println!("{}: {} bytes", path, size);
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait StorageSink: Send + Sync {
+    /// Stores `size` bytes of `content` at `path`, replacing the existing file.
+    /// Paths are relative and separated by slashes. The content should be streamed
+    /// rather than read into memory, as videos may be large.
+    fn put(&self, path: &str, content: Box<dyn Read + Send>, size: u64) -> crate::Result<()>;
+
+    /// Moves the complete local `file` to `path`. By default the file is streamed
+    /// by [put][StorageSink::put] and removed afterwards.
+    fn put_file(&self, path: &str, file: &Path) -> crate::Result<()> {
+        let content = File::open(file)?;
+        let size = content.metadata()?.len();
+        self.put(path, Box::new(content), size)?;
+        fs::remove_file(file)?;
+        Ok(())
+    }
+
+    /// Creates a directory at `path`. Directories are created before their contents.
+    /// Does nothing by default, for storages without directories.
+    fn create_dir(&self, _path: &str) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the local directory that files are stored in. Files of local sinks are
+    /// downloaded in place, while files of other sinks are downloaded to the destination
+    /// of the downloader first. `None` by default.
+    fn local_dir(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Sink that stores files in a local directory.
+#[derive(Clone, Debug)]
+pub struct LocalSink {
+    root: PathBuf,
+}
+
+/// Sink of a single download along with the directories that paths are resolved against.
+pub(super) struct Target {
+    sink: Arc<dyn StorageSink>,
+    /// Directory that paths of the downloaded files start with: the directory
+    /// of a local sink or an empty path.
+    root: PathBuf,
+    /// Local directory that files are downloaded to before they're put to a sink
+    /// that isn't local.
+    staging: Option<PathBuf>,
+}
+
+impl LocalSink {
+    /// Constructs a sink that stores files inside of `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl StorageSink for LocalSink {
+    fn put(&self, path: &str, mut content: Box<dyn Read + Send>, _size: u64) -> crate::Result<()> {
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut content, &mut File::create(path)?)?;
+        Ok(())
+    }
+
+    /// Renames `file`, which is expected to be on the same file system.
+    fn put_file(&self, path: &str, file: &Path) -> crate::Result<()> {
+        fs::rename(file, self.root.join(path))?;
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &str) -> crate::Result<()> {
+        fs::create_dir_all(self.root.join(path))?;
+        Ok(())
+    }
+
+    fn local_dir(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+}
+
+impl Target {
+    /// Constructs a target that stages files in `destination` if `sink` isn't local.
+    pub(super) fn new(sink: Arc<dyn StorageSink>, destination: &Path) -> Self {
+        match sink.local_dir().map(Path::to_path_buf) {
+            Some(root) => Self { sink, root, staging: None },
+            None => Self { sink, root: PathBuf::new(), staging: Some(destination.to_path_buf()) },
+        }
+    }
+
+    pub(super) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub(super) fn is_local(&self) -> bool {
+        self.staging.is_none()
+    }
+
+    /// Returns the local path that the content of `path` is written to before it's put
+    /// to the sink. Paths of local sinks are returned as is.
+    pub(super) fn local_path(&self, path: &Path) -> PathBuf {
+        match &self.staging {
+            Some(staging) => staging.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Creates `dir` and its parents that aren't in `created` yet, both locally
+    /// and in the sink, and adds them to `created`.
+    pub(super) fn create_dirs(
+        &self,
+        dir: &Path,
+        created: &mut HashSet<PathBuf>,
+    ) -> crate::Result<()> {
+        if created.contains(dir) {
+            return Ok(());
+        }
+        fs::create_dir_all(self.local_path(dir))?;
+        let mut missing: Vec<_> = dir
+            .ancestors()
+            .take_while(|dir| *dir != self.root && !created.contains(*dir))
+            .collect();
+        missing.reverse();
+        for dir in missing {
+            // Directories of local sinks have been created along with the local ones.
+            if !self.is_local() {
+                self.sink.create_dir(&self.sink_path(dir)?)?;
+            }
+            created.insert(dir.to_path_buf());
+        }
+        created.insert(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Moves the complete local `file` to `path`.
+    pub(super) fn put_file(&self, path: &Path, file: &Path) -> crate::Result<()> {
+        self.sink.put_file(&self.sink_path(path)?, file)
+    }
+
+    /// Copies the local `file`, which is kept, to `path`.
+    pub(super) fn copy_file(&self, path: &Path, file: &Path) -> crate::Result<()> {
+        let content = File::open(file)?;
+        let size = content.metadata()?.len();
+        self.sink.put(&self.sink_path(path)?, Box::new(content), size)
+    }
+
+    /// Stores `content` at `path`.
+    pub(super) fn put_bytes(&self, path: &Path, content: Vec<u8>) -> crate::Result<()> {
+        let size = content.len() as u64;
+        self.sink.put(&self.sink_path(path)?, Box::new(io::Cursor::new(content)), size)
+    }
+
+    /// Converts `path` to the form used by sinks: relative to the root and separated by slashes.
+    fn sink_path(&self, path: &Path) -> crate::Result<String> {
+        Ok(manifest::relative_path(path, &self.root).ok_or("path can't be stored")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        download::{ContentCache, Downloader},
+        policy::{Action, Rule, SyncPolicy},
+        user::tests::{app_profile, media},
+    };
+    use std::{collections::BTreeMap, sync::Mutex};
+
+    /// Keeps contents of files in memory.
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        files: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+    }
+
+    impl StorageSink for MemorySink {
+        fn put(
+            &self,
+            path: &str,
+            mut content: Box<dyn Read + Send>,
+            size: u64,
+        ) -> crate::Result<()> {
+            let mut buffer = Vec::new();
+            content.read_to_end(&mut buffer)?;
+            assert_eq!(buffer.len() as u64, size);
+            self.files.lock().unwrap().insert(path.to_string(), buffer);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn local_sink() {
+        let root = tempfile::tempdir().unwrap();
+        let mut downloader = Downloader::new(app_profile(), "unused");
+        downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::MetadataOnly)] });
+        downloader.set_sidecars(true);
+        downloader.set_layout(Some("{type}".parse().unwrap()));
+        downloader.set_naming(|_| "same".to_string());
+        downloader.set_sink(LocalSink::new(root.path()));
+
+        let report = downloader.download_all(vec![media(2), media(1)]);
+        let json = fs::read_to_string(root.path().join("image").join("same_1.json"));
+        assert!(report.is_success());
+        assert!(json.unwrap().contains("\"parent_id\": null"));
+    }
+
+    #[test]
+    fn remote_sink() {
+        let destination = tempfile::tempdir().unwrap();
+        let cache = ContentCache::open(destination.path().join("cache")).unwrap();
+        cache.insert("1", Some("jpg"), &mut b"abc".as_slice()).unwrap();
+        let sink = MemorySink::default();
+        let mut downloader = Downloader::new(app_profile(), destination.path());
+        downloader.set_cache(Some(cache));
+        downloader.set_sidecars(true);
+        downloader.set_layout(Some("{type}".parse().unwrap()));
+        downloader.set_naming(|_| "a".to_string());
+        downloader.set_sink(sink.clone());
+
+        // Paths of the report are relative to the root of the sink.
+        let report = downloader.download(media(1));
+        assert_eq!(report.items()[0].path(), Some(Path::new("image").join("a.jpg").as_path()));
+        let files = sink.files.lock().unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["image/a.jpg", "image/a.jpg.json"]);
+        assert_eq!(files["image/a.jpg"], b"abc");
+        drop(files);
+
+        let staged = destination.path().join("b.part");
+        fs::write(&staged, "def").unwrap();
+        sink.put_file("b.jpg", &staged).unwrap();
+        assert!(!staged.exists());
+        assert_eq!(sink.files.lock().unwrap()["b.jpg"], b"def");
+    }
+}
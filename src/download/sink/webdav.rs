@@ -0,0 +1,102 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Uploads to WebDAV servers.
+
+use super::StorageSink;
+use std::io::Read;
+
+use reqwest::{
+    blocking::{Body, Client},
+    Method, StatusCode,
+};
+use url::Url;
+
+/// Sink that uploads files to a collection of a WebDAV server, like Nextcloud.
+/// Directories are created using `MKCOL` requests.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::download::WebDavSink;
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let base = "https://cloud.example.com/remote.php/dav/files/user/backup/".parse()?;
+/// let mut sink = WebDavSink::new(base)?;
+/// sink.set_credentials("user", "app password");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct WebDavSink {
+    http: Client,
+    base: Url,
+    credentials: Option<(String, String)>,
+}
+
+impl WebDavSink {
+    /// Constructs a sink that uploads files inside of the `base` collection.
+    ///
+    /// Returns an error if an HTTP client can't be initialized.
+    pub fn new(mut base: Url) -> crate::Result<Self> {
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        Ok(Self { http: Client::builder().build()?, base, credentials: None })
+    }
+
+    /// Sets credentials of the basic authentication.
+    pub fn set_credentials<U: Into<String>, P: Into<String>>(&mut self, username: U, password: P) {
+        self.credentials = Some((username.into(), password.into()));
+    }
+
+    /// Returns the URL of `path` inside of the base collection.
+    fn url(&self, path: &str) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            // The trailing slash of the base produces an empty segment.
+            segments.pop_if_empty().extend(path.split('/'));
+        }
+        url
+    }
+
+    fn request(&self, method: Method, path: &str) -> reqwest::blocking::RequestBuilder {
+        let request = self.http.request(method, self.url(path));
+        match &self.credentials {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+}
+
+impl StorageSink for WebDavSink {
+    fn put(&self, path: &str, content: Box<dyn Read + Send>, size: u64) -> crate::Result<()> {
+        let body = Body::sized(content, size);
+        self.request(Method::PUT, path).body(body).send()?.error_for_status()?;
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &str) -> crate::Result<()> {
+        // The method name is valid, so parsing can't fail.
+        let mkcol = Method::from_bytes(b"MKCOL").unwrap();
+        let response = self.request(mkcol, path).send()?;
+        // The collection exists already.
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(());
+        }
+        response.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let sink = WebDavSink::new("https://example.com/dav".parse().unwrap()).unwrap();
+        assert_eq!(sink.url("album/a b.jpg").as_str(), "https://example.com/dav/album/a%20b.jpg");
+    }
+}
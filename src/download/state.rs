@@ -0,0 +1,191 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! State of incremental syncs.
+
+use super::{manifest, DownloadOutcome, DownloadReport};
+use crate::user::Media;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs, io,
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What previous [syncs][super::Downloader::sync] have processed. Stored as JSON
+/// in the destination directory, so a sync retrieves only the items published since.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SyncState {
+    /// Items published before this date have been processed. It doesn't move past
+    /// items that failed to download, so they're tried again during the next sync.
+    pub last_seen_timestamp: Option<DateTime<Utc>>,
+    /// ID of the newest item seen. Used for items without a publish date.
+    pub last_seen_id: Option<u64>,
+    /// IDs of the items that have been downloaded, whose metadata only has been kept
+    /// or that are skipped as duplicates.
+    pub downloaded: BTreeSet<u64>,
+    /// Paths of the downloaded files and album directories relative to the destination
+    /// by IDs of the items. Album contents are stored in the directories of their albums.
+    pub paths: BTreeMap<u64, String>,
+}
+
+impl SyncState {
+    /// Name of the file in the destination directory.
+    pub const FILENAME: &'static str = ".instapi-sync.json";
+
+    /// Reads the state from `path`. Returns an empty state if the file doesn't exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(json.as_str())?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Serializes and writes the state, overwriting the existing one.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Checks if `media` has been processed already.
+    pub fn is_known(&self, media: &Media) -> bool {
+        self.downloaded.contains(&media.id())
+    }
+
+    /// Checks if `media` is older than the items of the previous syncs, so the pagination
+    /// through newer ones can stop.
+    pub fn reaches_known(&self, media: &Media) -> bool {
+        match (media.timestamp_utc(), self.last_seen_timestamp) {
+            (Some(timestamp), Some(last_seen)) => timestamp < last_seen,
+            (None, _) => self.last_seen_id == Some(media.id()),
+            _ => false,
+        }
+    }
+
+    /// Records the results of a sync to `destination`. The items of the report are expected
+    /// to be in the order of the API: newest first.
+    pub fn update<P: AsRef<Path>>(&mut self, report: &DownloadReport, destination: P) {
+        self.record(report, destination);
+        let incomplete = incomplete_albums(report);
+        let top_level: Vec<_> =
+            report.items().iter().filter(|item| item.parent_id().is_none()).collect();
+        let pending: Vec<_> = top_level
+            .iter()
+            .filter(|item| {
+                matches!(item.outcome(), DownloadOutcome::Failed(_))
+                    || incomplete.contains(&item.media().id())
+            })
+            .map(|item| item.media().timestamp_utc())
+            .collect();
+
+        // Pending items without a publish date can't be placed in the order,
+        // so the position of the previous syncs is kept to process them again.
+        if pending.contains(&None) {
+            return;
+        }
+        let newest = top_level.iter().filter_map(|item| item.media().timestamp_utc()).max();
+        self.last_seen_timestamp = match (pending.into_iter().flatten().min(), newest) {
+            (Some(pending), _) => Some(pending),
+            (None, newest) => newest.max(self.last_seen_timestamp),
+        };
+        if let Some(item) = top_level.first() {
+            self.last_seen_id = Some(item.media().id());
+        }
+    }
+
+    /// Records the items of a sync to `destination` that have been processed without moving
+    /// the position of the previous syncs, e.g. if the sync is incomplete, so the rest
+    /// of the items is gathered again by the next sync.
+    pub fn record<P: AsRef<Path>>(&mut self, report: &DownloadReport, destination: P) {
+        let incomplete = incomplete_albums(report);
+        self.downloaded.extend(
+            report
+                .succeeded()
+                .chain(report.duplicates())
+                .map(|item| item.media().id())
+                .filter(|id| !incomplete.contains(id)),
+        );
+        for item in report.items().iter().filter(|item| item.parent_id().is_none()) {
+            let path = item.path().and_then(|path| {
+                manifest::relative_path(path, destination.as_ref())
+            });
+            if let Some(path) = path {
+                self.paths.insert(item.media().id(), path);
+            }
+        }
+    }
+}
+
+/// Returns IDs of the albums whose contents failed to download. They aren't recorded,
+/// so they're processed again.
+fn incomplete_albums(report: &DownloadReport) -> HashSet<u64> {
+    report.failed().filter_map(|item| item.parent_id()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        download::ItemReport,
+        user::tests::{media, undated},
+    };
+
+    use chrono::TimeZone;
+
+    #[test]
+    fn update() {
+        let report = DownloadReport::new(vec![
+            ItemReport::new(media(3), None, DownloadOutcome::Failed("timeout".into()), 3),
+            ItemReport::new(media(2), None, DownloadOutcome::Downloaded("dir/2.jpg".into()), 1),
+            ItemReport::new(media(1), None, DownloadOutcome::Skipped, 0),
+        ]);
+        let mut state = SyncState::default();
+        state.update(&report, "dir");
+
+        assert_eq!(state.downloaded, BTreeSet::from([2]));
+        assert_eq!(state.paths[&2], "2.jpg");
+        assert_eq!(state.last_seen_id, Some(3));
+        assert!(state.is_known(&media(2)));
+        // The failed item has the same date, so it isn't skipped.
+        assert!(!state.is_known(&media(3)));
+        assert!(!state.reaches_known(&media(3)));
+    }
+
+    #[test]
+    fn keep_position_for_undated_failures() {
+        let report = DownloadReport::new(vec![
+            ItemReport::new(undated(3), None, DownloadOutcome::Failed("timeout".into()), 3),
+            ItemReport::new(media(2), None, DownloadOutcome::Downloaded("dir/2.jpg".into()), 1),
+        ]);
+        let previous = Utc.timestamp_opt(-1, 0).single();
+        let mut state = SyncState { last_seen_timestamp: previous, ..SyncState::default() };
+        state.update(&report, "dir");
+
+        assert_eq!(state.downloaded, BTreeSet::from([2]));
+        assert_eq!(state.last_seen_timestamp, previous);
+        assert_eq!(state.last_seen_id, None);
+        assert!(!state.reaches_known(&undated(3)));
+    }
+
+    #[test]
+    fn record() {
+        let report = DownloadReport::new(vec![ItemReport::new(
+            media(2),
+            None,
+            DownloadOutcome::Downloaded("dir/2.jpg".into()),
+            1,
+        )]);
+        let mut state = SyncState::default();
+        state.record(&report, "dir");
+
+        assert!(state.is_known(&media(2)));
+        assert_eq!(state.paths[&2], "2.jpg");
+        assert_eq!(state.last_seen_timestamp, None);
+        assert_eq!(state.last_seen_id, None);
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Templates of names of downloaded files.
+
+use crate::user::Media;
+use std::{convert::TryFrom, error::Error, fmt, mem, str::FromStr};
+
+use chrono::format::{Item, StrftimeItems};
+use serde::{Deserialize, Serialize};
+
+/// Format of `{timestamp}` if it isn't specified.
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%FT%H-%M-%S";
+
+/// Template of names of downloaded files and album directories without extensions,
+/// e.g. `{username}_{timestamp:%Y-%m-%d}_{shortcode}`. Supported fields:
+/// - `{id}`: ID of the media item;
+/// - `{username}`: owner's username, empty if the field hasn't been requested;
+/// - `{timestamp}` or `{timestamp:<format>}`: publish date in the
+///   [strftime][chrono::format::strftime] format, `%FT%H-%M-%S` by default,
+///   or `unknown-date` if the date is unknown;
+/// - `{type}`: `image`, `video` or `carousel_album`;
+/// - `{shortcode}`: short code of the permalink, empty if it's unavailable;
+/// - `{album_id}`: ID of the album the item is in, empty if it isn't in an album.
+///
+/// Braces are escaped by doubling them: `{{` and `}}`. Templates with slashes
+/// define the [layout][super::Downloader::set_layout] of directories.
+/// Templates are serialized as strings.
+///
+/// # Examples
+/// ```
+/// use instapi::download::FilenameTemplate;
+///
+/// let template: FilenameTemplate = "{timestamp:%Y-%m-%d}_{id}".parse().unwrap();
+/// assert!("{unknown}".parse::<FilenameTemplate>().is_err());
+/// ```
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct FilenameTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Part {
+    Text(String),
+    Id,
+    Username,
+    Timestamp(String),
+    Type,
+    Shortcode,
+    AlbumId,
+}
+
+/// Reason why a [FilenameTemplate] can't be parsed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TemplateError {
+    /// Contains the field with its format, if any.
+    UnknownField(String),
+    InvalidTimestampFormat(String),
+    /// A field isn't closed by `}`.
+    UnclosedField,
+    /// A closing brace isn't escaped.
+    UnmatchedBrace,
+}
+
+impl FilenameTemplate {
+    /// Constructs a name of `media`. `album_id` is the ID of the album the item is in.
+    pub fn render(&self, media: &Media, album_id: Option<u64>) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Id => name.push_str(&media.id().to_string()),
+                Part::Username => name.push_str(media.username().unwrap_or_default()),
+                Part::Timestamp(format) => match media.timestamp() {
+                    Some(timestamp) => name.push_str(&timestamp.format(format).to_string()),
+                    None => name.push_str("unknown-date"),
+                },
+                Part::Type => name.push_str(&media.media_type().as_str().to_ascii_lowercase()),
+                Part::Shortcode => name.push_str(media.shortcode().unwrap_or_default()),
+                Part::AlbumId => {
+                    if let Some(album_id) = album_id {
+                        name.push_str(&album_id.to_string());
+                    }
+                }
+            }
+        }
+        name
+    }
+}
+
+/// Returns the template of [default_filename][super::default_filename]:
+/// `{username}_{id}_{timestamp}`.
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self {
+            parts: vec![
+                Part::Username,
+                Part::Text("_".to_string()),
+                Part::Id,
+                Part::Text("_".to_string()),
+                Part::Timestamp(DEFAULT_TIMESTAMP_FORMAT.to_string()),
+            ],
+        }
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = TemplateError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek() == Some(&c) => {
+                    chars.next();
+                    text.push(c);
+                }
+                '}' => return Err(TemplateError::UnmatchedBrace),
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(TemplateError::UnclosedField),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(mem::take(&mut text)));
+                    }
+                    parts.push(Part::parse(field)?);
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl TryFrom<String> for FilenameTemplate {
+    type Error = TemplateError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        template.parse()
+    }
+}
+
+impl From<FilenameTemplate> for String {
+    fn from(template: FilenameTemplate) -> Self {
+        template.to_string()
+    }
+}
+
+/// Formats the template in the form it's parsed from.
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => f.write_str(&text.replace('{', "{{").replace('}', "}}"))?,
+                Part::Id => f.write_str("{id}")?,
+                Part::Username => f.write_str("{username}")?,
+                Part::Timestamp(format) => write!(f, "{{timestamp:{}}}", format)?,
+                Part::Type => f.write_str("{type}")?,
+                Part::Shortcode => f.write_str("{shortcode}")?,
+                Part::AlbumId => f.write_str("{album_id}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Part {
+    fn parse(field: String) -> Result<Self, TemplateError> {
+        let (name, format) = match field.split_once(':') {
+            Some((name, format)) => (name, Some(format)),
+            None => (field.as_str(), None),
+        };
+        match (name, format) {
+            ("id", None) => Ok(Self::Id),
+            ("username", None) => Ok(Self::Username),
+            ("type", None) => Ok(Self::Type),
+            ("shortcode", None) => Ok(Self::Shortcode),
+            ("album_id", None) => Ok(Self::AlbumId),
+            ("timestamp", None) => Ok(Self::Timestamp(DEFAULT_TIMESTAMP_FORMAT.to_string())),
+            ("timestamp", Some(format)) => {
+                // Invalid formats make chrono panic during formatting.
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    Err(TemplateError::InvalidTimestampFormat(format.to_string()))
+                } else {
+                    Ok(Self::Timestamp(format.to_string()))
+                }
+            }
+            _ => Err(TemplateError::UnknownField(field)),
+        }
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownField(field) => write!(f, "unknown field \"{}\"", field),
+            Self::InvalidTimestampFormat(format) => {
+                write!(f, "invalid timestamp format \"{}\"", format)
+            }
+            Self::UnclosedField => write!(f, "field isn't closed by '}}'"),
+            Self::UnmatchedBrace => write!(f, "unmatched '}}', use '}}}}' to escape it"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{download::default_filename, user::tests::media};
+
+    #[test]
+    fn render() {
+        let media = media(2);
+        assert_eq!(FilenameTemplate::default().render(&media, None), default_filename(&media));
+
+        let template: FilenameTemplate = "{{{type}}}_{id}-{album_id}{shortcode}".parse().unwrap();
+        assert_eq!(template.render(&media, Some(1)), "{image}_2-1");
+        assert_eq!(template.render(&media, None), "{image}_2-");
+    }
+
+    #[test]
+    fn serialize() {
+        let template: FilenameTemplate = "{{{type}}}/{timestamp:%Y}_{id}".parse().unwrap();
+        let json = serde_json::to_string(&template).unwrap();
+        assert_eq!(json, r#""{{{type}}}/{timestamp:%Y}_{id}""#);
+        assert_eq!(serde_json::from_str::<FilenameTemplate>(&json).unwrap(), template);
+        assert!(serde_json::from_str::<FilenameTemplate>(r#""{unknown}""#).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let parse = |template: &str| template.parse::<FilenameTemplate>().unwrap_err();
+        assert_eq!(parse("{id:x}"), TemplateError::UnknownField("id:x".to_string()));
+        assert_eq!(parse("{timestamp:%Q}"), TemplateError::InvalidTimestampFormat("%Q".into()));
+        assert_eq!(parse("{id"), TemplateError::UnclosedField);
+        assert_eq!(parse("id}"), TemplateError::UnmatchedBrace);
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Per-host limits of requests, so downloads don't trip CDN throttling.
+
+use std::{
+    collections::HashMap,
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Limits the number of in-flight requests to a host and delays the start of requests.
+pub(super) struct Throttle {
+    max_in_flight: usize,
+    delay: Duration,
+    hosts: Mutex<HashMap<String, HostState>>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct HostState {
+    in_flight: usize,
+    next_start: Option<Instant>,
+}
+
+/// Permission to send a request. The slot is released on drop.
+pub(super) struct Permit<'a> {
+    throttle: &'a Throttle,
+    host: String,
+}
+
+impl Throttle {
+    pub(super) fn new(max_in_flight: usize, delay: Duration) -> Self {
+        Self { max_in_flight, delay, hosts: Mutex::default(), released: Condvar::new() }
+    }
+
+    /// Blocks until a request to `host` is allowed: there is a free slot and at least
+    /// the delay has passed since the start of the previous request.
+    pub(super) fn acquire(&self, host: &str) -> Permit<'_> {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let state = hosts.entry(host.to_string()).or_default();
+            let now = Instant::now();
+            let wait = match state.next_start {
+                _ if state.in_flight >= self.max_in_flight => None,
+                Some(next_start) if next_start > now => Some(next_start - now),
+                _ => {
+                    state.in_flight += 1;
+                    state.next_start = Some(now + self.delay);
+                    return Permit { throttle: self, host: host.to_string() };
+                }
+            };
+            // Another thread may start a request in the meantime, so the state is checked again.
+            hosts = match wait {
+                Some(timeout) => {
+                    self.released.wait_timeout(hosts, timeout).unwrap_or_else(|e| e.into_inner()).0
+                }
+                None => self.released.wait(hosts).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut hosts = self.throttle.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(state) = hosts.get_mut(&self.host) {
+            state.in_flight -= 1;
+        }
+        self.throttle.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn limit_in_flight() {
+        let throttle = Arc::new(Throttle::new(1, Duration::ZERO));
+        let permit = throttle.acquire("example.com");
+        // Other hosts aren't affected.
+        drop(throttle.acquire("example.org"));
+
+        let other = Arc::clone(&throttle);
+        let handle = thread::spawn(move || drop(other.acquire("example.com")));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        drop(permit);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn delay_requests() {
+        let throttle = Throttle::new(2, Duration::from_millis(50));
+        let start = Instant::now();
+        drop(throttle.acquire("example.com"));
+        drop(throttle.acquire("example.com"));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
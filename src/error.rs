@@ -0,0 +1,254 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Typed errors returned by the API.
+
+use std::fmt;
+
+use reqwest::blocking::Response;
+use serde::Deserialize;
+
+const SUNSET_REPLACEMENT_HINT: &str = "migrate to the Instagram API with Instagram Login: \
+    https://developers.facebook.com/docs/instagram-platform/instagram-api-with-instagram-login";
+
+/// Error that occurred while performing an API request.
+///
+/// Functions of this crate return boxed errors, use
+/// [downcast_ref][std::error::Error::downcast_ref] to get this type.
+///
+/// # Examples
+/// ```no_run
+/// # fn example(profile: instapi::user::Profile<instapi::auth::LongLivedToken>) {
+/// if let Err(e) = profile.info() {
+///     match e.downcast_ref::<instapi::Error>() {
+///         Some(e) if e.is_recoverable_by_reauth() => println!("Log in again: {}", e),
+///         _ => println!("Failed: {}", e),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Error {
+    /// Token has been expired. Contains `None` if it's detected before sending a request.
+    TokenExpired(Option<ApiError>),
+    /// Token has been invalidated: the user revoked the app authorization, changed the password
+    /// or the token is malformed.
+    TokenRevoked(ApiError),
+    /// The user didn't grant a permission required by the request.
+    InsufficientPermission(ApiError),
+    /// The endpoint has been shut down. `replacement_hint` describes the API to migrate to.
+    ApiSunset {
+        replacement_hint: &'static str,
+        error: ApiError,
+    },
+    /// Any other error reported by the API.
+    Api(ApiError),
+}
+
+/// Details of an error reported by the API.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    /// HTTP status code of the response.
+    pub status: u16,
+    /// Error type, like `OAuthException`.
+    pub error_type: Option<String>,
+    /// Graph API error code (`code`), like `190` for invalid tokens.
+    pub code: Option<i64>,
+    /// Graph API error subcode (`error_subcode`) that refines the code, like `463`
+    /// for expired tokens.
+    pub subcode: Option<i64>,
+    /// Human-readable description of the error reported by the API.
+    pub message: String,
+}
+
+/// Abstractions over JSON responses.
+mod response {
+    use super::Deserialize;
+
+    /// Error body of both the Graph API and the authorization endpoints.
+    #[derive(Deserialize)]
+    pub(super) struct Body {
+        pub(super) error: Option<GraphError>,
+        pub(super) error_type: Option<String>,
+        pub(super) code: Option<i64>,
+        pub(super) error_message: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct GraphError {
+        pub(super) message: String,
+        #[serde(rename = "type")]
+        pub(super) error_type: Option<String>,
+        pub(super) code: Option<i64>,
+        pub(super) error_subcode: Option<i64>,
+    }
+}
+
+impl Error {
+    /// Checks if the error can be resolved by performing the authorization again.
+    pub fn is_recoverable_by_reauth(&self) -> bool {
+        matches!(
+            self,
+            Self::TokenExpired(_) | Self::TokenRevoked(_) | Self::InsufficientPermission(_)
+        )
+    }
+
+    /// Returns details reported by the API, if the error came from a response.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::TokenExpired(error) => error.as_ref(),
+            Self::TokenRevoked(error) | Self::InsufficientPermission(error) | Self::Api(error) => {
+                Some(error)
+            }
+            Self::ApiSunset { error, .. } => Some(error),
+        }
+    }
+
+    /// Classifies an API error by its code and subcode.
+    fn from(error: ApiError) -> Self {
+        if error.is_sunset() {
+            return Self::ApiSunset { replacement_hint: SUNSET_REPLACEMENT_HINT, error };
+        }
+
+        // See https://developers.facebook.com/docs/graph-api/guides/error-handling.
+        match (error.code, error.subcode) {
+            (Some(190), Some(463)) => Self::TokenExpired(Some(error)),
+            (Some(190), _) | (Some(102), _) => {
+                if error.message.to_lowercase().contains("expired") {
+                    Self::TokenExpired(Some(error))
+                } else {
+                    Self::TokenRevoked(error)
+                }
+            }
+            (Some(10), _) => Self::InsufficientPermission(error),
+            (Some(code), _) if (200..300).contains(&code) => Self::InsufficientPermission(error),
+            _ => Self::Api(error),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::TokenExpired(_) => "token has been expired",
+            Self::TokenRevoked(_) => "token has been revoked",
+            Self::InsufficientPermission(_) => "permission hasn't been granted",
+            Self::ApiSunset { replacement_hint, .. } => {
+                return write!(f, "API has been shut down ({})", replacement_hint);
+            }
+            Self::Api(_) => "API request failed",
+        };
+        match self.api_error() {
+            Some(error) => write!(f, "{}: {}", description, error),
+            None => f.write_str(description),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ApiError {
+    /// Parses an error `body` of a response with the `status` code.
+    /// If the body isn't recognized, it's used as the message.
+    fn parse(status: u16, body: &str) -> Self {
+        let mut error = Self {
+            status,
+            error_type: None,
+            code: None,
+            subcode: None,
+            message: body.trim().to_string(),
+        };
+
+        if let Ok(body) = serde_json::from_str::<response::Body>(body) {
+            if let Some(graph_error) = body.error {
+                error.error_type = graph_error.error_type;
+                error.code = graph_error.code;
+                error.subcode = graph_error.error_subcode;
+                error.message = graph_error.message;
+            } else if let Some(message) = body.error_message {
+                error.error_type = body.error_type;
+                error.code = body.code;
+                error.message = message;
+            }
+        }
+        error
+    }
+}
+
+impl ApiError {
+    /// Checks if the error reports that the Basic Display API is no longer available.
+    fn is_sunset(&self) -> bool {
+        let message = self.message.to_lowercase();
+        message.contains("basic display")
+            && ["deprecated", "no longer", "sunset", "shut down"]
+                .iter()
+                .any(|phrase| message.contains(phrase))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (HTTP {}", self.message, self.status)?;
+        if let Some(code) = self.code {
+            write!(f, ", code {}", code)?;
+        }
+        if let Some(subcode) = self.subcode {
+            write!(f, ", subcode {}", subcode)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Returns `response` if its status is successful, otherwise converts the body to an [Error].
+pub(crate) fn check(response: Response) -> crate::Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().unwrap_or_default();
+    Err(Box::new(Error::from(ApiError::parse(status.as_u16(), &body))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let expired = r#"{"error": {"message": "Session has expired",
+            "type": "OAuthException", "code": 190, "error_subcode": 463}}"#;
+        let revoked = r#"{"error": {"message": "The user has not authorized application",
+            "type": "OAuthException", "code": 190, "error_subcode": 458}}"#;
+        let permission = r#"{"error": {"message": "Permission denied", "code": 10}}"#;
+
+        assert!(matches!(Error::from(ApiError::parse(400, expired)), Error::TokenExpired(_)));
+        assert!(matches!(Error::from(ApiError::parse(400, revoked)), Error::TokenRevoked(_)));
+        assert!(matches!(
+            Error::from(ApiError::parse(403, permission)),
+            Error::InsufficientPermission(_)
+        ));
+        assert!(!Error::from(ApiError::parse(500, "")).is_recoverable_by_reauth());
+    }
+
+    #[test]
+    fn sunset() {
+        let body = r#"{"error": {"message": "Instagram Basic Display API is no longer available",
+            "type": "OAuthException", "code": 400}}"#;
+        let error = Error::from(ApiError::parse(400, body));
+        assert!(matches!(error, Error::ApiSunset { .. }));
+        assert!(error.to_string().contains("Instagram API with Instagram Login"));
+    }
+
+    #[test]
+    fn parse_auth_error() {
+        let body = r#"{"error_type": "OAuthException", "code": 400,
+            "error_message": "This authorization code has been used"}"#;
+        let error = ApiError::parse(400, body);
+        assert_eq!(error.error_type.as_deref(), Some("OAuthException"));
+        assert_eq!(error.message, "This authorization code has been used");
+
+        assert_eq!(ApiError::parse(502, " Bad Gateway ").message, "Bad Gateway");
+    }
+}
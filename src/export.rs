@@ -0,0 +1,330 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Streaming export of media metadata to various formats.
+
+mod markdown;
+
+pub use markdown::MarkdownExporter;
+
+use crate::user::{Media, ParseTypeError};
+use std::{io::Write, str::FromStr};
+
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+/// Output format of the export pipeline. Items are passed one by one,
+/// so an implementation doesn't need to keep the whole list in memory.
+///
+/// Implement this trait to plug a custom format into [export].
+pub trait Exporter {
+    /// Called once before the first item.
+    fn begin(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+    /// Writes a single media item.
+    fn item(&mut self, media: &Media) -> crate::Result<()>;
+    /// Called once after the last item.
+    fn finish(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes media items as a JSON array.
+pub struct JsonExporter<W> {
+    writer: W,
+    is_first: bool,
+}
+
+/// Writes media items as comma-separated values with a header row.
+/// Values are quoted if needed, absent values are empty.
+pub struct CsvExporter<W> {
+    writer: W,
+    columns: Vec<Column>,
+}
+
+/// Writes media items as JSON objects, one per line.
+pub struct JsonLinesExporter<W> {
+    writer: W,
+    columns: Vec<Column>,
+}
+
+/// Field of media metadata written by [CsvExporter] and [JsonLinesExporter].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Column {
+    Id,
+    MediaType,
+    Username,
+    Caption,
+    MediaProductType,
+    Timestamp,
+    MediaUrl,
+    Permalink,
+    ThumbnailUrl,
+}
+
+/// Passes all `media` items to `exporter`, preserving the order. To export only specific items
+/// filter or sort the iterator beforehand. Returns number of exported items.
+///
+/// # Examples
+/// ```no_run
+/// # fn example(profile: instapi::user::Profile<instapi::auth::LongLivedToken>)
+/// #         -> Result<(), Box<dyn std::error::Error>> {
+/// use instapi::{export::{self, JsonExporter}, user::MediaType};
+///
+/// let media = profile.media()?;
+/// let images = media.iter().filter(|media| media.media_type() == MediaType::Image);
+/// export::export(images, &mut JsonExporter::new(std::io::stdout()))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn export<'a, I, E>(media: I, exporter: &mut E) -> crate::Result<usize>
+where
+    I: IntoIterator<Item = &'a Media>,
+    E: Exporter + ?Sized,
+{
+    exporter.begin()?;
+    let mut count = 0;
+    for media in media {
+        exporter.item(media)?;
+        count += 1;
+    }
+    exporter.finish()?;
+    Ok(count)
+}
+
+impl<W: Write> JsonExporter<W> {
+    /// Constructs an exporter that writes to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer, is_first: true }
+    }
+
+    /// Consumes the exporter, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Exporter for JsonExporter<W> {
+    fn begin(&mut self) -> crate::Result<()> {
+        self.is_first = true;
+        Ok(self.writer.write_all(b"[")?)
+    }
+
+    fn item(&mut self, media: &Media) -> crate::Result<()> {
+        if !self.is_first {
+            self.writer.write_all(b",")?;
+        }
+        self.is_first = false;
+        Ok(serde_json::to_writer(&mut self.writer, &to_json(media))?)
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        self.writer.write_all(b"]")?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+impl<W: Write> CsvExporter<W> {
+    /// Constructs an exporter that writes [all columns][Column::ALL] to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer, columns: Column::ALL.to_vec() }
+    }
+
+    /// Sets columns to write in the given order.
+    pub fn set_columns(&mut self, columns: Vec<Column>) {
+        self.columns = columns;
+    }
+
+    /// Consumes the exporter, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_row<'a, I: IntoIterator<Item = &'a str>>(&mut self, fields: I) -> crate::Result<()> {
+        let row: Vec<_> = fields.into_iter().map(csv_field).collect();
+        Ok(self.writer.write_all(format!("{}\r\n", row.join(",")).as_bytes())?)
+    }
+}
+
+impl<W: Write> Exporter for CsvExporter<W> {
+    fn begin(&mut self) -> crate::Result<()> {
+        let columns = self.columns.clone();
+        self.write_row(columns.iter().map(|column| column.as_str()))
+    }
+
+    fn item(&mut self, media: &Media) -> crate::Result<()> {
+        let json = to_json(media);
+        let fields: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| match &json[column.as_str()] {
+                Value::Null => String::new(),
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+            .collect();
+        self.write_row(fields.iter().map(String::as_str))
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+impl<W: Write> JsonLinesExporter<W> {
+    /// Constructs an exporter that writes [all columns][Column::ALL] to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer, columns: Column::ALL.to_vec() }
+    }
+
+    /// Sets fields to write in the given order.
+    pub fn set_columns(&mut self, columns: Vec<Column>) {
+        self.columns = columns;
+    }
+
+    /// Consumes the exporter, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Exporter for JsonLinesExporter<W> {
+    fn item(&mut self, media: &Media) -> crate::Result<()> {
+        let json = to_json(media);
+        // Fields are written one by one, as maps of `serde_json` don't preserve the order.
+        let fields: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| format!("\"{}\":{}", column.as_str(), json[column.as_str()]))
+            .collect();
+        Ok(self.writer.write_all(format!("{{{}}}\n", fields.join(",")).as_bytes())?)
+    }
+
+    fn finish(&mut self) -> crate::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+impl Column {
+    /// All columns in the default order.
+    pub const ALL: [Column; 9] = [
+        Self::Id,
+        Self::MediaType,
+        Self::Username,
+        Self::Caption,
+        Self::MediaProductType,
+        Self::Timestamp,
+        Self::MediaUrl,
+        Self::Permalink,
+        Self::ThumbnailUrl,
+    ];
+
+    /// Returns the name of the column, which is the same as the key
+    /// used by [JsonExporter], like `media_type`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::MediaType => "media_type",
+            Self::Username => "username",
+            Self::Caption => "caption",
+            Self::MediaProductType => "media_product_type",
+            Self::Timestamp => "timestamp",
+            Self::MediaUrl => "media_url",
+            Self::Permalink => "permalink",
+            Self::ThumbnailUrl => "thumbnail_url",
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = ParseTypeError;
+
+    /// Parses the name of a column.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|column| column.as_str() == s)
+            .ok_or_else(|| ParseTypeError { type_name: "column", value: s.to_string() })
+    }
+}
+
+/// Quotes `field` if it contains separators, quotes or line breaks.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\r', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Converts `media` to a JSON object. Absent values are represented as `null`.
+pub(crate) fn to_json(media: &Media) -> serde_json::Value {
+    json!({
+        "id": media.id(),
+        "media_type": media.media_type().as_str(),
+        "username": media.username(),
+        "caption": media.caption(),
+        "media_product_type": media.media_product_type(),
+        "timestamp": media
+            .timestamp()
+            .map_or_else(|| media.raw_timestamp().to_string(), DateTime::to_rfc3339),
+        "media_url": media.media_url().as_str(),
+        "permalink": media.permalink().map(|url| url.as_str()),
+        "thumbnail_url": media.thumbnail_url().map(|url| url.as_str()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::tests::{captioned, media};
+
+    #[test]
+    fn json_exporter() {
+        let mut exporter = JsonExporter::new(Vec::new());
+        assert_eq!(export(&[media(1), media(2)], &mut exporter).unwrap(), 2);
+
+        let json: serde_json::Value = serde_json::from_slice(&exporter.into_inner()).unwrap();
+        assert_eq!(json[0]["id"], 1);
+        assert_eq!(json[1]["media_type"], "IMAGE");
+    }
+
+    #[test]
+    fn empty_export() {
+        let mut exporter = JsonExporter::new(Vec::new());
+        assert_eq!(export(&[], &mut exporter).unwrap(), 0);
+        assert_eq!(exporter.into_inner(), b"[]");
+    }
+
+    #[test]
+    fn csv_exporter() {
+        let mut exporter = CsvExporter::new(Vec::new());
+        exporter.set_columns(vec![Column::Id, Column::Caption, Column::Permalink]);
+        let media = [captioned(1, "Hello, \"world\"\nagain"), media(2)];
+        export(&media, &mut exporter).unwrap();
+        assert_eq!(
+            String::from_utf8(exporter.into_inner()).unwrap(),
+            "id,caption,permalink\r\n1,\"Hello, \"\"world\"\"\nagain\",\r\n2,,\r\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_exporter() {
+        let mut exporter = JsonLinesExporter::new(Vec::new());
+        exporter.set_columns(vec![Column::MediaType, Column::Id]);
+        export(&[media(1), media(2)], &mut exporter).unwrap();
+        assert_eq!(
+            String::from_utf8(exporter.into_inner()).unwrap(),
+            "{\"media_type\":\"IMAGE\",\"id\":1}\n{\"media_type\":\"IMAGE\",\"id\":2}\n"
+        );
+    }
+
+    #[test]
+    fn parse_column() {
+        assert!(Column::ALL.iter().all(|column| column.as_str().parse() == Ok(*column)));
+        assert!("ID".parse::<Column>().is_err());
+    }
+}
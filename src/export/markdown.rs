@@ -0,0 +1,187 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Markdown pages of posts for static site generators.
+
+use super::Exporter;
+use crate::{
+    download::DownloadReport,
+    feed,
+    user::{Media, MediaType},
+    util,
+};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Extensions of the files that are embedded as images, others are linked.
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "gif", "webp", "heic"];
+
+/// Writes a Markdown file per media item to a directory, e.g. the content directory
+/// of Hugo or Zola. Files are named `<media ID>.md` and start with TOML front matter:
+/// `title` (an excerpt of the caption) and `date` are top-level, `id`, `type`
+/// and `permalink` are in the `extra` table. The body contains the caption followed
+/// by links to the local files of the item, images are embedded.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::LongLivedToken, download::Downloader, export::{self, MarkdownExporter}};
+/// # use instapi::user::Profile;
+///
+/// # fn run(profile: Profile<LongLivedToken>) -> Result<(), Box<dyn std::error::Error>> {
+/// let media = profile.media()?;
+/// let report = Downloader::new(profile, "site/static/media").download_all(media.clone());
+/// let mut exporter = MarkdownExporter::new("site/content/posts");
+/// exporter.add_report(&report);
+/// export::export(&media, &mut exporter)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MarkdownExporter {
+    dir: PathBuf,
+    /// Local files of items by their IDs. Albums include files of their contents.
+    files: HashMap<u64, Vec<PathBuf>>,
+}
+
+impl MarkdownExporter {
+    /// Constructs an exporter that writes files to `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into(), files: HashMap::new() }
+    }
+
+    /// Links the item with `id` to the local file at `path`. The path is expected
+    /// to have the same base as the directory of the exporter, e.g. both are relative
+    /// to the current directory, so a relative link can be made.
+    pub fn add_file<P: Into<PathBuf>>(&mut self, id: u64, path: P) {
+        self.files.entry(id).or_default().push(path.into());
+    }
+
+    /// Links the downloaded items of `report` to their files. Albums are linked
+    /// to the files of their contents.
+    pub fn add_report(&mut self, report: &DownloadReport) {
+        for item in report.succeeded() {
+            let path = match item.path() {
+                Some(path) if item.media().media_type() != MediaType::CarouselAlbum => path,
+                _ => continue,
+            };
+            self.add_file(item.media().id(), path);
+            if let Some(parent_id) = item.parent_id() {
+                self.add_file(parent_id, path);
+            }
+        }
+    }
+
+    /// Returns the page of `media` that is written to `page_path`.
+    fn page(&self, media: &Media, page_path: &Path) -> String {
+        let mut page = String::from("+++\n");
+        // Writing to a string doesn't fail.
+        writeln!(page, "title = {}", toml_string(&feed::title(media))).unwrap();
+        if let Some(timestamp) = media.timestamp() {
+            writeln!(page, "date = {}", timestamp.to_rfc3339()).unwrap();
+        }
+        writeln!(page, "\n[extra]\nid = \"{}\"", media.id()).unwrap();
+        writeln!(page, "type = \"{}\"", media.media_type()).unwrap();
+        if let Some(permalink) = media.permalink() {
+            writeln!(page, "permalink = {}", toml_string(permalink.as_str())).unwrap();
+        }
+        page.push_str("+++\n");
+
+        if let Some(caption) = media.caption() {
+            writeln!(page, "\n{}", caption).unwrap();
+        }
+        for file in self.files.get(&media.id()).into_iter().flatten() {
+            let link = markdown_link(&util::relative_path(file, page_path));
+            let is_image = file
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+                });
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            if is_image {
+                writeln!(page, "\n![{}](<{}>)", name, link).unwrap();
+            } else {
+                writeln!(page, "\n[{}](<{}>)", name, link).unwrap();
+            }
+        }
+        page
+    }
+}
+
+impl Exporter for MarkdownExporter {
+    fn begin(&mut self) -> crate::Result<()> {
+        Ok(fs::create_dir_all(&self.dir)?)
+    }
+
+    fn item(&mut self, media: &Media) -> crate::Result<()> {
+        let path = self.dir.join(format!("{}.md", media.id()));
+        Ok(fs::write(&path, self.page(media, &path))?)
+    }
+}
+
+/// Formats `path` using forward slashes, as they're expected by Markdown on any platform.
+fn markdown_link(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| match component {
+            Component::ParentDir => "..".into(),
+            component => component.as_os_str().to_string_lossy(),
+        })
+        .collect();
+    components.join("/")
+}
+
+/// Formats `value` as a TOML basic string.
+fn toml_string(value: &str) -> String {
+    let mut string = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            c if c.is_control() => write!(string, "\\u{:04X}", c as u32).unwrap(),
+            c => string.push(c),
+        }
+    }
+    string.push('"');
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::tests::{captioned, media};
+
+    #[test]
+    fn pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let mut exporter = MarkdownExporter::new(dir.join("posts"));
+        exporter.add_file(1, dir.join("media").join("1 a.JPG"));
+        exporter.add_file(1, dir.join("media").join("1.mp4"));
+        let media = [captioned(1, "Sea \"view\"\n#sea"), media(2)];
+        let result = crate::export::export(&media, &mut exporter);
+        let page = fs::read_to_string(dir.join("posts").join("1.md"));
+        let other = fs::read_to_string(dir.join("posts").join("2.md"));
+
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(
+            page.unwrap(),
+            "+++\ntitle = \"Sea \\\"view\\\"\"\ndate = 1970-01-01T00:00:00+00:00\n\n[extra]\n\
+             id = \"1\"\ntype = \"IMAGE\"\n+++\n\nSea \"view\"\n#sea\n\n\
+             ![1 a.JPG](<../media/1 a.JPG>)\n\n[1.mp4](<../media/1.mp4>)\n"
+        );
+        assert!(other.unwrap().starts_with("+++\ntitle = \"Image 2\"\n"));
+    }
+
+    #[test]
+    fn toml_strings() {
+        assert_eq!(toml_string("a\\b\t\u{1}"), "\"a\\\\b\\t\\u0001\"");
+    }
+}
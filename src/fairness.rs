@@ -0,0 +1,184 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Weighted round-robin scheduling of work of several accounts.
+
+use std::sync::{Condvar, Mutex};
+
+/// Picks indices of accounts in proportion to their weights, spreading the picks
+/// of each account evenly instead of giving all its turns in a row
+/// (the smooth weighted round-robin of nginx).
+#[derive(Debug)]
+pub(crate) struct WeightedRoundRobin {
+    weights: Vec<i64>,
+    current: Vec<i64>,
+}
+
+/// Lets threads of several accounts take turns in the order of a [WeightedRoundRobin].
+pub(crate) struct Turns {
+    state: Mutex<TurnsState>,
+    changed: Condvar,
+}
+
+struct TurnsState {
+    order: WeightedRoundRobin,
+    /// Account whose turn it is, if it has been picked already.
+    current: Option<usize>,
+}
+
+impl WeightedRoundRobin {
+    /// Zero weights are treated as ones, so every account makes progress.
+    pub(crate) fn new(weights: &[u32]) -> Self {
+        Self {
+            weights: weights.iter().map(|&weight| i64::from(weight.max(1))).collect(),
+            current: vec![0; weights.len()],
+        }
+    }
+
+    /// Excludes the account at `index` from the following picks.
+    pub(crate) fn remove(&mut self, index: usize) {
+        self.weights[index] = 0;
+        self.current[index] = 0;
+    }
+}
+
+impl Iterator for WeightedRoundRobin {
+    type Item = usize;
+
+    /// Returns `None` once all accounts are removed.
+    fn next(&mut self) -> Option<usize> {
+        let total: i64 = self.weights.iter().sum();
+        for (current, weight) in self.current.iter_mut().zip(&self.weights) {
+            *current += weight;
+        }
+        // Earlier accounts win ties.
+        let (index, _) = self
+            .current
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.weights[*i] > 0)
+            .rev()
+            .max_by_key(|(_, current)| **current)?;
+        self.current[index] -= total;
+        Some(index)
+    }
+}
+
+impl Turns {
+    pub(crate) fn new(weights: &[u32]) -> Self {
+        Self {
+            state: Mutex::new(TurnsState {
+                order: WeightedRoundRobin::new(weights),
+                current: None,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Blocks until it's the turn of the account at `index`, runs `f` and passes the turn on.
+    /// The account leaves the rotation if `f` returns `None` or panics.
+    pub(crate) fn take<T, F: FnOnce() -> Option<T>>(&self, index: usize, f: F) -> Option<T> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if state.current.is_none() {
+                state.current = state.order.next();
+            }
+            match state.current {
+                Some(current) if current == index => break,
+                // The account has left the rotation already.
+                None => return None,
+                Some(_) => {
+                    state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+        drop(state);
+
+        let mut guard = TurnGuard { turns: self, index, leave: true };
+        let result = f();
+        guard.leave = result.is_none();
+        result
+    }
+
+    /// Removes the account at `index` from the rotation, e.g. once its work is done.
+    pub(crate) fn leave(&self, index: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.order.remove(index);
+        if state.current == Some(index) {
+            state.current = None;
+        }
+        drop(state);
+        self.changed.notify_all();
+    }
+}
+
+/// Passes the turn on when it's dropped, including unwinding.
+struct TurnGuard<'a> {
+    turns: &'a Turns,
+    index: usize,
+    leave: bool,
+}
+
+impl Drop for TurnGuard<'_> {
+    fn drop(&mut self) {
+        if self.leave {
+            self.turns.leave(self.index);
+            return;
+        }
+        let mut state = self.turns.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.current = None;
+        drop(state);
+        self.turns.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn distribute_by_weight() {
+        let picks: Vec<_> = WeightedRoundRobin::new(&[3, 1]).take(8).collect();
+        assert_eq!(picks, [0, 0, 1, 0, 0, 0, 1, 0]);
+
+        let mut counts = [0; 3];
+        for index in WeightedRoundRobin::new(&[5, 3, 0]).take(900) {
+            counts[index] += 1;
+        }
+        assert_eq!(counts, [500, 300, 100]);
+
+        let mut order = WeightedRoundRobin::new(&[2, 1]);
+        order.remove(0);
+        assert_eq!(order.by_ref().take(3).collect::<Vec<_>>(), [1, 1, 1]);
+        order.remove(1);
+        assert_eq!(order.next(), None);
+    }
+
+    #[test]
+    fn take_turns() {
+        let turns = Arc::new(Turns::new(&[2, 1]));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let threads: Vec<_> = [4, 2]
+            .iter()
+            .enumerate()
+            .map(|(index, &count)| {
+                let (turns, log) = (Arc::clone(&turns), Arc::clone(&log));
+                thread::spawn(move || {
+                    let mut items = 0..count;
+                    let mut next = || {
+                        let item = items.next()?;
+                        log.lock().unwrap().push((index, item));
+                        Some(item)
+                    };
+                    while turns.take(index, &mut next).is_some() {}
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*log.lock().unwrap(), [(0, 0), (1, 0), (0, 1), (0, 2), (1, 1), (0, 3)]);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Atom feeds of media, so posts can be followed using a feed reader.
+
+use crate::user::{Media, MediaType};
+use std::io::{self, Write};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use url::Url;
+
+/// Maximum number of characters of a caption used as the title of an entry.
+const TITLE_LENGTH: usize = 80;
+
+/// Metadata of an Atom feed whose entries are media items.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::LongLivedToken, feed::Feed, user::Profile};
+///
+/// # fn run(token: LongLivedToken) -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = Profile::new(token)?;
+/// let feed = Feed::for_user(profile.info()?.username());
+/// feed.write_atom(&profile.media_recent(20)?, std::io::stdout())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Feed {
+    pub title: String,
+    /// Permanent and unique identifier of the feed, usually a URL.
+    pub id: String,
+    /// Page the feed is about, e.g. the profile.
+    pub link: Option<Url>,
+    pub author: Option<String>,
+}
+
+impl Feed {
+    pub fn new<T: Into<String>, I: Into<String>>(title: T, id: I) -> Self {
+        Self { title: title.into(), id: id.into(), link: None, author: None }
+    }
+
+    /// Constructs a feed of the user's profile, which is used as its ID and link.
+    pub fn for_user(username: &str) -> Self {
+        let link = Url::parse("https://www.instagram.com/").and_then(|url| url.join(username));
+        Self {
+            title: format!("@{}", username),
+            id: link.as_ref().map_or_else(|_| username.to_string(), Url::to_string),
+            link: link.ok(),
+            author: Some(username.to_string()),
+        }
+    }
+
+    /// Writes an Atom document with `media` as entries in the given order. Titles of
+    /// entries are excerpts of captions, links point to permalinks and enclosures
+    /// to media files. The feed is updated when the newest item is published.
+    pub fn write_atom<W: Write>(&self, media: &[Media], mut writer: W) -> io::Result<()> {
+        let updated = media.iter().filter_map(Media::timestamp_utc).max().unwrap_or_else(Utc::now);
+        writeln!(writer, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+        writeln!(writer, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#)?;
+        writeln!(writer, "  <title>{}</title>", escape(&self.title))?;
+        writeln!(writer, "  <id>{}</id>", escape(&self.id))?;
+        writeln!(writer, "  <updated>{}</updated>", format_date(updated))?;
+        if let Some(link) = &self.link {
+            writeln!(writer, r#"  <link rel="alternate" href="{}"/>"#, escape(link.as_str()))?;
+        }
+        if let Some(author) = &self.author {
+            writeln!(writer, "  <author><name>{}</name></author>", escape(author))?;
+        }
+        for item in media {
+            write_entry(&mut writer, item, updated)?;
+        }
+        writeln!(writer, "</feed>")?;
+        writer.flush()
+    }
+
+    /// Works like [write_atom][Feed::write_atom], but returns the document as a string.
+    pub fn to_atom(&self, media: &[Media]) -> String {
+        let mut atom = Vec::new();
+        // Writing to a vector doesn't fail.
+        self.write_atom(media, &mut atom).unwrap();
+        String::from_utf8(atom).unwrap()
+    }
+}
+
+/// Writes an entry of `media`. Items without a publish date are considered
+/// updated along with the feed.
+fn write_entry<W: Write>(writer: &mut W, media: &Media, updated: DateTime<Utc>) -> io::Result<()> {
+    let id = media.permalink().map_or_else(
+        || format!("urn:instagram:media:{}", media.id()),
+        |permalink| permalink.to_string(),
+    );
+    writeln!(writer, "  <entry>")?;
+    writeln!(writer, "    <title>{}</title>", escape(&title(media)))?;
+    writeln!(writer, "    <id>{}</id>", escape(&id))?;
+    writeln!(
+        writer,
+        "    <updated>{}</updated>",
+        format_date(media.timestamp_utc().unwrap_or(updated))
+    )?;
+    if let Some(permalink) = media.permalink() {
+        writeln!(writer, r#"    <link rel="alternate" href="{}"/>"#, escape(permalink.as_str()))?;
+    }
+    // Albums have no file of their own.
+    if media.media_type() != MediaType::CarouselAlbum {
+        let url = escape(media.media_url().as_str());
+        writeln!(writer, r#"    <link rel="enclosure" href="{}"/>"#, url)?;
+    }
+    if let Some(caption) = media.caption() {
+        writeln!(writer, r#"    <content type="text">{}</content>"#, escape(caption))?;
+    }
+    writeln!(writer, "  </entry>")
+}
+
+/// Returns the first line of the caption shortened to [TITLE_LENGTH] characters,
+/// or the type and the ID of `media` if it has no caption.
+pub(crate) fn title(media: &Media) -> String {
+    let line = media
+        .caption()
+        .and_then(|caption| caption.lines().map(str::trim).find(|line| !line.is_empty()));
+    match line {
+        Some(line) if line.chars().count() > TITLE_LENGTH => {
+            let excerpt: String = line.chars().take(TITLE_LENGTH - 1).collect();
+            format!("{}…", excerpt.trim_end())
+        }
+        Some(line) => line.to_string(),
+        None => {
+            let media_type = match media.media_type() {
+                MediaType::Image => "Image",
+                MediaType::Video => "Video",
+                MediaType::CarouselAlbum => "Album",
+            };
+            format!("{} {}", media_type, media.id())
+        }
+    }
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escapes characters that have a special meaning in XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::tests::{captioned, media};
+
+    #[test]
+    fn atom() {
+        let feed = Feed::for_user("jane.doe");
+        assert_eq!(feed.id, "https://www.instagram.com/jane.doe");
+        let atom = feed.to_atom(&[captioned(1, "\nSea & <sun>\nDay two"), media(2)]);
+        assert!(atom.contains("<title>@jane.doe</title>"));
+        assert!(atom.contains("<updated>1970-01-01T00:00:00Z</updated>"));
+        assert!(atom.contains("<title>Sea &amp; &lt;sun&gt;</title>"));
+        assert!(atom.contains("<content type=\"text\">\nSea &amp; &lt;sun&gt;\nDay two</content>"));
+        assert!(atom.contains("<title>Image 2</title>"));
+        assert!(atom.contains("<id>urn:instagram:media:2</id>"));
+        assert!(atom.contains(r#"<link rel="enclosure" href="https://example.com/"/>"#));
+        assert!(atom.ends_with("</feed>\n"));
+    }
+
+    #[test]
+    fn long_title() {
+        let caption = "a".repeat(TITLE_LENGTH + 1);
+        let title = title(&captioned(1, &caption));
+        assert_eq!(title.chars().count(), TITLE_LENGTH);
+        assert!(title.ends_with("a…"));
+    }
+}
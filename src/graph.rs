@@ -0,0 +1,499 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Provides methods to retrieve information and media of business and creator accounts
+//! using the [Instagram Graph API](https://developers.facebook.com/docs/instagram-api/).
+
+mod comments;
+mod hashtag;
+mod insights;
+mod mentions;
+mod pages;
+mod token;
+
+pub use comments::Comment;
+pub use pages::{pages, Page};
+pub use token::FacebookToken;
+pub use insights::{
+    AccountMetric, Breakdown, BreakdownValue, DataPoint, InsightsQuery, Metric, Period, Timeframe,
+    TimeSeries,
+};
+
+use crate::{
+    auth::Token,
+    client::Client,
+    json,
+    user::{self, Media, MediaField, MediaFields, MediaSummary, RawPage, Timezone, UrlPolicy},
+};
+use std::{collections::HashMap, fmt};
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+/// Fields of the business account requested by [Account::info].
+const INFO_FIELDS: &str =
+    "id,username,name,biography,website,profile_picture_url,followers_count,follows_count,\
+     media_count";
+
+/// Instagram business or creator account that is connected to a Facebook Page.
+/// Requests are made using a Facebook User or Page access token
+/// with the `instagram_basic` and `pages_show_list` permissions.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::Token, client::Client, graph::Account};
+///
+/// # fn run<T: Token>(token: T) -> Result<(), Box<dyn std::error::Error>> {
+/// let account = Account::from_page(token, Client::new()?, 134895793791914)?;
+/// let info = account.info()?;
+/// println!("{} has {} followers", info.username(), info.followers_count());
+/// for media in account.media()? {
+///     println!("{}", media.media_url());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Account<T> {
+    token: T,
+    client: Client,
+    /// Instagram user ID of the account, it differs from the ID used by the Basic Display API.
+    id: u64,
+    url_policy: UrlPolicy,
+    timezone: Timezone,
+}
+
+/// Information about a business account.
+pub struct BusinessInfo {
+    id: u64,
+    username: String,
+    name: Option<String>,
+    biography: Option<String>,
+    website: Option<String>,
+    profile_picture_url: Option<Url>,
+    followers_count: u64,
+    follows_count: u64,
+    media_count: u64,
+}
+
+/// Describes why an account can't be resolved or a request can't be performed.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum GraphError {
+    /// No Instagram business account is connected to the Facebook Page. Contains the Page ID.
+    NoBusinessAccount(u64),
+    /// The comment doesn't exist or the account can't moderate it. Contains the comment ID.
+    CommentUnavailable(u64),
+    /// No Facebook Page of the user has a connected Instagram business account.
+    NoConnectedAccount,
+}
+
+/// Abstractions over JSON responses.
+mod response {
+    use serde::Deserialize;
+
+    /// Page of items, like comments.
+    #[derive(Deserialize)]
+    pub(super) struct Container<T> {
+        pub(super) data: Vec<T>,
+        pub(super) paging: Option<Paging>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Paging {
+        /// URL to the next page, it contains the access token.
+        pub(super) next: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Page {
+        pub(super) instagram_business_account: Option<Node>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Node {
+        pub(super) id: String,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct BusinessInfo {
+        pub(super) id: String,
+        pub(super) username: String,
+        pub(super) name: Option<String>,
+        pub(super) biography: Option<String>,
+        pub(super) website: Option<String>,
+        pub(super) profile_picture_url: Option<String>,
+        #[serde(default)]
+        pub(super) followers_count: u64,
+        #[serde(default)]
+        pub(super) follows_count: u64,
+        #[serde(default)]
+        pub(super) media_count: u64,
+    }
+}
+
+impl<T: Token> Account<T> {
+    /// Constructs an account with the Instagram user ID `id`.
+    ///
+    /// Returns an error if a [Client] can't be initialized.
+    pub fn new(token: T, id: u64) -> crate::Result<Self> {
+        Ok(Self::with_client(token, Client::new()?, id))
+    }
+
+    /// Works like [new][Account::new], but performs requests using the provided `client`.
+    pub fn with_client(token: T, client: Client, id: u64) -> Self {
+        Self { token, client, id, url_policy: UrlPolicy::Lenient, timezone: Timezone::Original }
+    }
+
+    /// Resolves the Instagram account connected to the Facebook Page with `page_id`.
+    /// Fails with [GraphError::NoBusinessAccount] if there is no such account.
+    pub fn from_page(token: T, client: Client, page_id: u64) -> crate::Result<Self> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}", crate::FACEBOOK_BASE_URL, crate::API_VERSION, page_id).as_str(),
+            [("fields", "instagram_business_account"), ("access_token", token.get())],
+        )?;
+        let mut body = client.get(url)?.text()?.into_bytes();
+        let page: response::Page = json::from_body(&mut body)?;
+        match page.instagram_business_account {
+            Some(account) => Ok(Self::with_client(token, client, account.id.parse()?)),
+            None => Err(Box::new(GraphError::NoBusinessAccount(page_id))),
+        }
+    }
+
+    /// Sets a policy that applies to URLs of gathered media items.
+    /// [Lenient][UrlPolicy::Lenient] is used by default.
+    pub fn set_url_policy(&mut self, policy: UrlPolicy) {
+        self.url_policy = policy;
+    }
+
+    /// Sets an offset that publish dates of gathered media items are converted to.
+    /// [Original][Timezone::Original] is used by default.
+    pub fn set_timezone(&mut self, timezone: Timezone) {
+        self.timezone = timezone;
+    }
+
+    /// Returns the Instagram user ID of the account.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Retrieves the profile of the account, including the number of followers.
+    pub fn info(&self) -> crate::Result<BusinessInfo> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}", crate::FACEBOOK_BASE_URL, crate::API_VERSION, self.id).as_str(),
+            [("fields", INFO_FIELDS)],
+        )?;
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        BusinessInfo::from(json::from_body(&mut body)?)
+    }
+
+    /// Gathers all media items of the account, newest first.
+    /// Items are represented in the same way as ones of the Basic Display API.
+    pub fn media(&self) -> crate::Result<Vec<Media>> {
+        self.media_summary()?.into_result()
+    }
+
+    /// Works like [media][Account::media], but items that can't be parsed are skipped
+    /// and reported by [MediaSummary::errors] instead of failing the whole gathering.
+    pub fn media_summary(&self) -> crate::Result<MediaSummary> {
+        self.collect_media(self.media_url()?)
+    }
+
+    /// Returns an iterator that fetches media items of the account page by page, newest first.
+    /// If a page can't be retrieved, the error is yielded and the iteration stops.
+    pub fn media_iter(&self) -> impl Iterator<Item = crate::Result<Media>> + '_ {
+        let mut next = Some(self.media_url());
+        let pages = std::iter::from_fn(move || {
+            let page = next.take()?.and_then(|url| {
+                let body = self.client.get(self.with_token(url))?.text()?;
+                let mut page = RawPage::parse(body)?;
+                next = page.next.take().map(Ok);
+                page.into_media(self.url_policy, self.timezone)
+            });
+            Some(page)
+        });
+        pages.flat_map(|page| page.unwrap_or_else(|e| vec![Err(e)]))
+    }
+
+    /// Retrieves a single media item of the account by its ID.
+    pub fn media_by_id(&self, id: u64) -> crate::Result<Media> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}", crate::FACEBOOK_BASE_URL, crate::API_VERSION, id).as_str(),
+            [("fields", media_fields().to_param())],
+        )?;
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        user::media_from_value(json::from_body(&mut body)?, self.url_policy, self.timezone)
+    }
+
+    /// Retrieves media items page by page starting from `url`, which has no access token.
+    fn collect_media(&self, url: Url) -> crate::Result<MediaSummary> {
+        let mut url = Some(url);
+        let pages = std::iter::from_fn(|| {
+            let page = self.client.get(self.with_token(url.take()?)).and_then(|response| {
+                let mut page = RawPage::parse(response.text()?)?;
+                url = page.next.take();
+                Ok(page)
+            });
+            Some(page)
+        });
+        user::parse_pages(pages, self.url_policy, self.timezone)
+    }
+
+    /// Retrieves lifetime values of `metrics` of the media item with `media_id`.
+    /// Metrics that the API doesn't report for the item are absent from the result.
+    pub fn media_insights(
+        &self,
+        media_id: u64,
+        metrics: &[Metric],
+    ) -> crate::Result<HashMap<Metric, u64>> {
+        if metrics.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let metrics: Vec<_> = metrics.iter().map(|metric| metric.as_str()).collect();
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}/insights", crate::FACEBOOK_BASE_URL, crate::API_VERSION, media_id)
+                .as_str(),
+            [("metric", metrics.join(","))],
+        )?;
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        insights::parse_media(&mut body)
+    }
+
+    /// Retrieves values of account `metrics` aggregated over `period`, e.g. the number
+    /// of profile views per day. The API limits the time range, by default values
+    /// of the last two periods are returned.
+    pub fn account_insights(
+        &self,
+        period: Period,
+        metrics: &[AccountMetric],
+    ) -> crate::Result<Vec<TimeSeries>> {
+        self.account_insights_with(period, metrics, &InsightsQuery::default())
+    }
+
+    /// Works like [account_insights][Account::account_insights], but narrows down
+    /// the request or breaks the values down using `query`.
+    pub fn account_insights_with(
+        &self,
+        period: Period,
+        metrics: &[AccountMetric],
+        query: &InsightsQuery,
+    ) -> crate::Result<Vec<TimeSeries>> {
+        if metrics.is_empty() {
+            return Ok(Vec::new());
+        }
+        let metrics: Vec<_> = metrics.iter().map(|metric| metric.as_str()).collect();
+        let mut url = Url::parse_with_params(
+            format!("{}/{}/{}/insights", crate::FACEBOOK_BASE_URL, crate::API_VERSION, self.id)
+                .as_str(),
+            [("metric", metrics.join(",").as_str()), ("period", period.as_str())],
+        )?;
+        query.apply(&mut url);
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        insights::parse_account(&mut body)
+    }
+
+    /// Retrieves all comments of the media item with `media_id` in the order returned
+    /// by the API, along with all their replies.
+    pub fn comments(&self, media_id: u64) -> crate::Result<Vec<Comment>> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}/comments", crate::FACEBOOK_BASE_URL, crate::API_VERSION, media_id)
+                .as_str(),
+            [("fields", comments::FIELDS)],
+        )?;
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        let container = json::from_body(&mut body)?;
+        let comments = collect_rest(&self.client, container)?;
+        comments.into_iter().map(|response| self.comment(response)).collect()
+    }
+
+    /// Converts `response` to a comment, retrieving replies that aren't embedded.
+    fn comment(&self, mut response: comments::response::Comment) -> crate::Result<Comment> {
+        let replies = match response.replies.take() {
+            Some(replies) => collect_rest(&self.client, replies)?
+                .into_iter()
+                .map(|reply| Comment::from(reply, Vec::new()))
+                .collect::<crate::Result<_>>()?,
+            None => Vec::new(),
+        };
+        Comment::from(response, replies)
+    }
+
+    /// Returns URL to the first page of media items.
+    fn media_url(&self) -> crate::Result<Url> {
+        Ok(Url::parse_with_params(
+            format!("{}/{}/{}/media", crate::FACEBOOK_BASE_URL, crate::API_VERSION, self.id)
+                .as_str(),
+            [("fields", media_fields().to_param())],
+        )?)
+    }
+
+    /// Returns the client that performs requests of the account.
+    pub(crate) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub(crate) fn with_token(&self, mut url: Url) -> Url {
+        url.query_pairs_mut().append_pair("access_token", self.token.get());
+        url
+    }
+}
+
+impl BusinessInfo {
+    /// Returns the Instagram user ID of the account.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+    /// Returns the name displayed in the profile.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn biography(&self) -> Option<&str> {
+        self.biography.as_deref()
+    }
+    /// Returns the website link as entered by the user, so it may be not a valid URL.
+    pub fn website(&self) -> Option<&str> {
+        self.website.as_deref()
+    }
+    pub fn profile_picture_url(&self) -> Option<&Url> {
+        self.profile_picture_url.as_ref()
+    }
+    pub fn followers_count(&self) -> u64 {
+        self.followers_count
+    }
+    /// Returns the number of accounts followed by the account.
+    pub fn follows_count(&self) -> u64 {
+        self.follows_count
+    }
+    pub fn media_count(&self) -> u64 {
+        self.media_count
+    }
+
+    fn from(response: response::BusinessInfo) -> crate::Result<Self> {
+        Ok(Self {
+            id: response.id.parse()?,
+            username: response.username,
+            name: response.name,
+            biography: response.biography,
+            website: response.website,
+            profile_picture_url: crate::parse_opt(response.profile_picture_url)?,
+            followers_count: response.followers_count,
+            follows_count: response.follows_count,
+            media_count: response.media_count,
+        })
+    }
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBusinessAccount(page_id) => {
+                write!(f, "no Instagram business account is connected to page {}", page_id)
+            }
+            Self::CommentUnavailable(id) => {
+                write!(f, "comment {} doesn't exist or can't be moderated", id)
+            }
+            Self::NoConnectedAccount => {
+                f.write_str("no Instagram business account is connected to pages of the user")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Returns items of `container` followed by items of the next pages retrieved using `client`.
+fn collect_rest<R: DeserializeOwned>(
+    client: &Client,
+    container: response::Container<R>,
+) -> crate::Result<Vec<R>> {
+    let mut items = container.data;
+    let mut next = container.paging.and_then(|paging| paging.next);
+    while let Some(url) = crate::parse_opt::<Url, _>(next)? {
+        let mut body = client.get(url)?.text()?.into_bytes();
+        let container: response::Container<R> = json::from_body(&mut body)?;
+        items.extend(container.data);
+        next = container.paging.and_then(|paging| paging.next);
+    }
+    Ok(items)
+}
+
+/// Returns fields requested for media items of the account.
+fn media_fields() -> MediaFields {
+    MediaFields::default().with(MediaField::MediaProductType)
+}
+
+/// Parses a date in the format used by the API, like `2022-05-01T07:00:00+0000`.
+fn parse_date(date: &str) -> crate::Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%z")?.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::auth::{AppToken, Secrets};
+
+    #[test]
+    fn media_url() {
+        let account = Account::with_client(app_token(), Client::new().unwrap(), 17841405822304914);
+        let url = account.media_url().unwrap();
+        assert_eq!(url.path(), "/v13.0/17841405822304914/media");
+        assert!(url.query().unwrap().contains("media_product_type"));
+    }
+
+    #[test]
+    fn comment_thread() {
+        let mut body = br#"{"data": [{
+            "id": "17870913679156914",
+            "text": "Nice view!",
+            "username": "john",
+            "timestamp": "2022-05-01T12:00:00+0000",
+            "like_count": 2,
+            "replies": {"data": [{"id": "17873440459141021", "text": "Thanks"}]}
+        }]}"#
+        .to_vec();
+        let container = json::from_body(&mut body).unwrap();
+        let account = Account::with_client(app_token(), Client::new().unwrap(), 1);
+        let comments: Vec<_> = collect_rest(&account.client, container)
+            .unwrap()
+            .into_iter()
+            .map(|response| account.comment(response).unwrap())
+            .collect();
+        assert_eq!(comments[0].username.as_deref(), Some("john"));
+        assert_eq!(comments[0].timestamp.unwrap().timestamp(), 1651406400);
+        assert_eq!(comments[0].like_count, 2);
+        assert_eq!(comments[0].replies[0].text, "Thanks");
+        assert_eq!(comments[0].replies[0].username, None);
+    }
+
+    #[test]
+    fn into_info() {
+        let mut body = br#"{
+            "id": "17841405822304914",
+            "username": "jane.doe",
+            "biography": "Travel photos",
+            "profile_picture_url": "https://scontent.xx.fbcdn.net/v/t51.jpg",
+            "followers_count": 267788,
+            "follows_count": 1249
+        }"#
+        .to_vec();
+        let info = BusinessInfo::from(json::from_body(&mut body).unwrap()).unwrap();
+        assert_eq!(info.id(), 17841405822304914);
+        assert_eq!(info.name(), None);
+        assert_eq!(info.biography(), Some("Travel photos"));
+        assert!(info.profile_picture_url().is_some());
+        assert_eq!(info.followers_count(), 267788);
+        assert_eq!(info.media_count(), 0);
+    }
+
+    pub(crate) fn app_token() -> AppToken {
+        AppToken::from_secrets(&Secrets {
+            app_id: 1,
+            app_secret: "secret",
+            oauth_uri: Url::parse("https://example.com/").unwrap(),
+        })
+    }
+}
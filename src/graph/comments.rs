@@ -0,0 +1,146 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Comments of media items and their moderation.
+
+use super::{Account, GraphError};
+use crate::{auth::Token, json};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+/// Fields requested for comments. Replies are expanded, so a thread is retrieved at once.
+pub(super) const FIELDS: &str =
+    "id,text,username,timestamp,like_count,replies{id,text,username,timestamp,like_count}";
+
+/// Comment of a media item returned by [Account::comments][super::Account::comments].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Comment {
+    pub id: u64,
+    pub text: String,
+    /// Username of the author. Not reported for comments of deleted accounts.
+    pub username: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub like_count: u64,
+    /// Replies to the comment, they never have replies of their own.
+    pub replies: Vec<Comment>,
+}
+
+pub(super) mod response {
+    use super::super::response::Container;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(in super::super) struct Comment {
+        pub(in super::super) id: String,
+        #[serde(default)]
+        pub(in super::super) text: String,
+        pub(in super::super) username: Option<String>,
+        pub(in super::super) timestamp: Option<String>,
+        #[serde(default)]
+        pub(in super::super) like_count: u64,
+        pub(in super::super) replies: Option<Container<Comment>>,
+    }
+}
+
+/// Moderation of comments on media of the account. Requires the
+/// `instagram_manage_comments` permission, its absence is reported as
+/// [Error::InsufficientPermission][crate::Error::InsufficientPermission].
+/// Comments that don't exist or belong to media of other accounts
+/// are reported as [GraphError::CommentUnavailable].
+impl<T: Token> Account<T> {
+    /// Publishes a reply with `text` to the comment with `comment_id`.
+    /// Returns the ID of the reply.
+    pub fn reply(&self, comment_id: u64, text: &str) -> crate::Result<u64> {
+        let url = self.with_token(comment_url(comment_id, "/replies")?);
+        let response = self.client.post(url, &[("message", text)]);
+        let mut body = check(response, comment_id)?.text()?.into_bytes();
+        let reply: super::response::Node = json::from_body(&mut body)?;
+        Ok(reply.id.parse()?)
+    }
+
+    /// Hides or unhides the comment with `comment_id`. Hidden comments are visible
+    /// to their authors only.
+    pub fn set_hidden(&self, comment_id: u64, hidden: bool) -> crate::Result<()> {
+        let url = self.with_token(comment_url(comment_id, "")?);
+        let hidden = if hidden { "true" } else { "false" };
+        check(self.client.post(url, &[("hide", hidden)]), comment_id).map(drop)
+    }
+
+    /// Deletes the comment with `comment_id` along with its replies.
+    pub fn delete_comment(&self, comment_id: u64) -> crate::Result<()> {
+        let url = self.with_token(comment_url(comment_id, "")?);
+        check(self.client.delete(url), comment_id).map(drop)
+    }
+}
+
+impl Comment {
+    /// Converts `response` to a comment with `replies`, which are retrieved separately
+    /// as there may be more of them than embedded in the response.
+    pub(super) fn from(response: response::Comment, replies: Vec<Comment>) -> crate::Result<Self> {
+        Ok(Self {
+            id: response.id.parse()?,
+            text: response.text,
+            username: response.username,
+            timestamp: response.timestamp.as_deref().map(super::parse_date).transpose()?,
+            like_count: response.like_count,
+            replies,
+        })
+    }
+}
+
+/// Returns URL to the node of the comment with `comment_id` followed by `edge`.
+fn comment_url(comment_id: u64, edge: &str) -> crate::Result<Url> {
+    Ok(Url::parse(&format!(
+        "{}/{}/{}{}",
+        crate::FACEBOOK_BASE_URL,
+        crate::API_VERSION,
+        comment_id,
+        edge
+    ))?)
+}
+
+/// Converts an error reporting that the comment with `comment_id` doesn't exist
+/// or can't be moderated to [GraphError::CommentUnavailable].
+fn check<R>(result: crate::Result<R>, comment_id: u64) -> crate::Result<R> {
+    result.map_err(|e| {
+        // See https://developers.facebook.com/docs/graph-api/guides/error-handling.
+        let unavailable = e
+            .downcast_ref::<crate::Error>()
+            .and_then(crate::Error::api_error)
+            .is_some_and(|error| error.code == Some(100) && error.subcode == Some(33));
+        if unavailable {
+            Box::new(GraphError::CommentUnavailable(comment_id))
+        } else {
+            e
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ApiError, Error};
+
+    #[test]
+    fn unavailable_comment() {
+        let api_error = |code, subcode| ApiError {
+            status: 400,
+            error_type: Some("GraphMethodException".to_string()),
+            code: Some(code),
+            subcode,
+            message: "Unsupported post request".to_string(),
+        };
+        let error = check::<()>(Err(Box::new(Error::Api(api_error(100, Some(33))))), 1);
+        assert_eq!(
+            error.unwrap_err().downcast_ref::<GraphError>(),
+            Some(&GraphError::CommentUnavailable(1))
+        );
+
+        let permission = Error::InsufficientPermission(api_error(10, None));
+        let error = check::<()>(Err(Box::new(permission)), 1).unwrap_err();
+        assert!(matches!(error.downcast_ref::<Error>(), Some(Error::InsufficientPermission(_))));
+    }
+}
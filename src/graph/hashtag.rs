@@ -0,0 +1,77 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Search of hashtags and their media.
+
+use super::{response, Account};
+use crate::{
+    auth::Token,
+    json,
+    user::{MediaField, MediaFields, MediaSummary},
+};
+
+use url::Url;
+
+/// Hashtag search and media of hashtags. Requires the `instagram_basic` permission
+/// and the Instagram Public Content Access feature. The API allows to search
+/// up to 30 unique hashtags per account within 7 days.
+impl<T: Token> Account<T> {
+    /// Returns the ID of the hashtag with `name`, which may start with `#`.
+    /// Returns `None` if there is no such hashtag.
+    pub fn hashtag_search(&self, name: &str) -> crate::Result<Option<u64>> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/ig_hashtag_search", crate::FACEBOOK_BASE_URL, crate::API_VERSION)
+                .as_str(),
+            [("user_id", self.id.to_string().as_str()), ("q", name.trim_start_matches('#'))],
+        )?;
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        let container: response::Container<response::Node> = json::from_body(&mut body)?;
+        match container.data.into_iter().next() {
+            Some(hashtag) => Ok(Some(hashtag.id.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Gathers the most popular media items with the hashtag with `hashtag_id`.
+    /// Media of hashtags has neither usernames nor thumbnails. Albums and videos
+    /// with copyrighted content have no media URL, so they're reported
+    /// by [MediaSummary::errors].
+    pub fn top_media(&self, hashtag_id: u64) -> crate::Result<MediaSummary> {
+        self.collect_media(self.hashtag_url(hashtag_id, "top_media")?)
+    }
+
+    /// Works like [top_media][Account::top_media], but gathers media items
+    /// published within the last 24 hours, newest first.
+    pub fn recent_media(&self, hashtag_id: u64) -> crate::Result<MediaSummary> {
+        self.collect_media(self.hashtag_url(hashtag_id, "recent_media")?)
+    }
+
+    /// Returns URL to the first page of the hashtag media `edge`.
+    fn hashtag_url(&self, hashtag_id: u64, edge: &str) -> crate::Result<Url> {
+        let fields =
+            MediaFields::required().with(MediaField::Caption).with(MediaField::Permalink);
+        Ok(Url::parse_with_params(
+            format!("{}/{}/{}/{}", crate::FACEBOOK_BASE_URL, crate::API_VERSION, hashtag_id, edge)
+                .as_str(),
+            [("user_id", self.id.to_string()), ("fields", fields.to_param())],
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::Client, graph::tests::app_token};
+
+    #[test]
+    fn hashtag_url() {
+        let account = Account::with_client(app_token(), Client::new().unwrap(), 1);
+        let url = account.hashtag_url(17843857450040591, "recent_media").unwrap();
+        assert_eq!(url.path(), "/v13.0/17843857450040591/recent_media");
+        assert_eq!(
+            url.query(),
+            Some("user_id=1&fields=caption%2Cid%2Cmedia_type%2Cmedia_url%2Cpermalink%2Ctimestamp")
+        );
+    }
+}
@@ -0,0 +1,464 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Metrics of media items and business accounts.
+
+use crate::{json, user::ParseTypeError};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+/// Metric of a media item returned by [Account::media_insights][super::Account::media_insights].
+/// Not every metric is available for every type of media, e.g. plays are counted for reels only.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
+pub enum Metric {
+    /// Number of times the item has been seen.
+    Impressions,
+    /// Number of unique accounts that have seen the item.
+    Reach,
+    /// Number of unique accounts that have saved the item.
+    Saved,
+    /// Number of times a reel or a video has started to play.
+    Plays,
+    /// Total number of likes and comments.
+    Engagement,
+    Likes,
+    Comments,
+    Shares,
+    /// Total number of likes, saves, comments and shares.
+    TotalInteractions,
+}
+
+/// Metric of a business account returned by
+/// [Account::account_insights][super::Account::account_insights].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
+pub enum AccountMetric {
+    /// Number of new followers, reported for the [day][Period::Day] period only.
+    FollowerCount,
+    ProfileViews,
+    /// Number of unique accounts that have seen any media of the account.
+    Reach,
+    Impressions,
+    WebsiteClicks,
+    EmailContacts,
+    /// Demographics of followers, requires a [breakdown][InsightsQuery::breakdown].
+    FollowerDemographics,
+    /// Demographics of engaged accounts, requires a [breakdown][InsightsQuery::breakdown].
+    EngagedAudienceDemographics,
+}
+
+/// Period that values of account metrics are aggregated over.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum Period {
+    #[default]
+    Day,
+    Week,
+    Days28,
+    /// Used for metrics that are broken down, like demographics.
+    Lifetime,
+}
+
+/// Dimension that values of demographic metrics are broken down by.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Breakdown {
+    Age,
+    City,
+    Country,
+    Gender,
+}
+
+/// Time range of demographic metrics relative to the current date.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Timeframe {
+    ThisWeek,
+    ThisMonth,
+    PrevMonth,
+    Last14Days,
+    Last30Days,
+    Last90Days,
+}
+
+/// Parameters of [Account::account_insights_with][super::Account::account_insights_with].
+/// A missing parameter isn't sent.
+///
+/// # Examples
+/// Countries of followers during the last 30 days:
+/// ```
+/// use instapi::graph::{Breakdown, InsightsQuery, Timeframe};
+///
+/// let query = InsightsQuery {
+///     breakdown: Some(Breakdown::Country),
+///     timeframe: Some(Timeframe::Last30Days),
+///     ..InsightsQuery::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Default, Debug)]
+pub struct InsightsQuery {
+    /// Only values at or after this date are returned.
+    pub since: Option<DateTime<Utc>>,
+    /// Only values before this date are returned.
+    pub until: Option<DateTime<Utc>>,
+    /// Breaks the values down by a dimension, so total values are returned
+    /// instead of time series.
+    pub breakdown: Option<Breakdown>,
+    pub timeframe: Option<Timeframe>,
+}
+
+/// Values of an account metric.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TimeSeries {
+    pub metric: AccountMetric,
+    pub period: Period,
+    /// Values in chronological order. A total value has no end time.
+    pub values: Vec<DataPoint>,
+    /// Values by dimensions if a [breakdown][InsightsQuery::breakdown] is requested.
+    pub breakdowns: Vec<BreakdownValue>,
+}
+
+/// Value of a metric aggregated over a period that ends at `end_time`.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct DataPoint {
+    pub end_time: Option<DateTime<Utc>>,
+    pub value: u64,
+}
+
+/// Value of a metric for a combination of dimensions, e.g. `["Poland"]` for countries.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct BreakdownValue {
+    pub dimensions: Vec<String>,
+    pub value: u64,
+}
+
+/// Abstractions over JSON responses.
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Insights {
+        pub(super) data: Vec<Insight>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Insight {
+        pub(super) name: String,
+        #[serde(default)]
+        pub(super) period: String,
+        #[serde(default)]
+        pub(super) values: Vec<Value>,
+        pub(super) total_value: Option<TotalValue>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Value {
+        pub(super) value: u64,
+        pub(super) end_time: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct TotalValue {
+        pub(super) value: Option<u64>,
+        #[serde(default)]
+        pub(super) breakdowns: Vec<Breakdown>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Breakdown {
+        #[serde(default)]
+        pub(super) results: Vec<BreakdownResult>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct BreakdownResult {
+        pub(super) dimension_values: Vec<String>,
+        pub(super) value: u64,
+    }
+}
+
+impl InsightsQuery {
+    /// Appends the parameters to `url`. Broken down metrics are requested as total values.
+    pub(super) fn apply(&self, url: &mut Url) {
+        let mut pairs = url.query_pairs_mut();
+        if let Some(since) = self.since {
+            pairs.append_pair("since", &since.timestamp().to_string());
+        }
+        if let Some(until) = self.until {
+            pairs.append_pair("until", &until.timestamp().to_string());
+        }
+        if let Some(breakdown) = self.breakdown {
+            pairs.append_pair("metric_type", "total_value");
+            pairs.append_pair("breakdown", breakdown.as_str());
+        }
+        if let Some(timeframe) = self.timeframe {
+            pairs.append_pair("timeframe", timeframe.as_str());
+        }
+    }
+}
+
+impl Metric {
+    /// Returns the value used by the API, like `total_interactions`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Impressions => "impressions",
+            Self::Reach => "reach",
+            Self::Saved => "saved",
+            Self::Plays => "plays",
+            Self::Engagement => "engagement",
+            Self::Likes => "likes",
+            Self::Comments => "comments",
+            Self::Shares => "shares",
+            Self::TotalInteractions => "total_interactions",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "impressions" => Ok(Self::Impressions),
+            "reach" => Ok(Self::Reach),
+            "saved" => Ok(Self::Saved),
+            "plays" => Ok(Self::Plays),
+            "engagement" => Ok(Self::Engagement),
+            "likes" => Ok(Self::Likes),
+            "comments" => Ok(Self::Comments),
+            "shares" => Ok(Self::Shares),
+            "total_interactions" => Ok(Self::TotalInteractions),
+            _ => Err(ParseTypeError { type_name: "metric", value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl AccountMetric {
+    /// Returns the value used by the API, like `profile_views`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FollowerCount => "follower_count",
+            Self::ProfileViews => "profile_views",
+            Self::Reach => "reach",
+            Self::Impressions => "impressions",
+            Self::WebsiteClicks => "website_clicks",
+            Self::EmailContacts => "email_contacts",
+            Self::FollowerDemographics => "follower_demographics",
+            Self::EngagedAudienceDemographics => "engaged_audience_demographics",
+        }
+    }
+}
+
+impl FromStr for AccountMetric {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "follower_count" => Ok(Self::FollowerCount),
+            "profile_views" => Ok(Self::ProfileViews),
+            "reach" => Ok(Self::Reach),
+            "impressions" => Ok(Self::Impressions),
+            "website_clicks" => Ok(Self::WebsiteClicks),
+            "email_contacts" => Ok(Self::EmailContacts),
+            "follower_demographics" => Ok(Self::FollowerDemographics),
+            "engaged_audience_demographics" => Ok(Self::EngagedAudienceDemographics),
+            _ => Err(ParseTypeError { type_name: "account metric", value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for AccountMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Period {
+    /// Returns the value used by the API, like `days_28`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Days28 => "days_28",
+            Self::Lifetime => "lifetime",
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "days_28" => Ok(Self::Days28),
+            "lifetime" => Ok(Self::Lifetime),
+            _ => Err(ParseTypeError { type_name: "period", value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Breakdown {
+    /// Returns the value used by the API, like `country`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Age => "age",
+            Self::City => "city",
+            Self::Country => "country",
+            Self::Gender => "gender",
+        }
+    }
+}
+
+impl Timeframe {
+    /// Returns the value used by the API, like `last_30_days`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ThisWeek => "this_week",
+            Self::ThisMonth => "this_month",
+            Self::PrevMonth => "prev_month",
+            Self::Last14Days => "last_14_days",
+            Self::Last30Days => "last_30_days",
+            Self::Last90Days => "last_90_days",
+        }
+    }
+}
+
+/// Parses a response body of the media insights endpoint. Lifetime metrics have a single value.
+pub(super) fn parse_media(body: &mut [u8]) -> crate::Result<HashMap<Metric, u64>> {
+    let insights: response::Insights = json::from_body(body)?;
+    let mut values = HashMap::new();
+    for insight in insights.data {
+        if let Some(value) = insight.values.first() {
+            values.insert(insight.name.parse()?, value.value);
+        }
+    }
+    Ok(values)
+}
+
+/// Parses a response body of the account insights endpoint.
+pub(super) fn parse_account(body: &mut [u8]) -> crate::Result<Vec<TimeSeries>> {
+    let insights: response::Insights = json::from_body(body)?;
+    insights
+        .data
+        .into_iter()
+        .map(|insight| {
+            let mut values = insight
+                .values
+                .into_iter()
+                .map(|value| {
+                    Ok(DataPoint {
+                        end_time: value.end_time.as_deref().map(super::parse_date).transpose()?,
+                        value: value.value,
+                    })
+                })
+                .collect::<crate::Result<Vec<_>>>()?;
+            let mut breakdowns = Vec::new();
+            if let Some(total) = insight.total_value {
+                if let Some(value) = total.value {
+                    values.push(DataPoint { end_time: None, value });
+                }
+                breakdowns = total
+                    .breakdowns
+                    .into_iter()
+                    .flat_map(|breakdown| breakdown.results)
+                    .map(|result| BreakdownValue {
+                        dimensions: result.dimension_values,
+                        value: result.value,
+                    })
+                    .collect();
+            }
+            Ok(TimeSeries {
+                metric: insight.name.parse()?,
+                period: insight.period.parse()?,
+                values,
+                breakdowns,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn media_insights() {
+        let mut body = br#"{"data": [
+            {"name": "reach", "period": "lifetime", "values": [{"value": 1120}]},
+            {"name": "saved", "period": "lifetime", "values": [{"value": 0}]},
+            {"name": "plays", "period": "lifetime", "values": []}
+        ]}"#
+        .to_vec();
+        assert_eq!(
+            parse_media(&mut body).unwrap(),
+            HashMap::from([(Metric::Reach, 1120), (Metric::Saved, 0)])
+        );
+        assert_eq!(Metric::TotalInteractions.to_string().parse(), Ok(Metric::TotalInteractions));
+        assert!("views".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn account_insights() {
+        let mut body = br#"{"data": [
+            {"name": "profile_views", "period": "day", "values": [
+                {"value": 12, "end_time": "2022-05-01T07:00:00+0000"},
+                {"value": 15, "end_time": "2022-05-02T07:00:00+0000"}
+            ]},
+            {"name": "follower_demographics", "period": "lifetime", "total_value": {
+                "breakdowns": [{"dimension_keys": ["country"], "results": [
+                    {"dimension_values": ["PL"], "value": 40},
+                    {"dimension_values": ["DE"], "value": 8}
+                ]}]
+            }}
+        ]}"#
+        .to_vec();
+        let series = parse_account(&mut body).unwrap();
+        assert_eq!(series[0].metric, AccountMetric::ProfileViews);
+        assert_eq!(
+            series[0].values[1],
+            DataPoint { end_time: Utc.timestamp_opt(1651474800, 0).single(), value: 15 }
+        );
+        assert!(series[0].breakdowns.is_empty());
+        assert_eq!(series[1].period, Period::Lifetime);
+        assert!(series[1].values.is_empty());
+        assert_eq!(
+            series[1].breakdowns[0],
+            BreakdownValue { dimensions: vec!["PL".to_string()], value: 40 }
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let mut url = Url::parse("https://example.com/").unwrap();
+        let query = InsightsQuery {
+            since: Utc.timestamp_opt(1651363200, 0).single(),
+            breakdown: Some(Breakdown::Age),
+            timeframe: Some(Timeframe::ThisMonth),
+            ..InsightsQuery::default()
+        };
+        query.apply(&mut url);
+        assert_eq!(
+            url.query(),
+            Some("since=1651363200&metric_type=total_value&breakdown=age&timeframe=this_month")
+        );
+    }
+}
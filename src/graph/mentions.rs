@@ -0,0 +1,108 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Media that the account is tagged in and mentions of the account.
+
+use super::{comments, Account, Comment};
+use crate::{
+    auth::Token,
+    json,
+    user::{self, Media, MediaField, MediaFields, MediaSummary},
+};
+
+use url::Url;
+
+/// Fields requested for a comment that mentions the account.
+const COMMENT_FIELDS: &str = "id,text,username,timestamp,like_count";
+
+mod response {
+    use super::comments;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Mention {
+        pub(super) mentioned_comment: Option<comments::response::Comment>,
+        pub(super) mentioned_media: Option<serde_json::Value>,
+    }
+}
+
+/// Tags and mentions of the account by other users. Mentions are looked up by IDs
+/// that are delivered by the `mentions` webhook.
+impl<T: Token> Account<T> {
+    /// Gathers media items that the account is tagged in, newest first.
+    pub fn tagged_media(&self) -> crate::Result<MediaSummary> {
+        let fields = MediaFields::required()
+            .with(MediaField::Caption)
+            .with(MediaField::Permalink)
+            .with(MediaField::Username);
+        self.collect_media(Url::parse_with_params(
+            format!("{}/{}/{}/tags", crate::FACEBOOK_BASE_URL, crate::API_VERSION, self.id)
+                .as_str(),
+            [("fields", fields.to_param())],
+        )?)
+    }
+
+    /// Retrieves the comment with `comment_id` that mentions the account.
+    /// Replies to the comment aren't retrieved.
+    pub fn mentioned_comment(&self, comment_id: u64) -> crate::Result<Comment> {
+        let field = format!("mentioned_comment.comment_id({}){{{}}}", comment_id, COMMENT_FIELDS);
+        let comment = self.mention(&field)?.mentioned_comment;
+        Comment::from(comment.ok_or("mentioned comment isn't reported")?, Vec::new())
+    }
+
+    /// Retrieves the media item with `media_id` whose caption mentions the account.
+    /// Such items have no permalinks.
+    pub fn mentioned_media(&self, media_id: u64) -> crate::Result<Media> {
+        let fields = MediaFields::required().with(MediaField::Caption).with(MediaField::Username);
+        let field = format!("mentioned_media.media_id({}){{{}}}", media_id, fields.to_param());
+        let media = self.mention(&field)?.mentioned_media;
+        user::media_from_value(
+            media.ok_or("mentioned media isn't reported")?,
+            self.url_policy,
+            self.timezone,
+        )
+    }
+
+    /// Requests the `field` of the account node that describes a mention.
+    fn mention(&self, field: &str) -> crate::Result<response::Mention> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}", crate::FACEBOOK_BASE_URL, crate::API_VERSION, self.id).as_str(),
+            [("fields", field)],
+        )?;
+        let mut body = self.client.get(self.with_token(url))?.text()?.into_bytes();
+        json::from_body(&mut body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::{Timezone, UrlPolicy};
+
+    #[test]
+    fn mentions() {
+        let mut body = br#"{
+            "mentioned_media": {
+                "id": "17873440459141021",
+                "media_type": "IMAGE",
+                "media_url": "https://scontent.cdninstagram.com/v/t51.jpg",
+                "timestamp": "2022-05-01T12:00:00+0000",
+                "caption": "With @jane.doe"
+            },
+            "id": "17841405822304914"
+        }"#
+        .to_vec();
+        let mention: response::Mention = json::from_body(&mut body).unwrap();
+        assert!(mention.mentioned_comment.is_none());
+        let media = user::media_from_value(
+            mention.mentioned_media.unwrap(),
+            UrlPolicy::Strict,
+            Timezone::Original,
+        )
+        .unwrap();
+        assert_eq!(media.id(), 17873440459141021);
+        assert_eq!(media.mentions(), ["jane.doe"]);
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Discovery of business accounts connected to Facebook Pages of the user.
+
+use super::{Account, GraphError};
+use crate::{auth::Token, client::Client, json};
+
+use url::Url;
+
+/// Facebook Page that the user can manage, returned by [pages].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Page {
+    pub id: u64,
+    pub name: String,
+    /// Instagram user ID of the connected business account.
+    pub account_id: Option<u64>,
+    /// Username of the connected business account.
+    pub username: Option<String>,
+}
+
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Page {
+        pub(super) id: String,
+        #[serde(default)]
+        pub(super) name: String,
+        pub(super) instagram_business_account: Option<Account>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Account {
+        pub(super) id: String,
+        pub(super) username: Option<String>,
+    }
+}
+
+/// Lists all Facebook Pages that the user with the User access `token` can manage,
+/// along with the connected Instagram accounts. Requires the `pages_show_list` permission.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::Token, client::Client, graph};
+///
+/// # fn run<T: Token>(token: T) -> Result<(), Box<dyn std::error::Error>> {
+/// for page in graph::pages(&token, &Client::new()?)? {
+///     if let Some(username) = page.username {
+///         println!("{} is connected to {}", username, page.name);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn pages<T: Token>(token: &T, client: &Client) -> crate::Result<Vec<Page>> {
+    let url = Url::parse_with_params(
+        format!("{}/{}/me/accounts", crate::FACEBOOK_BASE_URL, crate::API_VERSION).as_str(),
+        [
+            ("fields", "id,name,instagram_business_account{id,username}"),
+            ("access_token", token.get()),
+        ],
+    )?;
+    let mut body = client.get(url)?.text()?.into_bytes();
+    let container = json::from_body(&mut body)?;
+    super::collect_rest(client, container)?.into_iter().map(Page::from).collect()
+}
+
+impl<T: Token> Account<T> {
+    /// Resolves the Instagram account connected to the first Facebook Page of the user
+    /// that has one, so the Page ID doesn't have to be known in advance. Fails with
+    /// [GraphError::NoConnectedAccount] if no Page has a connected account.
+    /// Use [pages] to choose among several accounts.
+    pub fn discover(token: T, client: Client) -> crate::Result<Self> {
+        let account_id = pages(&token, &client)?.into_iter().find_map(|page| page.account_id);
+        match account_id {
+            Some(id) => Ok(Self::with_client(token, client, id)),
+            None => Err(Box::new(GraphError::NoConnectedAccount)),
+        }
+    }
+}
+
+impl Page {
+    fn from(response: response::Page) -> crate::Result<Self> {
+        let (account_id, username) = match response.instagram_business_account {
+            Some(account) => (Some(account.id.parse()?), account.username),
+            None => (None, None),
+        };
+        Ok(Self { id: response.id.parse()?, name: response.name, account_id, username })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pages() {
+        let mut body = br#"{"data": [
+            {"id": "134895793791914", "name": "Jaspers Market"},
+            {
+                "id": "17841405822304914",
+                "name": "Jaspers Bakery",
+                "instagram_business_account": {"id": "17841405309211844", "username": "bakery"}
+            }
+        ]}"#
+        .to_vec();
+        let container: super::super::response::Container<response::Page> =
+            json::from_body(&mut body).unwrap();
+        let pages: Vec<_> =
+            container.data.into_iter().map(|page| Page::from(page).unwrap()).collect();
+        assert_eq!(pages[0].account_id, None);
+        assert_eq!(
+            pages[1],
+            Page {
+                id: 17841405822304914,
+                name: "Jaspers Bakery".to_string(),
+                account_id: Some(17841405309211844),
+                username: Some("bakery".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Facebook User access tokens used by the Graph API.
+
+use crate::auth::{AuthError, Secrets, Token};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Serializable long-lived Facebook User access token, valid for about 60 days.
+/// Unlike [LongLivedToken][crate::auth::LongLivedToken], it's issued by Facebook Login,
+/// so it's accepted by the Graph API and not by the Basic Display API.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{client::Client, graph::{Account, FacebookToken}};
+///
+/// # fn run(secrets: instapi::auth::Secrets) -> Result<(), Box<dyn std::error::Error>> {
+/// // A short-lived token is returned by Facebook Login, e.g. by the JavaScript SDK.
+/// let token = FacebookToken::new(&secrets, "short-lived token")?;
+/// let account = Account::discover(token, Client::new()?)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct FacebookToken {
+    access_token: String,
+    /// App-scoped Facebook user ID.
+    user_id: u64,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    expiration_date: DateTime<Utc>,
+    #[serde(default)]
+    scopes: Option<Vec<String>>,
+}
+
+/// Abstractions over JSON responses.
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Token {
+        pub(super) access_token: String,
+        /// Represented in seconds. Not reported for tokens that never expire.
+        pub(super) expires_in: Option<u32>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct User {
+        pub(super) id: String,
+        pub(super) permissions: Option<Permissions>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Permissions {
+        pub(super) data: Vec<Permission>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Permission {
+        pub(super) permission: String,
+        /// `granted` or `declined`.
+        pub(super) status: String,
+    }
+}
+
+impl FacebookToken {
+    /// Constructs a long-lived token by exchanging a short-lived one using the
+    /// `fb_exchange_token` grant. Failures are wrapped into [AuthError::LongLivedExchange].
+    ///
+    /// # Panics
+    /// If `format!` panics while constructing an URL.
+    pub fn new(secrets: &Secrets, short_lived_token: &str) -> crate::Result<Self> {
+        Self::exchange(secrets, short_lived_token)
+            .map_err(|e| AuthError::LongLivedExchange(e).into())
+    }
+
+    fn exchange(secrets: &Secrets, short_lived_token: &str) -> crate::Result<Self> {
+        let token = request_exchange(secrets, short_lived_token)?;
+        let url = Url::parse_with_params(
+            format!("{}/{}/me", crate::FACEBOOK_BASE_URL, crate::API_VERSION).as_str(),
+            [("fields", "id,permissions"), ("access_token", token.access_token.as_str())],
+        )?;
+        let response = crate::error::check(reqwest::blocking::get(url)?)?;
+        Self::from(token, response.json()?)
+    }
+
+    /// Exchanges a valid token for a new one, extending its lifetime.
+    /// Failures are wrapped into [AuthError::Refresh].
+    ///
+    /// # Panics
+    /// If `format!` panics while constructing an URL.
+    pub fn refresh(&mut self, secrets: &Secrets) -> crate::Result<()> {
+        self.request_refresh(secrets).map_err(|e| AuthError::Refresh(e).into())
+    }
+
+    fn request_refresh(&mut self, secrets: &Secrets) -> crate::Result<()> {
+        if !self.is_valid() {
+            return Err(Box::new(crate::Error::TokenExpired(None)));
+        }
+
+        let token = request_exchange(secrets, &self.access_token)?;
+        self.expiration_date = expiration_date(token.expires_in);
+        self.access_token = token.access_token;
+        Ok(())
+    }
+
+    fn from(token: response::Token, user: response::User) -> crate::Result<Self> {
+        let scopes = user.permissions.map(|permissions| {
+            permissions
+                .data
+                .into_iter()
+                .filter(|permission| permission.status == "granted")
+                .map(|permission| permission.permission)
+                .collect()
+        });
+        Ok(Self {
+            access_token: token.access_token,
+            user_id: user.id.parse()?,
+            expiration_date: expiration_date(token.expires_in),
+            scopes,
+        })
+    }
+}
+
+impl Token for FacebookToken {
+    fn get(&self) -> &str {
+        &self.access_token
+    }
+    /// Returns the app-scoped Facebook user ID, it differs from the Instagram one.
+    fn user_id(&self) -> Option<u64> {
+        Some(self.user_id)
+    }
+    fn expiration_date(&self) -> &DateTime<Utc> {
+        &self.expiration_date
+    }
+    /// Returns Facebook permissions, like `instagram_basic` or `pages_show_list`.
+    fn scopes(&self) -> Option<&[String]> {
+        self.scopes.as_deref()
+    }
+}
+
+/// Exchanges `access_token` for a long-lived one.
+fn request_exchange(secrets: &Secrets, access_token: &str) -> crate::Result<response::Token> {
+    let url = Url::parse_with_params(
+        format!("{}/{}/oauth/access_token", crate::FACEBOOK_BASE_URL, crate::API_VERSION)
+            .as_str(),
+        [
+            ("client_id", secrets.app_id.to_string().as_str()),
+            ("client_secret", secrets.app_secret),
+            ("grant_type", "fb_exchange_token"),
+            ("fb_exchange_token", access_token),
+        ],
+    )?;
+    let response = crate::error::check(reqwest::blocking::get(url)?)?;
+    Ok(response.json()?)
+}
+
+fn expiration_date(expires_in: Option<u32>) -> DateTime<Utc> {
+    match expires_in {
+        Some(seconds) => Utc::now() + Duration::seconds(seconds.into()),
+        None => chrono::MAX_DATETIME,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_response() {
+        let token: response::Token =
+            serde_json::from_str(r#"{"access_token": "abc", "expires_in": 5183944}"#).unwrap();
+        let user: response::User = serde_json::from_str(
+            r#"{"id": "10158", "permissions": {"data": [
+                {"permission": "pages_show_list", "status": "granted"},
+                {"permission": "instagram_basic", "status": "declined"}
+            ]}}"#,
+        )
+        .unwrap();
+        let token = FacebookToken::from(token, user).unwrap();
+        assert_eq!(token.get(), "abc");
+        assert_eq!(token.user_id(), Some(10158));
+        assert!(token.is_valid());
+        assert_eq!(token.has_scope("pages_show_list"), Some(true));
+        assert_eq!(token.has_scope("instagram_basic"), Some(false));
+
+        let token: response::Token = serde_json::from_str(r#"{"access_token": ""}"#).unwrap();
+        let user = response::User { id: "1".to_string(), permissions: None };
+        let token = FacebookToken::from(token, user).unwrap();
+        assert_eq!(*token.expiration_date(), chrono::MAX_DATETIME);
+        assert_eq!(token.has_scope("instagram_basic"), None);
+    }
+}
@@ -0,0 +1,397 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Local SQLite index of media metadata, so media can be queried without the API.
+
+use crate::{
+    download::{DownloadReport, ItemReport},
+    user::{Media, MediaProductType, MediaType},
+};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql};
+use url::Url;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS media (
+        id INTEGER PRIMARY KEY,
+        media_type TEXT NOT NULL,
+        product_type TEXT,
+        username TEXT,
+        caption TEXT,
+        timestamp INTEGER,
+        permalink TEXT,
+        parent_id INTEGER,
+        path TEXT,
+        checksum TEXT
+    );
+    CREATE INDEX IF NOT EXISTS media_timestamp ON media (timestamp);
+
+    CREATE VIRTUAL TABLE IF NOT EXISTS media_fts
+        USING fts5 (caption, content = 'media', content_rowid = 'id');
+    CREATE TRIGGER IF NOT EXISTS media_fts_insert AFTER INSERT ON media BEGIN
+        INSERT INTO media_fts (rowid, caption) VALUES (new.id, new.caption);
+    END;
+    CREATE TRIGGER IF NOT EXISTS media_fts_delete AFTER DELETE ON media BEGIN
+        INSERT INTO media_fts (media_fts, rowid, caption) VALUES ('delete', old.id, old.caption);
+    END;
+    CREATE TRIGGER IF NOT EXISTS media_fts_update AFTER UPDATE OF caption ON media BEGIN
+        INSERT INTO media_fts (media_fts, rowid, caption) VALUES ('delete', old.id, old.caption);
+        INSERT INTO media_fts (rowid, caption) VALUES (new.id, new.caption);
+    END;
+";
+
+/// Columns that [Record] is read from.
+const COLUMNS: &str =
+    "id, media_type, product_type, username, caption, timestamp, permalink, parent_id, path, \
+     checksum";
+
+/// Media metadata persisted to an SQLite database along with local paths and checksums
+/// of the downloaded files. Items are updated when they're inserted again, a known local
+/// path or checksum is kept if the new item has none.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::LongLivedToken, download::Downloader, index::Index, user::Profile};
+///
+/// # fn run(token: LongLivedToken) -> Result<(), Box<dyn std::error::Error>> {
+/// let profile = Profile::new(token)?;
+/// let report = Downloader::new(profile.clone(), "backup").download_all(profile.media()?);
+/// let mut index = Index::open("backup/index.sqlite")?;
+/// index.record_report(&report)?;
+/// println!("{} items are indexed", index.len()?);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Index {
+    connection: Connection,
+}
+
+/// Media item stored in the [Index].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Record {
+    pub id: u64,
+    pub media_type: MediaType,
+    pub product_type: Option<MediaProductType>,
+    pub username: Option<String>,
+    pub caption: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub permalink: Option<Url>,
+    /// ID of the album the item is in.
+    pub parent_id: Option<u64>,
+    /// Path of the downloaded file or album directory.
+    pub path: Option<PathBuf>,
+    /// SHA-256 checksum of the downloaded file.
+    pub checksum: Option<String>,
+}
+
+impl Index {
+    /// Opens the index stored at `path`, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens an index that isn't persisted.
+    pub fn open_in_memory() -> crate::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> crate::Result<Self> {
+        let sql = "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'media_fts')";
+        let has_fts: bool = connection.query_row(sql, [], |row| row.get(0))?;
+        connection.execute_batch(SCHEMA)?;
+        if !has_fts {
+            // Captions of the items indexed before the search was introduced.
+            connection.execute("INSERT INTO media_fts (media_fts) VALUES ('rebuild')", [])?;
+        }
+        Ok(Self { connection })
+    }
+
+    /// Inserts or updates metadata of `media` given the ID of its album.
+    pub fn insert(&self, media: &Media, parent_id: Option<u64>) -> crate::Result<()> {
+        upsert(&self.connection, media, parent_id, None, None)
+    }
+
+    /// Inserts or updates metadata of the item along with the path and checksum
+    /// of the downloaded file.
+    ///
+    /// To index items as soon as they're downloaded, call it from
+    /// [on_item][crate::download::Downloader::on_item]. The callback runs on worker threads,
+    /// while a connection can be used by only one thread at a time, so put the index
+    /// into a [Mutex][std::sync::Mutex]:
+    /// ```no_run
+    /// use instapi::{auth::LongLivedToken, download::Downloader, index::Index, user::Profile};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// # fn run(token: LongLivedToken) -> Result<(), Box<dyn std::error::Error>> {
+    /// let index = Arc::new(Mutex::new(Index::open("backup/index.sqlite")?));
+    /// let mut downloader = Downloader::new(Profile::new(token)?, "backup");
+    /// let shared = Arc::clone(&index);
+    /// downloader.on_item(move |item| {
+    ///     if let Err(e) = shared.lock().unwrap().record(item) {
+    ///         eprintln!("Couldn't index {}: {}", item.media().id(), e);
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn record(&self, item: &ItemReport) -> crate::Result<()> {
+        upsert(&self.connection, item.media(), item.parent_id(), item.path(), item.checksum())
+    }
+
+    /// Records all items of `report` in a single transaction.
+    pub fn record_report(&mut self, report: &DownloadReport) -> crate::Result<()> {
+        let transaction = self.connection.transaction()?;
+        for item in report.items() {
+            upsert(&transaction, item.media(), item.parent_id(), item.path(), item.checksum())?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Removes the item with `id`. Returns `false` if there is no such item.
+    pub fn remove(&self, id: u64) -> crate::Result<bool> {
+        Ok(self.connection.execute("DELETE FROM media WHERE id = ?1", [to_sql_id(id)])? > 0)
+    }
+
+    pub fn get(&self, id: u64) -> crate::Result<Option<Record>> {
+        let sql = format!("SELECT {} FROM media WHERE id = ?1", COLUMNS);
+        Ok(self.connection.query_row(&sql, [to_sql_id(id)], record).optional()?)
+    }
+
+    pub fn contains(&self, id: u64) -> crate::Result<bool> {
+        let sql = "SELECT EXISTS (SELECT 1 FROM media WHERE id = ?1)";
+        Ok(self.connection.query_row(sql, [to_sql_id(id)], |row| row.get(0))?)
+    }
+
+    /// Returns the number of indexed items, including album contents.
+    pub fn len(&self) -> crate::Result<usize> {
+        let sql = "SELECT COUNT(*) FROM media";
+        let count: i64 = self.connection.query_row(sql, [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> crate::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Returns all items, newest first. Items without a timestamp go last.
+    pub fn records(&self) -> crate::Result<Vec<Record>> {
+        self.query("1", &[])
+    }
+
+    /// Returns items of the given type, newest first.
+    pub fn by_type(&self, media_type: MediaType) -> crate::Result<Vec<Record>> {
+        self.query("media_type = ?1", &[&media_type.as_str()])
+    }
+
+    /// Returns contents of the album with `parent_id`.
+    pub fn album_contents(&self, parent_id: u64) -> crate::Result<Vec<Record>> {
+        self.query("parent_id = ?1", &[&to_sql_id(parent_id)])
+    }
+
+    /// Returns items published in the range from `after` (inclusive) to `before` (exclusive),
+    /// newest first.
+    pub fn published_between(
+        &self,
+        after: DateTime<Utc>,
+        before: DateTime<Utc>,
+    ) -> crate::Result<Vec<Record>> {
+        self.query("timestamp >= ?1 AND timestamp < ?2", &[&after.timestamp(), &before.timestamp()])
+    }
+
+    /// Returns items whose captions contain all words of `query`, newest first.
+    /// Words are matched regardless of case and hashtag or mention signs,
+    /// special characters of the FTS5 syntax are matched literally.
+    pub fn search(&self, query: &str) -> crate::Result<Vec<Record>> {
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.query("id IN (SELECT rowid FROM media_fts WHERE media_fts MATCH ?1)", &[&query])
+    }
+
+    /// Returns the publish date of the newest item, e.g. to request only the items
+    /// published since then.
+    pub fn latest_timestamp(&self) -> crate::Result<Option<DateTime<Utc>>> {
+        let seconds: Option<i64> =
+            self.connection.query_row("SELECT MAX(timestamp) FROM media", [], |row| row.get(0))?;
+        Ok(seconds.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()))
+    }
+
+    /// Selects items that match the SQL `condition`, newest first.
+    fn query(&self, condition: &str, params: &[&dyn ToSql]) -> crate::Result<Vec<Record>> {
+        let sql = format!(
+            "SELECT {} FROM media WHERE {} ORDER BY timestamp IS NULL, timestamp DESC, id DESC",
+            COLUMNS, condition
+        );
+        let mut statement = self.connection.prepare_cached(&sql)?;
+        let records = statement.query_map(params, record)?;
+        Ok(records.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+/// Inserts or updates the item. Known parent ID, path and checksum are kept if the new
+/// values are unknown.
+fn upsert(
+    connection: &Connection,
+    media: &Media,
+    parent_id: Option<u64>,
+    path: Option<&Path>,
+    checksum: Option<&str>,
+) -> crate::Result<()> {
+    let mut statement = connection.prepare_cached(
+        "INSERT INTO media (id, media_type, product_type, username, caption, timestamp, \
+         permalink, parent_id, path, checksum) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT (id) DO UPDATE SET
+             media_type = excluded.media_type,
+             product_type = excluded.product_type,
+             username = excluded.username,
+             caption = excluded.caption,
+             timestamp = excluded.timestamp,
+             permalink = excluded.permalink,
+             parent_id = COALESCE(excluded.parent_id, parent_id),
+             path = COALESCE(excluded.path, path),
+             checksum = COALESCE(excluded.checksum, checksum)",
+    )?;
+    statement.execute(params![
+        to_sql_id(media.id()),
+        media.media_type().as_str(),
+        media.media_product_type().map(MediaProductType::as_str),
+        media.username(),
+        media.caption(),
+        media.timestamp().map(DateTime::timestamp),
+        media.permalink().map(Url::as_str),
+        parent_id.map(to_sql_id),
+        path.map(|path| path.to_string_lossy()),
+        checksum,
+    ])?;
+    Ok(())
+}
+
+/// Quotes each word of `query`, so they're matched as strings joined with `AND`.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Converts a media ID to the signed integer stored by SQLite. IDs fit into it,
+/// and the conversion round-trips anyway.
+fn to_sql_id(id: u64) -> i64 {
+    id as i64
+}
+
+/// Reads a record from a row of [COLUMNS].
+fn record(row: &Row) -> rusqlite::Result<Record> {
+    let parse_error = |index, e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e)
+    };
+    let media_type: String = row.get(1)?;
+    let product_type: Option<String> = row.get(2)?;
+    let timestamp: Option<i64> = row.get(5)?;
+    let permalink: Option<String> = row.get(6)?;
+    let parent_id: Option<i64> = row.get(7)?;
+    let path: Option<String> = row.get(8)?;
+    Ok(Record {
+        id: row.get::<_, i64>(0)? as u64,
+        media_type: media_type.parse().map_err(|e| parse_error(1, Box::new(e)))?,
+        product_type: product_type
+            .map(|product_type| product_type.parse())
+            .transpose()
+            .map_err(|e| parse_error(2, Box::new(e)))?,
+        username: row.get(3)?,
+        caption: row.get(4)?,
+        timestamp: timestamp.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()),
+        permalink: permalink
+            .map(|permalink| Url::parse(&permalink))
+            .transpose()
+            .map_err(|e| parse_error(6, Box::new(e)))?,
+        parent_id: parent_id.map(|id| id as u64),
+        path: path.map(PathBuf::from),
+        checksum: row.get(9)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        download::Downloader,
+        policy::{Action, Rule, SyncPolicy},
+        user::tests::{app_profile, captioned, media, video},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn upsert_and_query() {
+        let index = Index::open_in_memory().unwrap();
+        assert!(index.is_empty().unwrap());
+        index.insert(&media(1), None).unwrap();
+        let path = Path::new("album/2.mp4");
+        upsert(&index.connection, &video(2), Some(1), Some(path), Some("ab")).unwrap();
+        // Metadata is updated, but the known path and checksum are kept.
+        index.insert(&video(2), None).unwrap();
+
+        assert_eq!(index.len().unwrap(), 2);
+        assert!(index.contains(1).unwrap());
+        let record = index.get(2).unwrap().unwrap();
+        assert_eq!(record.media_type, MediaType::Video);
+        assert_eq!(record.parent_id, Some(1));
+        assert_eq!(record.path.as_deref(), Some(Path::new("album/2.mp4")));
+        assert_eq!(record.checksum.as_deref(), Some("ab"));
+
+        let ids = |records: Vec<Record>| records.iter().map(|record| record.id).collect::<Vec<_>>();
+        assert_eq!(ids(index.by_type(MediaType::Image).unwrap()), [1]);
+        assert_eq!(ids(index.album_contents(1).unwrap()), [2]);
+        assert_eq!(ids(index.records().unwrap()), [2, 1]);
+        assert_eq!(index.latest_timestamp().unwrap(), media(1).timestamp_utc());
+
+        assert!(index.remove(1).unwrap());
+        assert!(!index.remove(1).unwrap());
+        assert!(index.get(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn record_from_workers() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = Arc::new(Mutex::new(Index::open_in_memory().unwrap()));
+        let mut downloader = Downloader::new(app_profile(), dir.path());
+        downloader.set_policy(SyncPolicy { rules: vec![Rule::new(Action::MetadataOnly)] });
+        let shared = Arc::clone(&index);
+        downloader.on_item(move |item| shared.lock().unwrap().record(item).unwrap());
+
+        assert!(downloader.download_all(vec![media(1), video(2)]).is_success());
+        assert_eq!(index.lock().unwrap().len().unwrap(), 2);
+    }
+
+    #[test]
+    fn search() {
+        let index = Index::open_in_memory().unwrap();
+        index.insert(&captioned(1, "Sunset at the #beach"), None).unwrap();
+        index.insert(&captioned(2, "Beach volleyball with @friends"), None).unwrap();
+        index.insert(&media(3), None).unwrap();
+
+        let ids = |query| index.search(query).unwrap().iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids("beach"), [2, 1]);
+        assert_eq!(ids("#BEACH sunset"), [1]);
+        assert_eq!(ids("@friends \" OR"), Vec::<u64>::new());
+        assert_eq!(ids("@friends \""), [2]);
+        assert!(ids("  ").is_empty());
+
+        // Updated and removed captions aren't found.
+        index.insert(&captioned(1, "Mountains"), None).unwrap();
+        assert_eq!(ids("beach"), [2]);
+        index.remove(2).unwrap();
+        assert!(ids("beach").is_empty());
+    }
+
+    #[test]
+    fn fts_query() {
+        assert_eq!(super::fts_query(" a \"b\" OR "), "\"a\" \"\"\"b\"\"\" \"OR\"");
+    }
+}
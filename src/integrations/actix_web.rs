@@ -0,0 +1,163 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! [actix-web](https://docs.rs/actix-web) extractors for authenticated Instagram sessions.
+//!
+//! # Examples
+//! ```no_run
+//! use actix_web::{web, App, Responder};
+//! use instapi::integrations::actix_web::{SessionProfile, TokenStores};
+//!
+//! async fn username(profile: SessionProfile) -> actix_web::Result<impl Responder> {
+//!     profile.run(|profile| Ok(profile.info()?.username().to_string())).await
+//! }
+//!
+//! let app = App::new()
+//!     .app_data(web::Data::new(TokenStores::directory("tokens", "session")))
+//!     .route("/username", web::get().to(username));
+//! ```
+
+use crate::{
+    auth::{LongLivedToken, Token, TokenStore},
+    user::Profile,
+};
+use std::{future::Future, path::PathBuf, pin::Pin, sync::Arc};
+
+use ::actix_web::{
+    dev::Payload,
+    error::{ErrorInternalServerError, ErrorUnauthorized},
+    web, Error, FromRequest, HttpRequest,
+};
+
+type Resolver = dyn Fn(&HttpRequest) -> Option<TokenStore> + Send + Sync;
+
+/// Resolves a [TokenStore] of the user who sent a request.
+/// Register it using [App::app_data][::actix_web::App::app_data] wrapped into [web::Data].
+#[derive(Clone)]
+pub struct TokenStores {
+    resolver: Arc<Resolver>,
+}
+
+/// Extractor that loads a valid token of the current session. The token file is read
+/// on a thread pool, so the extractor doesn't block the server.
+/// Responds with `401 Unauthorized` if there is no token or it has been expired.
+pub struct SessionProfile {
+    token: LongLivedToken,
+}
+
+impl TokenStores {
+    /// Uses `resolver` to find a store for a request, e.g. based on a session.
+    /// Return `None` if the request isn't authenticated.
+    pub fn new<F>(resolver: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Option<TokenStore> + Send + Sync + 'static,
+    {
+        Self { resolver: Arc::new(resolver) }
+    }
+
+    /// Keeps tokens in `dir`, naming files after the value of the `cookie` cookie.
+    /// Values with characters other than ASCII alphanumerics, `-` and `_` are rejected.
+    pub fn directory<P: Into<PathBuf>>(dir: P, cookie: &str) -> Self {
+        let dir = dir.into();
+        let cookie = cookie.to_string();
+        Self::new(move |request| {
+            let session = request.cookie(&cookie)?;
+            let session = session.value();
+            let is_safe = !session.is_empty()
+                && session.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            is_safe.then(|| TokenStore::new(dir.join(session).with_extension("json")))
+        })
+    }
+
+    /// Returns a store for `request`.
+    pub fn resolve(&self, request: &HttpRequest) -> Option<TokenStore> {
+        (self.resolver)(request)
+    }
+}
+
+impl SessionProfile {
+    /// Returns the loaded token.
+    pub fn token(&self) -> &LongLivedToken {
+        &self.token
+    }
+
+    /// Constructs a [Profile] and passes it to `func` on a thread pool,
+    /// as the profile performs blocking requests.
+    /// An error returned by `func` results in `500 Internal Server Error`.
+    pub async fn run<F, R>(self, func: F) -> Result<R, Error>
+    where
+        F: FnOnce(&Profile<LongLivedToken>) -> crate::Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let result = web::block(move || {
+            // Boxed errors can't be sent between threads.
+            let profile = Profile::new(self.token).map_err(|e| e.to_string())?;
+            func(&profile).map_err(|e| e.to_string())
+        })
+        .await?;
+        result.map_err(ErrorInternalServerError)
+    }
+}
+
+impl FromRequest for SessionProfile {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Error>>>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let store = match request.app_data::<web::Data<TokenStores>>() {
+            Some(stores) => stores.resolve(request),
+            None => {
+                return Box::pin(async {
+                    Err(ErrorInternalServerError("TokenStores isn't registered"))
+                });
+            }
+        };
+
+        Box::pin(async move {
+            let store = store.ok_or_else(|| ErrorUnauthorized("not logged in"))?;
+            // Boxed errors can't be sent between threads.
+            let token = web::block(move || store.load().map_err(|e| e.to_string())).await?;
+            match token {
+                Ok(token) if token.is_valid() => Ok(Self { token }),
+                Ok(_) => Err(ErrorUnauthorized("token has been expired")),
+                Err(_) => Err(ErrorUnauthorized("not logged in")),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::actix_web::{cookie::Cookie, http::StatusCode, rt::System, test::TestRequest};
+
+    #[test]
+    fn directory() {
+        let stores = TokenStores::directory("tokens", "session");
+        let resolve = |value: &str| {
+            stores.resolve(&TestRequest::default()
+                .cookie(Cookie::new("session", value.to_string()))
+                .to_http_request())
+        };
+
+        assert_eq!(resolve("abc-1").unwrap().path(), PathBuf::from("tokens/abc-1.json"));
+        assert!(resolve("../secret").is_none());
+        assert!(stores.resolve(&TestRequest::default().to_http_request()).is_none());
+    }
+
+    #[test]
+    fn extract_missing_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = |request: TestRequest| {
+            let request = request.cookie(Cookie::new("session", "abc")).to_http_request();
+            let profile = SessionProfile::from_request(&request, &mut Payload::None);
+            let error = System::new().block_on(profile).err().unwrap();
+            error.as_response_error().status_code()
+        };
+
+        let stores = web::Data::new(TokenStores::directory(dir.path(), "session"));
+        assert_eq!(status(TestRequest::default().app_data(stores)), StatusCode::UNAUTHORIZED);
+        assert_eq!(status(TestRequest::default()), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Ready-made [axum](https://docs.rs/axum) routes for the OAuth redirect and webhooks.
+//!
+//! # Examples
+//! ```no_run
+//! use instapi::{auth::Secrets, integrations::axum::Routes};
+//!
+//! # fn example(secrets: Secrets) {
+//! let app = ::axum::Router::new().nest(
+//!     "/instagram",
+//!     Routes::new(secrets, "verify token")
+//!         .on_token(|token| { /* Save the token. */ })
+//!         .on_event(|event| println!("Webhook event: {}", event))
+//!         .into_router(),
+//! );
+//! # }
+//! ```
+
+use crate::{
+    auth::{self, LongLivedToken, Secrets, ShortLivedToken},
+    webhook::{self, Verification},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ::axum::{
+    body::Bytes,
+    extract::{Query, RawQuery, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+
+/// How long an issued `state` value is accepted by the callback route.
+const STATE_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+type TokenCallback = dyn Fn(LongLivedToken) + Send + Sync;
+type EventCallback = dyn Fn(serde_json::Value) + Send + Sync;
+
+/// Configuration of the routes:
+/// - `GET /login` redirects to the authorization page;
+/// - `GET /callback` validates the `state`, exchanges the code for a long-lived token
+///   and passes it to the [on_token][Routes::on_token] callback;
+/// - `GET /webhook` responds to the subscription verification;
+/// - `POST /webhook` checks the payload signature and passes it to the
+///   [on_event][Routes::on_event] callback.
+///
+/// The callback route must be reachable at the redirect URI of `secrets`.
+pub struct Routes {
+    secrets: Secrets,
+    verify_token: String,
+    on_token: Box<TokenCallback>,
+    on_event: Box<EventCallback>,
+    /// Issued `state` values and their issuing time.
+    states: Mutex<HashMap<String, Instant>>,
+}
+
+/// Abstractions over query parameters.
+mod query {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Callback {
+        pub(super) code: Option<String>,
+        pub(super) state: Option<String>,
+        pub(super) error_description: Option<String>,
+    }
+}
+
+impl Routes {
+    /// `verify_token` is the value specified when configuring the webhook subscription.
+    pub fn new(secrets: Secrets, verify_token: &str) -> Self {
+        Self {
+            secrets,
+            verify_token: verify_token.to_string(),
+            on_token: Box::new(|_| {}),
+            on_event: Box::new(|_| {}),
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets a callback that receives a token after the successful authorization.
+    /// It's called from a blocking thread, so it can save the token synchronously.
+    pub fn on_token<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(LongLivedToken) + Send + Sync + 'static,
+    {
+        self.on_token = Box::new(callback);
+        self
+    }
+
+    /// Sets a callback that receives payloads of webhooks with a valid signature.
+    pub fn on_event<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(serde_json::Value) + Send + Sync + 'static,
+    {
+        self.on_event = Box::new(callback);
+        self
+    }
+
+    /// Constructs a router that can be nested into an application.
+    pub fn into_router(self) -> Router {
+        Router::new()
+            .route("/login", get(login))
+            .route("/callback", get(callback))
+            .route("/webhook", get(verify_webhook).post(receive_webhook))
+            .with_state(Arc::new(self))
+    }
+
+    /// Generates and remembers a new `state` value, forgetting expired ones.
+    fn issue_state(&self) -> Result<String, getrandom::Error> {
+        let mut bytes = [0; 16];
+        getrandom::getrandom(&mut bytes)?;
+        let state = hex::encode(bytes);
+
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states.retain(|_, issued| issued.elapsed() < STATE_LIFETIME);
+        states.insert(state.clone(), Instant::now());
+        Ok(state)
+    }
+
+    /// Checks if `state` has been issued and isn't expired. Each value is accepted only once.
+    fn consume_state(&self, state: &str) -> bool {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        matches!(states.remove(state), Some(issued) if issued.elapsed() < STATE_LIFETIME)
+    }
+}
+
+async fn login(State(routes): State<Arc<Routes>>) -> Response {
+    let url = routes
+        .issue_state()
+        .map_err(|e| e.to_string())
+        .and_then(|state| {
+            auth::auth_url_with_state(&routes.secrets, &state).map_err(|e| e.to_string())
+        });
+    match url {
+        Ok(url) => Redirect::to(url.as_str()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn callback(
+    State(routes): State<Arc<Routes>>,
+    Query(query): Query<query::Callback>,
+) -> Response {
+    if !query.state.as_deref().is_some_and(|state| routes.consume_state(state)) {
+        return (StatusCode::BAD_REQUEST, "Invalid or expired state").into_response();
+    }
+    let code = match query.code {
+        Some(code) => code,
+        None => {
+            let reason = query.error_description.unwrap_or_else(|| "no code".to_string());
+            return (StatusCode::BAD_REQUEST, format!("Authorization failed: {}", reason))
+                .into_response();
+        }
+    };
+
+    // The library performs blocking requests.
+    let result = tokio::task::spawn_blocking(move || {
+        let token = ShortLivedToken::new(&routes.secrets, &code)
+            .and_then(|token| LongLivedToken::new(&routes.secrets, token))
+            // Boxed errors can't be sent between threads.
+            .map_err(|e| e.to_string())?;
+        (routes.on_token)(token);
+        Ok::<_, String>(())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => (StatusCode::OK, "Authorization completed").into_response(),
+        Ok(Err(e)) => (StatusCode::BAD_GATEWAY, format!("Token exchange failed: {}", e))
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn verify_webhook(
+    State(routes): State<Arc<Routes>>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    let verification = query.as_deref().and_then(Verification::from_query);
+    match verification.as_ref().and_then(|v| v.respond(&routes.verify_token)) {
+        Some(challenge) => (StatusCode::OK, challenge.to_string()).into_response(),
+        None => StatusCode::FORBIDDEN.into_response(),
+    }
+}
+
+async fn receive_webhook(
+    State(routes): State<Arc<Routes>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let signature = headers
+        .get(webhook::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !webhook::verify_signature(signature, &body, routes.secrets.app_secret) {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    match serde_json::from_slice(&body) {
+        Ok(event) => {
+            (routes.on_event)(event);
+            StatusCode::OK
+        }
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    #[test]
+    fn state() {
+        let routes = Routes::new(Secrets {
+            app_id: 1,
+            app_secret: "secret",
+            oauth_uri: Url::parse("https://example.com").unwrap(),
+        }, "token");
+
+        let state = routes.issue_state().unwrap();
+        assert!(!routes.consume_state("unknown"));
+        assert!(routes.consume_state(&state));
+        // A state can't be reused.
+        assert!(!routes.consume_state(&state));
+    }
+}
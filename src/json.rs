@@ -0,0 +1,35 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Backend that parses response bodies: `serde_json` or `simd-json` if the feature is enabled.
+
+use serde::Deserialize;
+
+/// Deserializes `body`, borrowing strings from it where possible.
+/// The body may be modified during parsing, so it can't be parsed again.
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn from_body<'a, T: Deserialize<'a>>(body: &'a mut [u8]) -> crate::Result<T> {
+    Ok(serde_json::from_slice(body)?)
+}
+
+/// Deserializes `body`, borrowing strings from it where possible.
+/// The body is modified during parsing, so it can't be parsed again.
+#[cfg(feature = "simd-json")]
+pub(crate) fn from_body<'a, T: Deserialize<'a>>(body: &'a mut [u8]) -> crate::Result<T> {
+    Ok(simd_json::serde::from_slice(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    #[test]
+    fn from_body() {
+        let mut body = br#"{"plain": "text", "escaped": "line\nbreak"}"#.to_vec();
+        let map: std::collections::HashMap<&str, Cow<'_, str>> =
+            super::from_body(&mut body).unwrap();
+        assert_eq!(map["plain"], "text");
+        assert_eq!(map["escaped"], "line\nbreak");
+    }
+}
@@ -6,22 +6,46 @@
 //! [Instagram Basic Display API](https://developers.facebook.com/docs/instagram-basic-display-api/).
 
 pub mod auth;
+pub mod client;
+pub mod download;
+pub mod error;
+pub mod export;
+pub mod feed;
+pub mod graph;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod integrations;
+pub mod oembed;
+pub mod policy;
+pub mod publish;
+pub mod settings;
+pub mod source;
+pub mod stats;
 pub mod user;
+pub mod util;
+pub mod webhook;
 
-use std::{error::Error, result, str::FromStr};
+mod fairness;
+mod json;
+
+pub use error::Error;
+
+use std::{result, str::FromStr};
 
 const BASE_URL: &str = "https://graph.instagram.com";
 /// Used in requests related to the short-lived token retrieving.
 const AUTH_BASE_URL: &str = "https://api.instagram.com";
+/// Used in requests that require an app access token.
+const FACEBOOK_BASE_URL: &str = "https://graph.facebook.com";
 const API_VERSION: &str = "v13.0";
 
-type Result<T> = result::Result<T, Box<dyn Error>>;
+type Result<T> = result::Result<T, Box<dyn std::error::Error>>;
 
 /// Converts `Option<String>` to `Option<T>` using the [parse][str::parse] method.
 fn parse_opt<T, E>(opt: Option<String>) -> result::Result<Option<T>, E>
 where
     T: FromStr<Err = E>,
-    E: Error,
+    E: std::error::Error,
 {
     Ok(match opt {
         Some(str) => Some(str.parse()?),
@@ -0,0 +1,197 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Embedding of public posts using the
+//! [oEmbed endpoint](https://developers.facebook.com/docs/instagram/oembed/).
+
+use crate::{
+    auth::{AppToken, Token},
+    client::Client,
+    json,
+};
+
+use url::Url;
+
+/// Resolves URLs of public posts into HTML that renders embedded posts, e.g. when
+/// a website builds its pages on the server side. Requests are made on behalf
+/// of the app, so the posts don't have to belong to a user who authorized it.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::{AppToken, Secrets}, oembed::OEmbed};
+///
+/// # fn run(secrets: Secrets) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut oembed = OEmbed::new(AppToken::from_secrets(&secrets))?;
+/// oembed.set_omit_script(true);
+/// let embed = oembed.embed(&url::Url::parse("https://www.instagram.com/p/CbAnFSmJ_lC/")?)?;
+/// println!("Post of {}: {}", embed.author_name, embed.html);
+/// # Ok(())
+/// # }
+/// ```
+pub struct OEmbed {
+    token: AppToken,
+    client: Client,
+    max_width: Option<u32>,
+    hide_caption: bool,
+    omit_script: bool,
+}
+
+/// Embed of a post returned by [OEmbed::embed].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Embed {
+    /// Markup that renders the post. Unless the script is omitted,
+    /// it includes a `<script>` element that loads the embed library.
+    pub html: String,
+    /// Username of the post owner.
+    pub author_name: String,
+    pub provider_name: String,
+    /// Maximum width of the embed in pixels, the height depends on the content.
+    pub width: Option<u32>,
+    pub thumbnail_url: Option<Url>,
+    pub thumbnail_width: Option<u32>,
+    pub thumbnail_height: Option<u32>,
+}
+
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Embed {
+        pub(super) html: String,
+        #[serde(default)]
+        pub(super) author_name: String,
+        #[serde(default)]
+        pub(super) provider_name: String,
+        pub(super) width: Option<u32>,
+        pub(super) thumbnail_url: Option<String>,
+        pub(super) thumbnail_width: Option<u32>,
+        pub(super) thumbnail_height: Option<u32>,
+    }
+}
+
+impl OEmbed {
+    /// Constructs an instance that makes requests using `token`.
+    ///
+    /// Returns an error if a [Client] can't be initialized.
+    pub fn new(token: AppToken) -> crate::Result<Self> {
+        Ok(Self::with_client(token, Client::new()?))
+    }
+
+    /// Works like [new][OEmbed::new], but performs requests using the provided `client`.
+    pub fn with_client(token: AppToken, client: Client) -> Self {
+        Self { token, client, max_width: None, hide_caption: false, omit_script: false }
+    }
+
+    /// Sets the maximum width of embeds in pixels, from 320 to 658.
+    /// The API chooses the width if it's `None`, which is the default.
+    pub fn set_max_width(&mut self, max_width: Option<u32>) {
+        self.max_width = max_width;
+    }
+
+    /// Excludes captions from embeds. Captions are included by default.
+    pub fn set_hide_caption(&mut self, hide_caption: bool) {
+        self.hide_caption = hide_caption;
+    }
+
+    /// Excludes the `<script>` element from embeds, so the embed library can be loaded
+    /// once per page. The script is included by default.
+    pub fn set_omit_script(&mut self, omit_script: bool) {
+        self.omit_script = omit_script;
+    }
+
+    /// Requests the embed of the public post at `post_url`, e.g.
+    /// `https://www.instagram.com/p/CbAnFSmJ_lC/`.
+    pub fn embed(&self, post_url: &Url) -> crate::Result<Embed> {
+        let mut body = self.client.get(self.request_url(post_url)?)?.text()?.into_bytes();
+        parse(&mut body)
+    }
+
+    fn request_url(&self, post_url: &Url) -> crate::Result<Url> {
+        let mut url = Url::parse_with_params(
+            format!("{}/{}/instagram_oembed", crate::FACEBOOK_BASE_URL, crate::API_VERSION)
+                .as_str(),
+            [("url", post_url.as_str()), ("access_token", self.token.get())],
+        )?;
+        {
+            let mut params = url.query_pairs_mut();
+            if let Some(max_width) = self.max_width {
+                params.append_pair("maxwidth", &max_width.to_string());
+            }
+            if self.hide_caption {
+                params.append_pair("hidecaption", "true");
+            }
+            if self.omit_script {
+                params.append_pair("omitscript", "true");
+            }
+        }
+        Ok(url)
+    }
+}
+
+/// Parses a response body of the oEmbed endpoint.
+fn parse(body: &mut [u8]) -> crate::Result<Embed> {
+    let response: response::Embed = json::from_body(body)?;
+    Ok(Embed {
+        html: response.html,
+        author_name: response.author_name,
+        provider_name: response.provider_name,
+        width: response.width,
+        thumbnail_url: crate::parse_opt(response.thumbnail_url)?,
+        thumbnail_width: response.thumbnail_width,
+        thumbnail_height: response.thumbnail_height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Secrets;
+
+    #[test]
+    fn request_url() {
+        let token = AppToken::from_secrets(&Secrets {
+            app_id: 1,
+            app_secret: "secret",
+            oauth_uri: Url::parse("https://example.com/").unwrap(),
+        });
+        let mut oembed = OEmbed::with_client(token, Client::new().unwrap());
+        let post_url = Url::parse("https://www.instagram.com/p/CbAnFSmJ_lC/").unwrap();
+        assert_eq!(
+            oembed.request_url(&post_url).unwrap().query(),
+            Some(
+                "url=https%3A%2F%2Fwww.instagram.com%2Fp%2FCbAnFSmJ_lC%2F\
+                 &access_token=1%7Csecret"
+            )
+        );
+
+        oembed.set_max_width(Some(320));
+        oembed.set_hide_caption(true);
+        oembed.set_omit_script(true);
+        let query = oembed.request_url(&post_url).unwrap().query().unwrap().to_string();
+        assert!(query.ends_with("&maxwidth=320&hidecaption=true&omitscript=true"));
+    }
+
+    #[test]
+    fn parse() {
+        let mut body = br#"{
+            "version": "1.0",
+            "author_name": "diegoquinteiro",
+            "provider_name": "Instagram",
+            "provider_url": "https://www.instagram.com/",
+            "type": "rich",
+            "width": 658,
+            "html": "<blockquote class=\"instagram-media\"></blockquote>",
+            "thumbnail_url": "https://scontent.cdninstagram.com/v/t51.jpg",
+            "thumbnail_width": 640,
+            "thumbnail_height": 640
+        }"#
+        .to_vec();
+        let embed = super::parse(&mut body).unwrap();
+        assert_eq!(embed.author_name, "diegoquinteiro");
+        assert_eq!(embed.html, r#"<blockquote class="instagram-media"></blockquote>"#);
+        assert_eq!(embed.width, Some(658));
+        assert_eq!(embed.thumbnail_url.unwrap().host_str(), Some("scontent.cdninstagram.com"));
+        assert_eq!(embed.thumbnail_height, Some(640));
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Declarative rules that decide how media items are synchronized.
+
+use crate::user::{Media, MediaProductType, MediaType};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Ordered list of rules. The first rule that matches an item defines the action,
+/// items that aren't matched by any rule are [downloaded][Action::Download].
+///
+/// # Examples
+/// Skip videos larger than 500 MB and reels, store only metadata of images published
+/// before 2018:
+/// ```
+/// use instapi::{
+///     policy::{Action, Rule, SyncPolicy},
+///     user::{MediaProductType, MediaType},
+/// };
+///
+/// let policy = SyncPolicy {
+///     rules: vec![
+///         Rule {
+///             media_type: Some(MediaType::Video),
+///             larger_than: Some(500 * 1024 * 1024),
+///             ..Rule::new(Action::Skip)
+///         },
+///         Rule {
+///             product_type: Some(MediaProductType::Reels),
+///             ..Rule::new(Action::Skip)
+///         },
+///         Rule {
+///             media_type: Some(MediaType::Image),
+///             published_before: Some("2018-01-01T00:00:00Z".parse().unwrap()),
+///             ..Rule::new(Action::MetadataOnly)
+///         },
+///     ],
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SyncPolicy {
+    pub rules: Vec<Rule>,
+}
+
+/// Conditions and an action to apply if all of them are met.
+/// A missing condition matches any item.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<MediaType>,
+    /// Surface the item has been published on. Matches only if it's known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_type: Option<MediaProductType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_before: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_after: Option<DateTime<Utc>>,
+    /// Size of the content in bytes. Matches only if the size is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub larger_than: Option<u64>,
+    pub action: Action,
+}
+
+/// What to do with a media item.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Download the content. For albums it means to include their children.
+    Download,
+    /// Keep the metadata, but don't download the content.
+    MetadataOnly,
+    /// Ignore the item completely.
+    Skip,
+}
+
+impl SyncPolicy {
+    /// Returns an action for `media`. `size` is the content length in bytes, if it's known.
+    /// Call this method again once the size becomes known, as size-based rules match only then.
+    pub fn action(&self, media: &Media, size: Option<u64>) -> Action {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(media, size))
+            .map_or(Action::Download, |rule| rule.action)
+    }
+
+    /// Checks if some rules depend on the content size. Then the
+    /// [downloader][crate::download::Downloader] retrieves sizes using HEAD requests,
+    /// so files that are too large aren't downloaded.
+    pub fn has_size_rules(&self) -> bool {
+        self.rules.iter().any(|rule| rule.larger_than.is_some())
+    }
+}
+
+impl Rule {
+    /// Constructs a rule without conditions, which matches all items.
+    pub fn new(action: Action) -> Self {
+        Self {
+            media_type: None,
+            product_type: None,
+            published_before: None,
+            published_after: None,
+            larger_than: None,
+            action,
+        }
+    }
+
+    /// Checks if all conditions are met.
+    /// Date conditions don't match items which publish date can't be parsed.
+    pub fn matches(&self, media: &Media, size: Option<u64>) -> bool {
+        let timestamp = media.timestamp().map(|timestamp| timestamp.with_timezone(&Utc));
+        self.media_type.map_or(true, |media_type| media.media_type() == media_type)
+            && self.product_type.as_ref().map_or(true, |product| {
+                media.media_product_type() == Some(product)
+            })
+            && self.published_before.map_or(true, |date| timestamp.is_some_and(|t| t < date))
+            && self.published_after.map_or(true, |date| timestamp.is_some_and(|t| t > date))
+            && self.larger_than.map_or(true, |limit| size.is_some_and(|size| size > limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::tests::media;
+
+    #[test]
+    fn first_matching_rule() {
+        let policy: SyncPolicy = serde_json::from_str(r#"{"rules": [
+            {"media_type": "VIDEO", "action": "skip"},
+            {"published_before": "2018-01-01T00:00:00Z", "action": "metadata_only"},
+            {"action": "skip"}
+        ]}"#).unwrap();
+        // Test media is an image published in 1970.
+        assert_eq!(policy.action(&media(0), None), Action::MetadataOnly);
+        assert_eq!(SyncPolicy::default().action(&media(0), None), Action::Download);
+    }
+
+    #[test]
+    fn size_rule() {
+        let rule = Rule { larger_than: Some(10), ..Rule::new(Action::Skip) };
+        assert!(!rule.matches(&media(0), None));
+        assert!(!rule.matches(&media(0), Some(10)));
+        assert!(rule.matches(&media(0), Some(11)));
+        assert!(SyncPolicy { rules: vec![rule] }.has_size_rules());
+        assert!(!SyncPolicy::default().has_size_rules());
+    }
+
+    #[test]
+    fn product_type_rule() {
+        let rule = Rule { product_type: Some(MediaProductType::Reels), ..Rule::new(Action::Skip) };
+        // The product type of the test media is unknown.
+        assert!(!rule.matches(&media(0), None));
+        assert!(Rule::new(Action::Skip).matches(&media(0), None));
+
+        // Surfaces unknown to the library are kept as they are.
+        let json = r#"{"product_type":"LIVE","action":"skip"}"#;
+        let rule: Rule = serde_json::from_str(json).unwrap();
+        assert_eq!(rule.product_type, Some(MediaProductType::Unknown("LIVE".to_string())));
+        assert_eq!(serde_json::to_string(&rule).unwrap(), json);
+    }
+}
@@ -0,0 +1,392 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Publishing of media to business accounts using the [Content Publishing API][guide].
+//!
+//! [guide]: https://developers.facebook.com/docs/instagram-api/guides/content-publishing
+
+use crate::{
+    auth::Token,
+    graph::Account,
+    json,
+    user::ParseTypeError,
+};
+use std::{
+    fmt,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+/// Default interval between requests of a container status.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Default time that a container is given to be processed.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Publishes media to a business account. Media is published in two steps: a container
+/// is created from files hosted on a public server, then it's published once the API
+/// has processed it. Requires the `instagram_content_publish` permission.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::Token, graph::Account, publish::{NewMedia, Publisher}};
+///
+/// # fn run<T: Token>(account: Account<T>) -> Result<(), Box<dyn std::error::Error>> {
+/// let publisher = Publisher::new(&account);
+/// if publisher.publishing_limit()?.remaining() > 0 {
+///     let image = NewMedia::image(url::Url::parse("https://example.com/sunset.jpg")?)
+///         .with_caption("Sunset #travel");
+///     let id = publisher.publish_media(&image)?;
+///     println!("Published media {}", id);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Publisher<'a, T> {
+    account: &'a Account<T>,
+    poll_interval: Duration,
+    timeout: Duration,
+}
+
+/// Media to create a container for.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NewMedia {
+    pub content: Content,
+    /// Not supported by carousel items.
+    pub caption: Option<String>,
+    /// Whether the container is a child of a carousel.
+    pub is_carousel_item: bool,
+}
+
+/// Content of a container. Files must be hosted on a public server, as the API
+/// retrieves them by URLs.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Content {
+    /// JPEG image.
+    Image(Url),
+    /// Video, supported as a carousel item only.
+    Video(Url),
+    Reel(Url),
+    /// Containers of up to 10 carousel items.
+    Carousel(Vec<u64>),
+}
+
+/// Processing status of a container.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum ContainerStatus {
+    /// Has been created, but not published within 24 hours.
+    Expired,
+    /// Failed to be processed, e.g. because of an unsupported file format.
+    Error,
+    /// Is ready to be published.
+    Finished,
+    InProgress,
+    Published,
+}
+
+/// Number of media published during the limit period and the maximum number.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct PublishingLimit {
+    pub usage: u32,
+    pub total: u32,
+    /// Length of the period that usage is counted over, usually 24 hours.
+    pub duration: Duration,
+}
+
+/// Describes why a container can't be published.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PublishError {
+    /// The container has [expired][ContainerStatus::Expired] or failed
+    /// to be [processed][ContainerStatus::Error].
+    ContainerFailed { id: u64, status: ContainerStatus },
+    /// The container hasn't been processed in time. Contains the container ID.
+    Timeout(u64),
+}
+
+/// Abstractions over JSON responses.
+mod response {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Node {
+        pub(super) id: String,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Status {
+        pub(super) status_code: String,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Limits {
+        pub(super) data: Vec<Limit>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Limit {
+        pub(super) quota_usage: u32,
+        pub(super) config: Config,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Config {
+        pub(super) quota_total: u32,
+        /// Represented in seconds.
+        pub(super) quota_duration: u64,
+    }
+}
+
+impl<'a, T: Token> Publisher<'a, T> {
+    /// Constructs a publisher to `account`.
+    pub fn new(account: &'a Account<T>) -> Self {
+        Self { account, poll_interval: DEFAULT_POLL_INTERVAL, timeout: DEFAULT_TIMEOUT }
+    }
+
+    /// Sets the interval between requests of a container status
+    /// by [publish_media][Publisher::publish_media]. 5 seconds are used by default.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    /// Sets the time that [publish_media][Publisher::publish_media] waits for a container
+    /// to be processed. 5 minutes are used by default.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Creates a container of `media` and returns its ID.
+    pub fn create(&self, media: &NewMedia) -> crate::Result<u64> {
+        let url = self.account.with_token(self.account_url("media")?);
+        let params = media.params();
+        let params: Vec<_> = params.iter().map(|(key, value)| (*key, value.as_str())).collect();
+        let mut body = self.account.client().post(url, &params)?.text()?.into_bytes();
+        let container: response::Node = json::from_body(&mut body)?;
+        Ok(container.id.parse()?)
+    }
+
+    /// Retrieves the processing status of the container with `container_id`.
+    pub fn status(&self, container_id: u64) -> crate::Result<ContainerStatus> {
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}", crate::FACEBOOK_BASE_URL, crate::API_VERSION, container_id)
+                .as_str(),
+            [("fields", "status_code")],
+        )?;
+        let url = self.account.with_token(url);
+        let mut body = self.account.client().get(url)?.text()?.into_bytes();
+        Ok(json::from_body::<response::Status>(&mut body)?.status_code.parse()?)
+    }
+
+    /// Publishes the processed container with `container_id` and returns the ID of the media.
+    pub fn publish(&self, container_id: u64) -> crate::Result<u64> {
+        let url = self.account.with_token(self.account_url("media_publish")?);
+        let creation_id = container_id.to_string();
+        let response = self.account.client().post(url, &[("creation_id", &creation_id)])?;
+        let mut body = response.text()?.into_bytes();
+        let media: response::Node = json::from_body(&mut body)?;
+        Ok(media.id.parse()?)
+    }
+
+    /// Creates a container of `media`, waits until it's processed and publishes it.
+    /// Returns the ID of the media. Items of a carousel have to be created beforehand.
+    pub fn publish_media(&self, media: &NewMedia) -> crate::Result<u64> {
+        let container_id = self.create(media)?;
+        let started = Instant::now();
+        loop {
+            match self.status(container_id)? {
+                ContainerStatus::Finished => return self.publish(container_id),
+                ContainerStatus::InProgress => {}
+                status => {
+                    return Err(Box::new(PublishError::ContainerFailed { id: container_id, status }))
+                }
+            }
+            if started.elapsed() >= self.timeout {
+                return Err(Box::new(PublishError::Timeout(container_id)));
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// Retrieves the number of media published during the limit period.
+    /// The API rejects containers once the limit is reached.
+    pub fn publishing_limit(&self) -> crate::Result<PublishingLimit> {
+        let mut url = self.account_url("content_publishing_limit")?;
+        url.query_pairs_mut().append_pair("fields", "quota_usage,config");
+        let url = self.account.with_token(url);
+        let mut body = self.account.client().get(url)?.text()?.into_bytes();
+        parse_limit(&mut body)
+    }
+
+    /// Returns URL to the `edge` of the account.
+    fn account_url(&self, edge: &str) -> crate::Result<Url> {
+        Ok(Url::parse(&format!(
+            "{}/{}/{}/{}",
+            crate::FACEBOOK_BASE_URL,
+            crate::API_VERSION,
+            self.account.id(),
+            edge
+        ))?)
+    }
+}
+
+impl NewMedia {
+    pub fn image(url: Url) -> Self {
+        Self::new(Content::Image(url))
+    }
+
+    pub fn reel(url: Url) -> Self {
+        Self::new(Content::Reel(url))
+    }
+
+    /// Constructs an item of a carousel, which can be an image or a video.
+    pub fn carousel_item(content: Content) -> Self {
+        Self { is_carousel_item: true, ..Self::new(content) }
+    }
+
+    /// Constructs a carousel of containers created using [carousel_item][NewMedia::carousel_item].
+    pub fn carousel(children: Vec<u64>) -> Self {
+        Self::new(Content::Carousel(children))
+    }
+
+    pub fn with_caption<S: Into<String>>(mut self, caption: S) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+
+    fn new(content: Content) -> Self {
+        Self { content, caption: None, is_carousel_item: false }
+    }
+
+    /// Returns parameters of the container creation request.
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = match &self.content {
+            Content::Image(url) => vec![("image_url", url.to_string())],
+            Content::Video(url) => {
+                vec![("media_type", "VIDEO".to_string()), ("video_url", url.to_string())]
+            }
+            Content::Reel(url) => {
+                vec![("media_type", "REELS".to_string()), ("video_url", url.to_string())]
+            }
+            Content::Carousel(children) => {
+                let children: Vec<_> = children.iter().map(u64::to_string).collect();
+                vec![("media_type", "CAROUSEL".to_string()), ("children", children.join(","))]
+            }
+        };
+        if let Some(caption) = &self.caption {
+            params.push(("caption", caption.clone()));
+        }
+        if self.is_carousel_item {
+            params.push(("is_carousel_item", "true".to_string()));
+        }
+        params
+    }
+}
+
+impl PublishingLimit {
+    /// Returns the number of media that can be published before the limit is reached.
+    pub fn remaining(&self) -> u32 {
+        self.total.saturating_sub(self.usage)
+    }
+}
+
+impl ContainerStatus {
+    /// Returns the value used by the API, like `IN_PROGRESS`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Expired => "EXPIRED",
+            Self::Error => "ERROR",
+            Self::Finished => "FINISHED",
+            Self::InProgress => "IN_PROGRESS",
+            Self::Published => "PUBLISHED",
+        }
+    }
+}
+
+impl FromStr for ContainerStatus {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "EXPIRED" => Ok(Self::Expired),
+            "ERROR" => Ok(Self::Error),
+            "FINISHED" => Ok(Self::Finished),
+            "IN_PROGRESS" => Ok(Self::InProgress),
+            "PUBLISHED" => Ok(Self::Published),
+            _ => Err(ParseTypeError { type_name: "container status", value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for ContainerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ContainerFailed { id, status } => {
+                write!(f, "container {} can't be published, its status is {}", id, status)
+            }
+            Self::Timeout(id) => write!(f, "container {} hasn't been processed in time", id),
+        }
+    }
+}
+
+impl std::error::Error for PublishError {}
+
+/// Parses a response body of the publishing limit endpoint.
+fn parse_limit(body: &mut [u8]) -> crate::Result<PublishingLimit> {
+    let limits: response::Limits = json::from_body(body)?;
+    let limit = limits.data.into_iter().next().ok_or("publishing limit isn't reported")?;
+    Ok(PublishingLimit {
+        usage: limit.quota_usage,
+        total: limit.config.quota_total,
+        duration: Duration::from_secs(limit.config.quota_duration),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params() {
+        let url = Url::parse("https://example.com/sunset.jpg").unwrap();
+        assert_eq!(
+            NewMedia::image(url.clone()).with_caption("Sunset").params(),
+            [("image_url", url.to_string()), ("caption", "Sunset".to_string())]
+        );
+        assert_eq!(
+            NewMedia::carousel_item(Content::Video(url.clone())).params(),
+            [
+                ("media_type", "VIDEO".to_string()),
+                ("video_url", url.to_string()),
+                ("is_carousel_item", "true".to_string()),
+            ]
+        );
+        assert_eq!(
+            NewMedia::carousel(vec![1, 2]).params(),
+            [("media_type", "CAROUSEL".to_string()), ("children", "1,2".to_string())]
+        );
+    }
+
+    #[test]
+    fn limit() {
+        let mut body = br#"{"data": [{
+            "quota_usage": 2,
+            "config": {"quota_total": 25, "quota_duration": 86400}
+        }]}"#
+        .to_vec();
+        let limit = parse_limit(&mut body).unwrap();
+        assert_eq!(limit.duration, Duration::from_secs(86400));
+        assert_eq!(limit.remaining(), 23);
+        assert!(parse_limit(&mut br#"{"data": []}"#.to_vec()).is_err());
+        assert_eq!("IN_PROGRESS".parse(), Ok(ContainerStatus::InProgress));
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Per-account preferences that are persisted alongside a token.
+
+use crate::{
+    auth::Token,
+    download::{Downloader, FilenameTemplate},
+    policy::SyncPolicy,
+    user::{MediaFields, Profile, UrlPolicy},
+};
+use serde::{Deserialize, Serialize};
+
+/// Serializable preferences of a single account. Stored using
+/// [TokenStore::save_settings][crate::auth::TokenStore::save_settings],
+/// so the same settings are applied every time the account is used.
+///
+/// Missing fields are filled with default values when deserializing.
+///
+/// # Examples
+/// ```
+/// let settings = instapi::settings::AccountSettings {
+///     skip_albums: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct AccountSettings {
+    /// Don't gather album contents.
+    pub skip_albums: bool,
+    /// Use the [strict][UrlPolicy::Strict] URL policy instead of the lenient one.
+    pub strict_urls: bool,
+    /// Rules that decide which media items to download.
+    pub policy: SyncPolicy,
+    /// Fields requested for media items, the [default][MediaFields::default] ones if `None`.
+    pub media_fields: Option<MediaFields>,
+    /// Template of names of downloaded files, the [default][FilenameTemplate::default] one
+    /// if `None`.
+    pub name_template: Option<FilenameTemplate>,
+    /// Template of subdirectories that media is downloaded to.
+    pub layout: Option<FilenameTemplate>,
+}
+
+impl AccountSettings {
+    /// Returns the URL policy that corresponds to [strict_urls][AccountSettings::strict_urls].
+    pub fn url_policy(&self) -> UrlPolicy {
+        if self.strict_urls {
+            UrlPolicy::Strict
+        } else {
+            UrlPolicy::Lenient
+        }
+    }
+
+    /// Applies the URL policy and the field set to `profile`.
+    pub fn apply_to_profile<T: Token>(&self, profile: &mut Profile<T>) {
+        profile.set_url_policy(self.url_policy());
+        if let Some(fields) = self.media_fields {
+            profile.set_media_fields(fields);
+        }
+    }
+
+    /// Applies skipping of albums, the policy, the name template and the layout
+    /// to `downloader`.
+    pub fn apply_to_downloader<T: Token>(&self, downloader: &mut Downloader<T>) {
+        downloader.set_skip_albums(self.skip_albums);
+        downloader.set_policy(self.policy.clone());
+        if let Some(template) = &self.name_template {
+            downloader.set_template(template.clone());
+        }
+        downloader.set_layout(self.layout.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::MediaField;
+
+    #[test]
+    fn deserialize() {
+        let settings: AccountSettings = serde_json::from_str(
+            r#"{"skip_albums": true, "media_fields": ["caption"], "layout": "{type}"}"#,
+        )
+        .unwrap();
+        assert!(settings.skip_albums);
+        assert!(settings.media_fields.unwrap().contains(MediaField::Caption));
+        assert_eq!(settings.layout, Some("{type}".parse().unwrap()));
+        assert_eq!(settings.name_template, None);
+    }
+}
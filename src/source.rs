@@ -0,0 +1,89 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Common interface of accounts of the Basic Display API and the Graph API.
+
+use crate::{
+    auth::Token,
+    graph::{Account, BusinessInfo},
+    user::{Info, Media, Profile},
+};
+
+/// Account that provides media items, implemented by both [Profile] and [Account],
+/// so the same code can process media of either of them.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::source::MediaSource;
+///
+/// fn print_media(source: &dyn MediaSource) -> Result<(), Box<dyn std::error::Error>> {
+///     println!("Media of {}:", source.info()?.username);
+///     for media in source.media_iter() {
+///         println!("{}", media?.media_url());
+///     }
+///     Ok(())
+/// }
+/// ```
+pub trait MediaSource {
+    /// Retrieves information about the account.
+    fn info(&self) -> crate::Result<AccountInfo>;
+    /// Returns an iterator that fetches media items of the account page by page,
+    /// newest first.
+    fn media_iter(&self) -> Box<dyn Iterator<Item = crate::Result<Media>> + '_>;
+    /// Retrieves a single media item by its ID.
+    fn media_by_id(&self, id: u64) -> crate::Result<Media>;
+}
+
+/// Information that both APIs report about an account.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AccountInfo {
+    /// ID of the account, it differs between the APIs.
+    pub id: u64,
+    pub username: String,
+    pub media_count: u64,
+}
+
+impl<T: Token> MediaSource for Profile<T> {
+    fn info(&self) -> crate::Result<AccountInfo> {
+        Profile::info(self).map(AccountInfo::from)
+    }
+    fn media_iter(&self) -> Box<dyn Iterator<Item = crate::Result<Media>> + '_> {
+        Box::new(Profile::media_iter(self))
+    }
+    fn media_by_id(&self, id: u64) -> crate::Result<Media> {
+        Profile::media_by_id(self, id)
+    }
+}
+
+impl<T: Token> MediaSource for Account<T> {
+    fn info(&self) -> crate::Result<AccountInfo> {
+        Account::info(self).map(AccountInfo::from)
+    }
+    fn media_iter(&self) -> Box<dyn Iterator<Item = crate::Result<Media>> + '_> {
+        Box::new(Account::media_iter(self))
+    }
+    fn media_by_id(&self, id: u64) -> crate::Result<Media> {
+        Account::media_by_id(self, id)
+    }
+}
+
+impl From<Info> for AccountInfo {
+    fn from(info: Info) -> Self {
+        Self {
+            id: info.id(),
+            username: info.username().to_string(),
+            media_count: info.media_count(),
+        }
+    }
+}
+
+impl From<BusinessInfo> for AccountInfo {
+    fn from(info: BusinessInfo) -> Self {
+        Self {
+            id: info.id(),
+            username: info.username().to_string(),
+            media_count: info.media_count(),
+        }
+    }
+}
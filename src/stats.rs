@@ -0,0 +1,134 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Statistics of posting: frequency, types of media and hashtags.
+
+#[cfg(feature = "index")]
+use crate::index::{Index, Record};
+use crate::user::{Caption, Media, MediaType};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Utc};
+
+/// Statistics of posts. Album contents aren't counted separately, dates are in UTC.
+///
+/// # Examples
+/// ```no_run
+/// use instapi::{auth::LongLivedToken, stats::Stats, user::Profile};
+///
+/// # fn run(token: LongLivedToken) -> Result<(), Box<dyn std::error::Error>> {
+/// let stats = Stats::from_media(&Profile::new(token)?.media()?);
+/// for (hashtag, count) in stats.top_hashtags(10) {
+///     println!("#{}: {}", hashtag, count);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Stats {
+    pub posts: usize,
+    pub by_type: HashMap<MediaType, usize>,
+    /// Number of posts by years.
+    pub per_year: BTreeMap<i32, usize>,
+    /// Number of posts by years and months (starting from 1).
+    pub per_month: BTreeMap<(i32, u32), usize>,
+    /// Number of posts with a caption.
+    pub captioned_posts: usize,
+    /// Total number of characters of the captions.
+    pub caption_characters: usize,
+    /// Number of posts with each hashtag. Hashtags are lowercase.
+    pub hashtags: HashMap<String, usize>,
+}
+
+impl Stats {
+    pub fn from_media<'a, I: IntoIterator<Item = &'a Media>>(media: I) -> Self {
+        let mut stats = Self::default();
+        for item in media {
+            stats.add(item.media_type(), item.timestamp_utc(), item.caption());
+        }
+        stats
+    }
+
+    /// Computes statistics of the indexed items that aren't album contents.
+    #[cfg(feature = "index")]
+    pub fn from_records<'a, I: IntoIterator<Item = &'a Record>>(records: I) -> Self {
+        let mut stats = Self::default();
+        for record in records.into_iter().filter(|record| record.parent_id.is_none()) {
+            stats.add(record.media_type, record.timestamp, record.caption.as_deref());
+        }
+        stats
+    }
+
+    /// Computes statistics of all posts of `index`.
+    #[cfg(feature = "index")]
+    pub fn from_index(index: &Index) -> crate::Result<Self> {
+        Ok(Self::from_records(&index.records()?))
+    }
+
+    /// Returns the average number of characters of the captions.
+    /// Posts without a caption are skipped.
+    pub fn average_caption_length(&self) -> f64 {
+        match self.captioned_posts {
+            0 => 0.0,
+            posts => self.caption_characters as f64 / posts as f64,
+        }
+    }
+
+    /// Returns up to `count` most frequent hashtags with the number of posts.
+    /// Hashtags of the same frequency are sorted alphabetically.
+    pub fn top_hashtags(&self, count: usize) -> Vec<(&str, usize)> {
+        let mut hashtags: Vec<_> =
+            self.hashtags.iter().map(|(hashtag, count)| (hashtag.as_str(), *count)).collect();
+        hashtags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        hashtags.truncate(count);
+        hashtags
+    }
+
+    fn add(&mut self, media_type: MediaType, date: Option<DateTime<Utc>>, caption: Option<&str>) {
+        self.posts += 1;
+        *self.by_type.entry(media_type).or_default() += 1;
+        if let Some(date) = date {
+            *self.per_year.entry(date.year()).or_default() += 1;
+            *self.per_month.entry((date.year(), date.month())).or_default() += 1;
+        }
+
+        let caption = match caption.filter(|caption| !caption.is_empty()) {
+            Some(caption) => caption,
+            None => return,
+        };
+        self.captioned_posts += 1;
+        self.caption_characters += caption.chars().count();
+        // Hashtags that differ only in case are counted once per post.
+        let hashtags: HashSet<_> =
+            Caption::new(caption).hashtags().iter().map(|hashtag| hashtag.to_lowercase()).collect();
+        for hashtag in hashtags {
+            *self.hashtags.entry(hashtag).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::tests::{captioned, media, video};
+
+    #[test]
+    fn from_media() {
+        let stats = Stats::from_media(&[
+            captioned(1, "#Sea #sun #sea"),
+            captioned(2, "Again #sea"),
+            media(3),
+            video(4),
+        ]);
+        assert_eq!(stats.posts, 4);
+        assert_eq!(stats.by_type[&MediaType::Image], 3);
+        assert_eq!(stats.by_type[&MediaType::Video], 1);
+        assert_eq!(stats.per_year, BTreeMap::from([(1970, 4)]));
+        assert_eq!(stats.per_month, BTreeMap::from([((1970, 1), 4)]));
+        assert_eq!(stats.average_caption_length(), 12.0);
+        assert_eq!(Stats::default().average_caption_length(), 0.0);
+        assert_eq!(stats.top_hashtags(5), [("sea", 2), ("sun", 1)]);
+        assert_eq!(stats.top_hashtags(1), [("sea", 2)]);
+    }
+}
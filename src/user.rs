@@ -4,27 +4,98 @@
 
 //! Provides methods to retrieve user's information and media.
 
-use crate::auth::Token;
-use std::sync::{Arc, Mutex};
+mod cache;
+mod caption;
+mod content;
+mod error;
+mod fields;
+mod iter;
+mod page;
+mod query;
+#[cfg(feature = "stream")]
+mod stream;
+mod summary;
 
-use chrono::{DateTime, FixedOffset};
-use threadpool::ThreadPool;
+pub use caption::Caption;
+pub use content::{extension_from_mime, ContentInfo};
+pub use error::{ItemError, MediaError, ParseTypeError};
+pub use fields::{MediaField, MediaFields};
+pub use iter::MediaIter;
+pub use page::{Cursor, Page};
+pub use query::{MediaOrder, MediaQuery};
+#[cfg(feature = "stream")]
+pub use stream::MediaStream;
+pub use summary::MediaSummary;
+
+use crate::{auth::Token, client::Client, fairness::WeightedRoundRobin, json};
+use cache::ResponseCache;
+use std::{
+    convert::TryFrom,
+    fmt,
+    path::Path,
+    str::FromStr,
+    sync::{mpsc, Arc},
+    time::Duration,
+    thread,
+};
+
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use url::Url;
 
 /// Represents the user profile associated with the provided token.
+///
+/// Cloning is cheap as the token and the client are shared between clones.
+/// A profile is [Send] and [Sync] if the token is, so it can be used by a thread pool.
 pub struct Profile<T> {
+    shared: Arc<Shared<T>>,
+    url_policy: UrlPolicy,
+    timezone: Timezone,
+    media_fields: MediaFields,
+    use_me_endpoint: bool,
+    /// Lifetime of cached responses. Responses aren't cached if it's `None`.
+    cache_ttl: Option<Duration>,
+}
+
+/// Part of a profile that shared between its clones.
+struct Shared<T> {
     token: T,
+    client: Client,
+    cache: ResponseCache,
+}
+
+/// Defines how URLs of media items are validated during parsing.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum UrlPolicy {
+    /// Only absolute `https` URLs with a host are accepted,
+    /// an item that contains any other URL fails to parse.
+    Strict,
+    /// Scheme-relative URLs are resolved using `https`. Other violations don't fail an item:
+    /// they are reported by [Media::warnings], and invalid optional URLs are omitted.
+    Lenient,
+}
+
+/// Offset that publish dates of media items are converted to during parsing.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Timezone {
+    /// Keep the offset reported by the API.
+    Original,
+    Utc,
+    /// Offset of the local timezone.
+    Local,
 }
 
 /// Basic information about the user profile.
 pub struct Info {
+    id: u64,
     username: String,
     account_type: AccountType,
     media_count: u64,
 }
 
 /// The user's account type.
-#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum AccountType {
     Business,
     MediaCreator,
@@ -32,158 +103,634 @@ pub enum AccountType {
 }
 
 /// Provides metadata about the user's media: images, videos and albums.
+#[derive(Clone)]
 pub struct Media {
     id: u64,
     media_type: MediaType,
-    username: String,
+    username: Option<String>,
     caption: Option<String>,
-    timestamp: DateTime<FixedOffset>,
+    media_product_type: Option<MediaProductType>,
+    /// Contents of an album embedded using [MediaField::Children].
+    children: Option<Vec<Media>>,
+    timestamp: Option<DateTime<FixedOffset>>,
+    raw_timestamp: String,
 
     media_url: Url,
     permalink: Option<Url>,
     thumbnail_url: Option<Url>,
+
+    warnings: Vec<String>,
 }
 
 /// Type of a media item.
-#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MediaType {
     Image,
     Video,
     CarouselAlbum,
 }
 
+/// Surface where a media item has been published.
+/// Serialized as the value used by the API, like `REELS`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum MediaProductType {
+    Ad,
+    Feed,
+    Story,
+    Reels,
+    /// A surface that has been introduced after this version of the library.
+    /// Contains the value used by the API, so it's preserved when serialized again.
+    Unknown(String),
+}
+
 /// Abstractions over JSON responses.
 mod response {
+    use std::borrow::Cow;
+
     use serde::Deserialize;
 
     #[derive(Deserialize)]
     pub(super) struct Info {
+        pub(super) id: String,
         pub(super) account_type: String,
         pub(super) media_count: u64,
         pub(super) username: String,
     }
 
+    /// Media items borrow strings from the response body where possible,
+    /// as most of them are parsed into other types anyway.
     #[derive(Deserialize)]
-    pub(super) struct MediaContainer {
-        pub(super) data: Vec<Media>,
+    pub(super) struct MediaContainer<'a> {
+        #[serde(borrow)]
+        pub(super) data: Vec<Media<'a>>,
         pub(super) paging: Paging,
     }
 
+    /// Media container without paging, which is read by [PagingContainer].
+    #[derive(Deserialize)]
+    pub(super) struct ItemsContainer<'a> {
+        #[serde(borrow)]
+        pub(super) data: Vec<Media<'a>>,
+    }
+
+    /// Media container without items. They're skipped without allocations.
     #[derive(Deserialize)]
-    pub(super) struct Media {
+    pub(super) struct PagingContainer {
+        pub(super) paging: Paging,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Media<'a> {
         pub(super) caption: Option<String>,
-        pub(super) id: String,
-        pub(super) media_type: String,
-        pub(super) media_url: String,
-        pub(super) permalink: Option<String>,
-        pub(super) thumbnail_url: Option<String>,
-        pub(super) timestamp: String,
-        pub(super) username: String,
+        #[serde(borrow)]
+        pub(super) id: Cow<'a, str>,
+        #[serde(borrow)]
+        pub(super) media_type: Cow<'a, str>,
+        #[serde(borrow)]
+        pub(super) media_url: Cow<'a, str>,
+        #[serde(borrow)]
+        pub(super) permalink: Option<Cow<'a, str>>,
+        #[serde(borrow)]
+        pub(super) thumbnail_url: Option<Cow<'a, str>>,
+        #[serde(borrow)]
+        pub(super) timestamp: Cow<'a, str>,
+        pub(super) username: Option<String>,
+        #[serde(borrow)]
+        pub(super) media_product_type: Option<Cow<'a, str>>,
+        #[serde(borrow)]
+        pub(super) children: Option<Children<'a>>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Children<'a> {
+        #[serde(borrow)]
+        pub(super) data: Vec<Media<'a>>,
     }
 
     #[derive(Deserialize)]
     pub(super) struct Paging {
         /// URL to the next page with media items.
         pub(super) next: Option<String>,
+        pub(super) previous: Option<String>,
+        pub(super) cursors: Option<Cursors>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Cursors {
+        pub(super) before: Option<String>,
+        pub(super) after: Option<String>,
     }
 }
 
 impl<T: Token> Profile<T> {
     /// Constructs a new profile that associated with the provided `token`.
     /// Before calling make sure that `token` is valid.
-    pub fn new(token: T) -> Profile<T> {
-        Profile { token }
+    ///
+    /// Returns an error if a [Client] can't be initialized.
+    pub fn new(token: T) -> crate::Result<Profile<T>> {
+        Ok(Self::with_client(token, Client::new()?))
     }
 
-    /// Returns the user ID.
-    pub fn id(&self) -> u64 {
-        self.token.user_id()
+    /// Works like [new][Profile::new], but performs requests using the provided `client`.
+    pub fn with_client(token: T, client: Client) -> Profile<T> {
+        Profile {
+            shared: Arc::new(Shared { token, client, cache: ResponseCache::default() }),
+            url_policy: UrlPolicy::Lenient,
+            timezone: Timezone::Original,
+            media_fields: MediaFields::default(),
+            use_me_endpoint: false,
+            cache_ttl: None,
+        }
+    }
+
+    /// Sets a policy that applies to URLs of gathered media items.
+    /// [Lenient][UrlPolicy::Lenient] is used by default.
+    pub fn set_url_policy(&mut self, policy: UrlPolicy) {
+        self.url_policy = policy;
+    }
+
+    /// Sets an offset that publish dates of gathered media items are converted to.
+    /// [Original][Timezone::Original] is used by default.
+    pub fn set_timezone(&mut self, timezone: Timezone) {
+        self.timezone = timezone;
+    }
+
+    /// Sets fields that requested for media items.
+    /// [Default][MediaFields::default] fields are requested if not set.
+    pub fn set_media_fields(&mut self, fields: MediaFields) {
+        self.media_fields = fields;
+    }
+
+    /// Makes requests to the `/me` node instead of the user ID stored in the token,
+    /// which works even if the stored ID is stale or unknown, e.g. for imported tokens.
+    /// The actual ID is reported by [Info::id].
+    pub fn set_use_me_endpoint(&mut self, use_me_endpoint: bool) {
+        self.use_me_endpoint = use_me_endpoint;
+    }
+
+    /// Enables in-memory caching of [info][Profile::info] and media pages for `ttl`,
+    /// so repeated requests don't make network round trips. The cache is shared between
+    /// clones of the profile. Caching is disabled by default or if `ttl` is `None`.
+    pub fn set_cache_ttl(&mut self, ttl: Option<Duration>) {
+        self.cache_ttl = ttl;
+    }
+
+    /// Forgets all cached responses.
+    pub fn clear_cache(&self) {
+        self.shared.cache.clear();
+    }
+
+    /// Returns the client that performs requests of the profile.
+    pub(crate) fn client(&self) -> &Client {
+        &self.shared.client
+    }
+
+    /// Returns the user ID stored in the token, if it's linked to a user.
+    pub fn id(&self) -> Option<u64> {
+        self.shared.token.user_id()
     }
 
     /// Retrieves basic information about the user.
+    /// A cached response is returned if [caching][Profile::set_cache_ttl] is enabled.
     pub fn info(&self) -> crate::Result<Info> {
-        let url = Url::parse_with_params(
-            format!("{}/{}/{}", crate::BASE_URL, crate::API_VERSION, self.id()).as_str(),
-            [
-                ("access_token", self.token.get()),
-                ("fields", "account_type,media_count,username"),
-            ]
-        )?;
-        let response = reqwest::blocking::get(url)?.error_for_status()?;
-        Info::from(response.json::<response::Info>()?)
+        Info::from(self.get_json(self.info_url()?, true)?)
+    }
+
+    /// Works like [info][Profile::info], but always makes a request
+    /// and replaces the cached response.
+    pub fn refresh_info(&self) -> crate::Result<Info> {
+        Info::from(self.get_json(self.info_url()?, false)?)
+    }
+
+    fn info_url(&self) -> crate::Result<Url> {
+        Ok(Url::parse_with_params(
+            format!("{}/{}/{}", crate::BASE_URL, crate::API_VERSION, self.user_node()).as_str(),
+            [("fields", "id,account_type,media_count,username")],
+        )?)
     }
 
-    /// Gathers all user's media items. Uses all logical CPU cores to parse responses.
+    /// Gathers all user's media items, newest first.
+    /// Responses are parsed while the next page is being retrieved.
     /// To gather album contents use [album][Profile::album] method.
-    ///
-    /// # Panics
-    /// If [Client][reqwest::blocking::Client] failed to initialize.
     pub fn media(&self) -> crate::Result<Vec<Media>> {
-        Self::collect_media(Url::parse_with_params(
-            format!("{}/{}/{}/media", crate::BASE_URL, crate::API_VERSION, self.id()).as_str(),
-            self.media_params(),
-        )?)
+        self.media_with(&MediaQuery::default())
     }
 
-    /// Gathers all album contents. Works in the same way as [media][Profile::media] method.
+    /// Works like [media][Profile::media], but narrows down the request using `query`.
+    pub fn media_with(&self, query: &MediaQuery) -> crate::Result<Vec<Media>> {
+        self.media_summary_with(query)?.into_result()
+    }
+
+    /// Works like [media][Profile::media], but items that can't be parsed are skipped
+    /// and reported by [MediaSummary::errors] instead of failing the whole gathering.
+    pub fn media_summary(&self) -> crate::Result<MediaSummary> {
+        self.media_summary_with(&MediaQuery::default())
+    }
+
+    /// Works like [media_summary][Profile::media_summary], but narrows down the request
+    /// using `query`.
+    pub fn media_summary_with(&self, query: &MediaQuery) -> crate::Result<MediaSummary> {
+        Ok(self.collect_media(self.media_url(query)?)?.arrange(query))
+    }
+
+    /// Returns an iterator that fetches the user's media items page by page,
+    /// so they can be processed before the whole history is loaded.
+    pub fn media_iter(&self) -> MediaIter<T> {
+        self.media_iter_with(&MediaQuery::default())
+    }
+
+    /// Works like [media_iter][Profile::media_iter], but narrows down the request
+    /// using `query`.
+    pub fn media_iter_with(&self, query: &MediaQuery) -> MediaIter<T> {
+        MediaIter::new(self.clone(), self.media_url(query), *query)
+    }
+
+    /// Gathers up to `count` newest media items. Pagination stops as soon as
+    /// enough items are retrieved, so it's much cheaper than [media][Profile::media].
+    pub fn media_recent(&self, count: usize) -> crate::Result<Vec<Media>> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        // Don't retrieve more items than needed if only the first page is required.
+        let query = MediaQuery { limit: u32::try_from(count).ok(), ..MediaQuery::default() };
+        self.media_iter_with(&query).take(count).collect()
+    }
+
+    /// Asynchronous version of [media_iter][Profile::media_iter].
+    /// Pages are fetched on a background thread as the stream is polled.
+    #[cfg(feature = "stream")]
+    pub fn media_stream(&self) -> MediaStream
+    where
+        T: Send + Sync + 'static,
+    {
+        MediaStream::spawn(self.clone())
+    }
+
+    /// Retrieves a single page of the user's media items, starting from the beginning
+    /// if `cursor` is `None`. Persist [Page::next] to resume gathering later.
     ///
-    /// # Panics
-    /// If [Client][reqwest::blocking::Client] failed to initialize.
+    /// Fails if any item of the page can't be parsed.
+    pub fn media_page(&self, cursor: Option<&Cursor>) -> crate::Result<Page<Media>> {
+        self.media_page_at(cursor.map(|cursor| ("after", cursor)))
+    }
+
+    /// Retrieves a single page of newer media items that precede the page whose
+    /// [before][Page::before] cursor is `cursor`. Works like [media_page][Profile::media_page].
+    pub fn media_page_before(&self, cursor: &Cursor) -> crate::Result<Page<Media>> {
+        self.media_page_at(Some(("before", cursor)))
+    }
+
+    /// Retrieves a page whose position is set by a cursor parameter (`before` or `after`).
+    fn media_page_at(&self, cursor: Option<(&str, &Cursor)>) -> crate::Result<Page<Media>> {
+        let mut url = self.media_url(&MediaQuery::default())?;
+        if let Some((param, cursor)) = cursor {
+            url.query_pairs_mut().append_pair(param, cursor.as_str());
+        }
+
+        let mut body = self.fetch_body(url, true)?.into_bytes();
+        let container: response::MediaContainer = json::from_body(&mut body)?;
+        let items = container
+            .data
+            .into_iter()
+            .map(|response| self.parse_media(response))
+            .collect::<crate::Result<_>>()?;
+        Ok(Page::new(items, container.paging))
+    }
+
+    /// Retrieves a single media item by its ID, e.g. saved during a previous sync.
+    /// Album contents can be retrieved in the same way.
+    pub fn media_by_id(&self, id: u64) -> crate::Result<Media> {
+        self.fetch_media(id, true)
+    }
+
+    /// Replaces the media URL and the thumbnail URL of `media` with fresh ones,
+    /// as they're signed by the CDN and [expire][Media::media_url_expired] after a while.
+    pub fn refresh_media_url(&self, media: &mut Media) -> crate::Result<()> {
+        let fresh = self.fetch_media(media.id, false)?;
+        media.media_url = fresh.media_url;
+        media.thumbnail_url = fresh.thumbnail_url;
+        Ok(())
+    }
+
+    /// Gathers all album contents. Works in the same way as [media][Profile::media] method.
+    /// Fails with [MediaError::NotAnAlbum] if `parent` isn't an album.
     pub fn album(&self, parent: &Media) -> crate::Result<Vec<Media>> {
         if parent.media_type != MediaType::CarouselAlbum {
-            return Err("parent must be an album".into());
+            return Err(Box::new(MediaError::NotAnAlbum(parent.id)));
         }
+        self.check_media_scope()?;
 
-        Self::collect_media(Url::parse_with_params(
+        self.collect_media(Url::parse_with_params(
             format!("{}/{}/children", crate::BASE_URL, parent.id).as_str(),
             self.media_params(),
-        )?)
+        )?)?.into_result()
     }
 
     /// Recursively retrieves media items by iterating over pages.
-    ///
-    /// # Panics
-    /// If [Client][reqwest::blocking::Client] failed to initialize.
-    fn collect_media(url: Url) -> crate::Result<Vec<Media>> {
+    fn collect_media(&self, url: Url) -> crate::Result<MediaSummary> {
         let mut url = Some(url);
-        let client = reqwest::blocking::Client::new();
-        let pool = ThreadPool::new(num_cpus::get());
-        let media = Arc::new(Mutex::new(Vec::new()));
-
-        while url.is_some() {
-            let response = client.get(url.unwrap()).send()?.error_for_status()?;
-            let media_container: response::MediaContainer = response.json()?;
-            url = crate::parse_opt(media_container.paging.next)?;
-
-            let tx = Arc::clone(&media);
-            let data = media_container.data;
-            pool.execute(move || {
-                let mut media = tx.lock().unwrap();
-                for response in data {
-                    media.push(Media::from(response).unwrap());
-                }
+        let pages = std::iter::from_fn(|| {
+            let page = self.fetch_page(url.take()?).map(|mut page| {
+                url = page.next.take();
+                page
             });
+            Some(page)
+        });
+        parse_pages(pages, self.url_policy, self.timezone)
+    }
+
+    fn fetch_media(&self, id: u64, use_cache: bool) -> crate::Result<Media> {
+        self.check_media_scope()?;
+        let url = Url::parse_with_params(
+            format!("{}/{}/{}", crate::BASE_URL, crate::API_VERSION, id).as_str(),
+            self.media_params(),
+        )?;
+        let mut body = self.fetch_body(url, use_cache)?.into_bytes();
+        self.parse_media(json::from_body(&mut body)?)
+    }
+
+    /// Returns the node that represents the user in request paths.
+    /// Tokens that aren't linked to a user are resolved by the `me` endpoint too.
+    fn user_node(&self) -> String {
+        match self.id() {
+            Some(id) if !self.use_me_endpoint => id.to_string(),
+            _ => "me".to_string(),
+        }
+    }
+
+    fn parse_media(&self, response: response::Media<'_>) -> crate::Result<Media> {
+        Ok(Media::from(response, self.url_policy)?.with_timezone(self.timezone))
+    }
+
+    /// Returns URL to the first page of the user's media.
+    fn media_url(&self, query: &MediaQuery) -> crate::Result<Url> {
+        self.check_media_scope()?;
+        let mut url = Url::parse_with_params(
+            format!("{}/{}/{}/media", crate::BASE_URL, crate::API_VERSION, self.user_node())
+                .as_str(),
+            self.media_params(),
+        )?;
+        query.apply(&mut url);
+        Ok(url)
+    }
+
+    /// Retrieves a single page of media items.
+    fn fetch_page(&self, url: Url) -> crate::Result<RawPage> {
+        RawPage::parse(self.fetch_body(url, true)?)
+    }
+
+    fn get_json<R: DeserializeOwned>(&self, url: Url, use_cache: bool) -> crate::Result<R> {
+        json::from_body(&mut self.fetch_body(url, use_cache)?.into_bytes())
+    }
+
+    /// Requests `url` using the current token and returns the response body.
+    /// If caching is enabled, the response is stored and a fresh cached one is returned
+    /// instead of making a request if `use_cache`. Cached responses are keyed by token-free URLs.
+    fn fetch_body(&self, url: Url, use_cache: bool) -> crate::Result<String> {
+        let url = without_token(&url);
+        let ttl = match self.cache_ttl {
+            Some(ttl) => ttl,
+            None => return Ok(self.shared.client.get(self.with_token(url))?.text()?),
+        };
+        if use_cache {
+            if let Some(body) = self.shared.cache.get(&url, ttl) {
+                return Ok(body);
+            }
+        }
+
+        let body = self.shared.client.get(self.with_token(url.clone()))?.text()?;
+        self.shared.cache.insert(url, body.clone(), ttl);
+        Ok(body)
+    }
+
+    fn with_token(&self, mut url: Url) -> Url {
+        url.query_pairs_mut().append_pair("access_token", self.shared.token.get());
+        url
+    }
+
+    /// Fails only if the permission is known to be missing, unknown ones are left to the API.
+    fn check_media_scope(&self) -> crate::Result<()> {
+        if self.shared.token.has_scope("user_media") == Some(false) {
+            Err(Box::new(MediaError::MissingMediaScope))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn media_params(&self) -> [(&str, String); 1] {
+        [("fields", self.media_fields.to_param())]
+    }
+}
+
+/// Parses a media item nested in a response of another node, e.g. of a mention.
+pub(crate) fn media_from_value(
+    value: serde_json::Value,
+    url_policy: UrlPolicy,
+    timezone: Timezone,
+) -> crate::Result<Media> {
+    let response = response::Media::deserialize(value)?;
+    Ok(Media::from(response, url_policy)?.with_timezone(timezone))
+}
+
+/// Page of a media container whose items haven't been parsed yet.
+pub(crate) struct RawPage {
+    /// Body of the container. Items borrow strings from it while they're parsed.
+    body: Vec<u8>,
+    /// URL to the next page. The token is removed from it and attached again when
+    /// the next page is requested, so the URL is safe to keep and a refreshed token
+    /// is picked up.
+    pub(crate) next: Option<Url>,
+}
+
+impl RawPage {
+    /// Reads paging of the media container `body`. Items are skipped without allocations
+    /// and parsed later by [into_media][RawPage::into_media], e.g. on another thread.
+    pub(crate) fn parse(body: String) -> crate::Result<Self> {
+        let body = body.into_bytes();
+        #[cfg(not(feature = "simd-json"))]
+        let paging = serde_json::from_slice::<response::PagingContainer>(&body)?.paging;
+        // The body is modified by simd-json, so a copy is parsed.
+        #[cfg(feature = "simd-json")]
+        let paging = json::from_body::<response::PagingContainer>(&mut body.clone())?.paging;
+        let next: Option<Url> = crate::parse_opt(paging.next)?;
+        Ok(Self { body, next: next.map(|url| without_token(&url)) })
+    }
+
+    /// Parses the items. Fails if the container can't be deserialized,
+    /// items that can't be converted are returned as errors.
+    pub(crate) fn into_media(
+        mut self,
+        url_policy: UrlPolicy,
+        timezone: Timezone,
+    ) -> crate::Result<Vec<crate::Result<Media>>> {
+        let container: response::ItemsContainer = json::from_body(&mut self.body)?;
+        Ok(container
+            .data
+            .into_iter()
+            .map(|response| Ok(Media::from(response, url_policy)?.with_timezone(timezone)))
+            .collect())
+    }
+}
+
+/// Returns `url` without the access token and its proof, which the API embeds
+/// into URLs of next pages.
+fn without_token(url: &Url) -> Url {
+    let mut stripped = url.clone();
+    stripped.set_query(None);
+    let pairs = url
+        .query_pairs()
+        .filter(|(key, _)| key != "access_token" && key != "appsecret_proof");
+    stripped.query_pairs_mut().extend_pairs(pairs);
+    if stripped.query() == Some("") {
+        stripped.set_query(None);
+    }
+    stripped
+}
+
+/// Parses pages on a separate thread while the next ones are retrieved, preserving the order
+/// of pages and their items (newest first). Stops at the first page that can't be retrieved
+/// or parsed, but items that can't be parsed are only reported.
+pub(crate) fn parse_pages<I>(
+    pages: I,
+    url_policy: UrlPolicy,
+    timezone: Timezone,
+) -> crate::Result<MediaSummary>
+where
+    I: Iterator<Item = crate::Result<RawPage>>,
+{
+    let (tx, rx) = mpsc::channel::<RawPage>();
+    // Errors are converted to strings to pass them between threads.
+    let parser = thread::spawn(move || -> Result<MediaSummary, String> {
+        let mut media = Vec::new();
+        let mut errors = Vec::new();
+        for mut page in rx {
+            let container: response::ItemsContainer =
+                json::from_body(&mut page.body).map_err(|e| e.to_string())?;
+            for response in container.data {
+                let id = response.id.to_string();
+                match Media::from(response, url_policy) {
+                    Ok(item) => media.push(item.with_timezone(timezone)),
+                    Err(e) => errors.push(ItemError { id, message: e.to_string() }),
+                }
+            }
         }
+        Ok(MediaSummary::new(media, errors))
+    });
 
-        pool.join();
-        match Arc::try_unwrap(media) {
-            Ok(mutex) => Ok(mutex.into_inner()?),
-            Err(_) => Err("failed to consume result".into()),
+    for page in pages {
+        match page {
+            Ok(page) => {
+                // The parser has failed, its error is returned below.
+                if tx.send(page).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                // Don't leave the parser running.
+                drop(tx);
+                parser.join().ok();
+                return Err(e);
+            }
         }
     }
 
-    fn media_params(&self) -> [(&str, &str); 2] {
-        [
-            ("access_token", self.token.get()),
-            (
-                "fields",
-                "caption,id,media_type,media_url,permalink,thumbnail_url,timestamp,username"
-            ),
-        ]
+    drop(tx);
+    match parser.join() {
+        Ok(summary) => summary.map_err(Into::into),
+        Err(_) => Err("media parser panicked".into()),
+    }
+}
+
+impl<T> Clone for Profile<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            url_policy: self.url_policy,
+            timezone: self.timezone,
+            media_fields: self.media_fields,
+            use_me_endpoint: self.use_me_endpoint,
+            cache_ttl: self.cache_ttl,
+        }
     }
 }
 
+/// Gathers media items of several profiles at once. Instead of finishing one profile before
+/// starting the next, a single page of each profile is fetched in turn, so all accounts make
+/// steady progress within rate limits. Album contents aren't gathered.
+///
+/// Returns results in the same order as `profiles`.
+pub fn media_round_robin<T: Token>(profiles: &[Profile<T>]) -> Vec<crate::Result<Vec<Media>>> {
+    media_weighted(profiles, &vec![1; profiles.len()])
+}
+
+/// Works like [media_round_robin], but profiles get turns in proportion to their `weights`,
+/// e.g. [media counts][Info::media_count], so accounts with larger backlogs are fetched
+/// more often. Turns of a profile are spread evenly between turns of the others.
+/// Zero weights are treated as ones.
+///
+/// # Panics
+/// Panics if the number of weights differs from the number of profiles.
+pub fn media_weighted<T: Token>(
+    profiles: &[Profile<T>],
+    weights: &[u32],
+) -> Vec<crate::Result<Vec<Media>>> {
+    assert_eq!(profiles.len(), weights.len(), "each profile must have a weight");
+    let mut results = Vec::with_capacity(profiles.len());
+    let mut next_urls = Vec::with_capacity(profiles.len());
+    let mut order = WeightedRoundRobin::new(weights);
+    for (i, profile) in profiles.iter().enumerate() {
+        match profile.media_url(&MediaQuery::default()) {
+            Ok(url) => {
+                results.push(Ok(Vec::new()));
+                next_urls.push(Some(url));
+            }
+            Err(e) => {
+                results.push(Err(e));
+                next_urls.push(None);
+                order.remove(i);
+            }
+        }
+    }
+
+    while let Some(i) = order.next() {
+        let profile = &profiles[i];
+        let url = match next_urls[i].take() {
+            Some(url) => url,
+            None => {
+                order.remove(i);
+                continue;
+            }
+        };
+
+        let page = profile.fetch_page(url).and_then(|mut page| {
+            let next = page.next.take();
+            let media = page
+                .into_media(profile.url_policy, profile.timezone)?
+                .into_iter()
+                .collect::<crate::Result<Vec<_>>>()?;
+            Ok((media, next))
+        });
+
+        match page {
+            Ok((media, next)) => {
+                if let Ok(result) = &mut results[i] {
+                    result.extend(media);
+                }
+                next_urls[i] = next;
+            }
+            Err(e) => results[i] = Err(e),
+        }
+    }
+    results
+}
+
 impl Info {
+    /// Returns the user ID reported by the API.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
     pub fn username(&self) -> &str {
         &self.username
     }
@@ -198,13 +745,9 @@ impl Info {
 
     fn from(response: response::Info) -> crate::Result<Self> {
         Ok(Self {
+            id: response.id.parse()?,
             username: response.username,
-            account_type: match response.account_type.as_str() {
-                "BUSINESS" => AccountType::Business,
-                "MEDIA_CREATOR" => AccountType::MediaCreator,
-                "PERSONAL" => AccountType::Personal,
-                _ => return Err("invalid account type".into()),
-            },
+            account_type: response.account_type.parse()?,
             media_count: response.media_count,
         })
     }
@@ -218,100 +761,779 @@ impl Media {
         self.media_type
     }
     /// Get media's owner username.
-    pub fn username(&self) -> &str {
-        &self.username
+    /// Returns `None` if [MediaField::Username] hasn't been requested.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
     }
-    /// Returns `None` if a Media inside an album.
+    /// Returns `None` if a Media inside an album or the field hasn't been requested.
     pub fn caption(&self) -> Option<&str> {
         self.caption.as_deref()
     }
-    /// Returns publish date.
-    pub fn timestamp(&self) -> &DateTime<FixedOffset> {
-        &self.timestamp
+    /// Returns the caption that hashtags and mentions can be extracted from.
+    pub fn parsed_caption(&self) -> Option<Caption<'_>> {
+        self.caption().map(Caption::new)
+    }
+    /// Returns hashtags of the caption without the `#` sign, see [Caption::hashtags].
+    pub fn hashtags(&self) -> Vec<&str> {
+        self.parsed_caption().map(|caption| caption.hashtags()).unwrap_or_default()
+    }
+    /// Returns usernames mentioned in the caption, see [Caption::mentions].
+    pub fn mentions(&self) -> Vec<&str> {
+        self.parsed_caption().map(|caption| caption.mentions()).unwrap_or_default()
+    }
+    /// Returns `None` if [MediaField::MediaProductType] hasn't been requested.
+    pub fn media_product_type(&self) -> Option<&MediaProductType> {
+        self.media_product_type.as_ref()
+    }
+    /// Returns publish date. Returns `None` if it can't be parsed,
+    /// use [raw_timestamp][Media::raw_timestamp] in this case.
+    pub fn timestamp(&self) -> Option<&DateTime<FixedOffset>> {
+        self.timestamp.as_ref()
+    }
+    /// Returns publish date in UTC.
+    pub fn timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        self.timestamp.map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+    /// Returns publish date in the local timezone.
+    pub fn timestamp_local(&self) -> Option<DateTime<Local>> {
+        self.timestamp.map(|timestamp| timestamp.with_timezone(&Local))
+    }
+    /// Returns publish date as reported by the API.
+    pub fn raw_timestamp(&self) -> &str {
+        &self.raw_timestamp
     }
 
     pub fn media_url(&self) -> &Url {
         &self.media_url
     }
     /// Get permanent URL. Returns `None` if an item contains copyrighted
-    /// material, or it has been flagged for a copyright violation.
+    /// material, or it has been flagged for a copyright violation, or if the field
+    /// hasn't been requested.
     pub fn permalink(&self) -> Option<&Url> {
         self.permalink.as_ref()
     }
-    /// Get thumbnail image URL. Only available for videos.
+    /// Returns the short code of the permalink, e.g. `CdJx1bHoq2x` of
+    /// `https://www.instagram.com/p/CdJx1bHoq2x/`.
+    pub fn shortcode(&self) -> Option<&str> {
+        self.permalink.as_ref()?.path_segments()?.rfind(|segment| !segment.is_empty())
+    }
+    /// Get thumbnail image URL. Only available for videos if the field has been requested.
     pub fn thumbnail_url(&self) -> Option<&Url> {
         self.thumbnail_url.as_ref()
     }
 
-    fn from(response: response::Media) -> crate::Result<Self> {
+    /// Returns the expiry date of the media URL signed by the CDN,
+    /// if it's stored in the `oe` parameter (hexadecimal Unix timestamp).
+    pub fn media_url_expiry(&self) -> Option<DateTime<Utc>> {
+        let (_, value) = self.media_url.query_pairs().find(|(key, _)| key == "oe")?;
+        let timestamp = i64::from_str_radix(&value, 16).ok()?;
+        Utc.timestamp_opt(timestamp, 0).single()
+    }
+
+    /// Returns the extension of the media file. It's taken from the URL path if it has one,
+    /// otherwise it's inferred from `content_type` (the `Content-Type` header of the file).
+    pub fn file_extension(&self, content_type: Option<&str>) -> Option<String> {
+        url_extension(&self.media_url, content_type)
+    }
+
+    /// Retrieves the size and the type of the media file using a HEAD request,
+    /// so they're known before downloading it.
+    pub fn head_metadata(&self, client: &Client) -> crate::Result<ContentInfo> {
+        let response = client.head(self.media_url.clone())?;
+        Ok(ContentInfo::from_headers(response.headers()))
+    }
+
+    /// Checks if the media URL has expired, so downloading it would fail.
+    /// Returns `false` if the expiry date is unknown.
+    /// Use [Profile::refresh_media_url] to get a fresh URL.
+    pub fn media_url_expired(&self) -> bool {
+        self.media_url_expiry().is_some_and(|expiry| expiry <= Utc::now())
+    }
+
+    /// Returns contents of the album if they have been embedded using [MediaField::Children].
+    pub fn embedded_children(&self) -> Option<&[Media]> {
+        self.children.as_deref()
+    }
+    /// Returns IDs of the album contents if they have been embedded.
+    pub fn children_ids(&self) -> Option<Vec<u64>> {
+        self.children.as_ref().map(|children| children.iter().map(Media::id).collect())
+    }
+
+    /// Gathers contents of the album using `profile`, see [Profile::album].
+    pub fn children<T: Token>(&self, profile: &Profile<T>) -> crate::Result<Vec<Media>> {
+        profile.album(self)
+    }
+
+    /// Returns violations of the [lenient][UrlPolicy::Lenient] URL policy found during parsing.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Converts publish dates of the item and its embedded children to `timezone`.
+    fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timestamp = self.timestamp.map(|timestamp| match timezone {
+            Timezone::Original => timestamp,
+            Timezone::Utc => timestamp.with_timezone(&Utc).into(),
+            Timezone::Local => timestamp.with_timezone(&Local).into(),
+        });
+        self.children = self.children.map(|children| {
+            children.into_iter().map(|child| child.with_timezone(timezone)).collect()
+        });
+        self
+    }
+
+    fn from(response: response::Media<'_>, url_policy: UrlPolicy) -> crate::Result<Self> {
+        let mut warnings = Vec::new();
+        let media_url = url_policy.parse(&response.media_url, &mut warnings)?;
+        let permalink = url_policy.parse_opt(response.permalink.as_deref(), &mut warnings)?;
+        let thumbnail_url =
+            url_policy.parse_opt(response.thumbnail_url.as_deref(), &mut warnings)?;
+
         Ok(Self {
             id: response.id.parse()?,
-            media_type: match response.media_type.as_str() {
-                "IMAGE" => MediaType::Image,
-                "VIDEO" => MediaType::Video,
-                "CAROUSEL_ALBUM" => MediaType::CarouselAlbum,
-                _ => return Err("invalid media type".into()),
-            },
+            media_type: response.media_type.parse()?,
             username: response.username,
             caption: response.caption,
-            // parse_from_rfc3339 isn't working here.
-            timestamp: DateTime::parse_from_str(&response.timestamp, "%FT%T%z")?,
+            children: match response.children {
+                Some(children) => Some(
+                    children
+                        .data
+                        .into_iter()
+                        .map(|response| Media::from(response, url_policy))
+                        .collect::<crate::Result<_>>()?,
+                ),
+                None => None,
+            },
+            media_product_type: response.media_product_type.as_deref().map(str::parse).transpose()?,
+            timestamp: parse_timestamp(&response.timestamp, &mut warnings),
+            raw_timestamp: response.timestamp.into_owned(),
+
+            media_url,
+            permalink,
+            thumbnail_url,
 
-            media_url: response.media_url.parse()?,
-            permalink: crate::parse_opt(response.permalink)?,
-            thumbnail_url: crate::parse_opt(response.thumbnail_url)?,
+            warnings,
         })
     }
 }
 
+/// Returns the extension of the file at `url`, see [Media::file_extension].
+pub(crate) fn url_extension(url: &Url, content_type: Option<&str>) -> Option<String> {
+    let from_path = Path::new(url.path())
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| {
+            extension.len() <= 5 && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    match from_path {
+        Some(extension) => Some(extension.to_ascii_lowercase()),
+        None => content_type.and_then(extension_from_mime).map(str::to_string),
+    }
+}
+
+/// Parses a timestamp in either the RFC 3339 format or the compact one that used by the API
+/// (`2022-05-01T12:00:00+0000`). Failures are pushed to `warnings`.
+fn parse_timestamp(timestamp: &str, warnings: &mut Vec<String>) -> Option<DateTime<FixedOffset>> {
+    const COMPACT_FORMATS: [&str; 2] = ["%FT%T%z", "%FT%T%.f%z"];
+
+    let parsed = DateTime::parse_from_rfc3339(timestamp).ok().or_else(|| {
+        COMPACT_FORMATS
+            .iter()
+            .find_map(|format| DateTime::parse_from_str(timestamp, format).ok())
+    });
+    if parsed.is_none() {
+        warnings.push(format!("timestamp {} can't be parsed", timestamp));
+    }
+    parsed
+}
+
+impl AccountType {
+    /// Returns the value used by the API, like `MEDIA_CREATOR`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Business => "BUSINESS",
+            Self::MediaCreator => "MEDIA_CREATOR",
+            Self::Personal => "PERSONAL",
+        }
+    }
+}
+
+impl FromStr for AccountType {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BUSINESS" => Ok(Self::Business),
+            "MEDIA_CREATOR" => Ok(Self::MediaCreator),
+            "PERSONAL" => Ok(Self::Personal),
+            _ => Err(ParseTypeError { type_name: "account type", value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for AccountType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl MediaType {
+    /// Returns the value used by the API, like `CAROUSEL_ALBUM`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Image => "IMAGE",
+            Self::Video => "VIDEO",
+            Self::CarouselAlbum => "CAROUSEL_ALBUM",
+        }
+    }
+}
+
+impl FromStr for MediaType {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "IMAGE" => Ok(Self::Image),
+            "VIDEO" => Ok(Self::Video),
+            "CAROUSEL_ALBUM" => Ok(Self::CarouselAlbum),
+            _ => Err(ParseTypeError { type_name: "media type", value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl MediaProductType {
+    /// Returns the value used by the API, like `REELS`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Ad => "AD",
+            Self::Feed => "FEED",
+            Self::Story => "STORY",
+            Self::Reels => "REELS",
+            Self::Unknown(value) => value,
+        }
+    }
+}
+
+impl FromStr for MediaProductType {
+    type Err = ParseTypeError;
+
+    /// Parses the value used by the API. Unrecognized values are parsed
+    /// as [Unknown][MediaProductType::Unknown], so new surfaces don't break parsing.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "AD" => Self::Ad,
+            "FEED" => Self::Feed,
+            "STORY" => Self::Story,
+            "REELS" => Self::Reels,
+            _ => Self::Unknown(s.to_string()),
+        })
+    }
+}
+
+impl From<String> for MediaProductType {
+    fn from(value: String) -> Self {
+        match value.parse() {
+            Ok(Self::Unknown(_)) | Err(_) => Self::Unknown(value),
+            Ok(known) => known,
+        }
+    }
+}
+
+impl From<MediaProductType> for String {
+    fn from(product_type: MediaProductType) -> Self {
+        match product_type {
+            MediaProductType::Unknown(value) => value,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for MediaProductType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl UrlPolicy {
+    /// Parses and validates `url`. Violations of the lenient policy are pushed to `warnings`.
+    ///
+    /// # Panics
+    /// If `format!` panics.
+    fn parse(self, url: &str, warnings: &mut Vec<String>) -> crate::Result<Url> {
+        let url = match (self, url.strip_prefix("//")) {
+            (Self::Lenient, Some(relative)) => Url::parse(&format!("https://{}", relative))?,
+            _ => Url::parse(url)?,
+        };
+
+        let violation = if url.scheme() != "https" {
+            format!("URL {} doesn't use https", url)
+        } else if !url.has_host() {
+            format!("URL {} has no host", url)
+        } else {
+            return Ok(url);
+        };
+
+        match self {
+            Self::Strict => Err(violation.into()),
+            Self::Lenient => {
+                warnings.push(violation);
+                Ok(url)
+            }
+        }
+    }
+
+    /// Works like [parse][UrlPolicy::parse], but in the lenient mode
+    /// a malformed URL is reported as a warning and omitted.
+    fn parse_opt(
+        self,
+        url: Option<&str>,
+        warnings: &mut Vec<String>
+    ) -> crate::Result<Option<Url>> {
+        let url = match url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        match self.parse(url, warnings) {
+            Ok(url) => Ok(Some(url)),
+            Err(e) if self == Self::Lenient => {
+                warnings.push(format!("URL {} is omitted: {}", url, e));
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Constructs an image with the given ID. Used by tests of other modules.
+    pub(crate) fn media(id: u64) -> Media {
+        let mut response = default_media_response();
+        response.id = id.to_string().into();
+        Media::from(response, UrlPolicy::Strict).unwrap()
+    }
+
+    /// Constructs a video with a thumbnail.
+    pub(crate) fn video(id: u64) -> Media {
+        let mut response = default_media_response();
+        response.id = id.to_string().into();
+        response.media_type = "VIDEO".into();
+        response.thumbnail_url = Some("https://example.com/thumbnail.jpg".into());
+        Media::from(response, UrlPolicy::Strict).unwrap()
+    }
+
+    /// Constructs an image whose publish date can't be parsed.
+    pub(crate) fn undated(id: u64) -> Media {
+        let mut response = default_media_response();
+        response.id = id.to_string().into();
+        response.timestamp = "".into();
+        Media::from(response, UrlPolicy::Strict).unwrap()
+    }
+
+    /// Constructs an image with the given ID and caption.
+    pub(crate) fn captioned(id: u64, caption: &str) -> Media {
+        let mut response = default_media_response();
+        response.id = id.to_string().into();
+        response.caption = Some(caption.to_string());
+        Media::from(response, UrlPolicy::Strict).unwrap()
+    }
+
+    /// Constructs a profile without the `user_media` permission.
+    pub(crate) fn app_profile() -> Profile<crate::auth::AppToken> {
+        let token = crate::auth::AppToken::from_secrets(&crate::auth::Secrets {
+            app_id: 1,
+            app_secret: "secret",
+            oauth_uri: Url::parse("https://example.com").unwrap(),
+        });
+        Profile::with_client(token, Client::new().unwrap())
+    }
+
+    #[test]
+    fn share_profile() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Profile<crate::auth::LongLivedToken>>();
+
+        let profile = app_profile();
+        let clone = profile.clone();
+        assert!(Arc::ptr_eq(&profile.shared, &clone.shared));
+        assert_eq!(std::thread::spawn(move || clone.id()).join().unwrap(), None);
+    }
+
+    #[test]
+    fn media_iter_without_scope() {
+        let mut iter = app_profile().media_iter();
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        assert!(app_profile().media_recent(0).unwrap().is_empty());
+        assert!(app_profile().media_recent(5).is_err());
+        assert!(app_profile().media_by_id(1).is_err());
+    }
+
+    #[test]
+    fn children_of_image() {
+        let error = media(1).children(&app_profile()).err().unwrap();
+        assert_eq!(error.downcast_ref(), Some(&MediaError::NotAnAlbum(1)));
+    }
+
+    #[test]
+    fn preserve_media_order() {
+        let pages = (0..50).map(|page| RawPage::parse(page_body((0..20).map(|item| {
+            (page * 20 + item, "IMAGE")
+        }))));
+        let ids: Vec<_> = parse_pages(pages, UrlPolicy::Strict, Timezone::Original)
+            .unwrap()
+            .media()
+            .iter()
+            .map(Media::id)
+            .collect();
+        assert_eq!(ids, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn skip_malformed_media() {
+        let page = RawPage::parse(page_body(vec![(0, "IMAGE"), (1, "UNKNOWN"), (2, "VIDEO")]));
+        let summary =
+            parse_pages(vec![page].into_iter(), UrlPolicy::Strict, Timezone::Original).unwrap();
+        assert!(!summary.is_complete());
+        assert_eq!(summary.media().iter().map(Media::id).collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(summary.errors()[0].id(), "1");
+        assert!(summary.into_result().is_err());
+
+        assert!(RawPage::parse("{}".to_string()).is_err());
+        // Items are parsed after the next page is retrieved.
+        let pages = vec![RawPage::parse(r#"{"data": 1, "paging": {}}"#.to_string())];
+        assert!(pages[0].is_ok());
+        assert!(parse_pages(pages.into_iter(), UrlPolicy::Strict, Timezone::Original).is_err());
+    }
+
+    /// Compares deserialization of a large fixture page into the borrowed response structs
+    /// with deserialization into owned strings. Run using
+    /// `cargo test --release parse_large_page -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn parse_large_page() {
+        /// The response struct before strings were borrowed.
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct OwnedMedia {
+            caption: Option<String>,
+            id: String,
+            media_type: String,
+            media_url: String,
+            permalink: Option<String>,
+            timestamp: String,
+            username: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        struct OwnedContainer {
+            data: Vec<OwnedMedia>,
+        }
+
+        const ITEMS: u64 = 5000;
+        const ROUNDS: u32 = 20;
+        let body = page_body((0..ITEMS).map(|id| (id, "IMAGE")));
+        let time = |parse: &dyn Fn(&mut [u8]) -> usize| {
+            let mut body = body.clone().into_bytes();
+            let started = std::time::Instant::now();
+            assert_eq!(parse(&mut body) as u64, ITEMS);
+            started.elapsed()
+        };
+
+        // Rounds alternate and the fastest ones are taken to reduce noise.
+        let (mut owned, mut borrowed) = (Duration::MAX, Duration::MAX);
+        for _ in 0..ROUNDS {
+            owned = owned.min(time(&|body| {
+                json::from_body::<OwnedContainer>(body).unwrap().data.len()
+            }));
+            borrowed = borrowed.min(time(&|body| {
+                json::from_body::<response::ItemsContainer>(body).unwrap().data.len()
+            }));
+        }
+        println!("{} items: owned {:?}, borrowed {:?}", ITEMS, owned, borrowed);
+    }
+
+    #[test]
+    fn arrange_media() {
+        let page = RawPage::parse(page_body(vec![(0, "IMAGE"), (1, "VIDEO"), (2, "IMAGE")]));
+        let summary =
+            parse_pages(vec![page].into_iter(), UrlPolicy::Strict, Timezone::Original).unwrap();
+
+        let query = MediaQuery {
+            media_type: Some(MediaType::Image),
+            order: MediaOrder::OldestFirst,
+            ..MediaQuery::default()
+        };
+        let ids: Vec<_> = summary.arrange(&query).media().iter().map(Media::id).collect();
+        assert_eq!(ids, [2, 0]);
+    }
+
     #[test]
     fn into_info() {
-        assert!(Info::from(default_info_response()).is_ok());
+        assert_eq!(Info::from(default_info_response()).unwrap().id(), 1);
+    }
+
+    #[test]
+    fn cached_info() {
+        let mut profile = app_profile();
+        profile.set_cache_ttl(Some(Duration::from_secs(60)));
+        let response = serde_json::json!({
+            "id": "1",
+            "account_type": "PERSONAL",
+            "media_count": 5,
+            "username": "jane",
+        }).to_string();
+        let ttl = Duration::from_secs(60);
+        profile.shared.cache.insert(profile.info_url().unwrap(), response, ttl);
+        // The clone shares the cache, so no request is made.
+        assert_eq!(profile.clone().info().unwrap().username(), "jane");
+    }
+
+    #[test]
+    fn strip_token() {
+        let url = Url::parse(concat!(
+            "https://graph.instagram.com/1/media",
+            "?access_token=IGQV&fields=id&appsecret_proof=ab&after=QVFI",
+        )).unwrap();
+        assert_eq!(without_token(&url).query(), Some("fields=id&after=QVFI"));
+
+        let url = Url::parse("https://graph.instagram.com/me?access_token=IGQV").unwrap();
+        assert_eq!(without_token(&url).as_str(), "https://graph.instagram.com/me");
+    }
+
+    #[test]
+    fn user_node() {
+        let json = r#"{"access_token":"","user_id":1,"expiration_date":0}"#;
+        let token: crate::auth::LongLivedToken = serde_json::from_str(json).unwrap();
+        let mut profile = Profile::with_client(token, Client::new().unwrap());
+        assert_eq!(profile.user_node(), "1");
+        profile.set_use_me_endpoint(true);
+        assert_eq!(profile.user_node(), "me");
+
+        // App tokens aren't linked to a user.
+        assert_eq!(app_profile().user_node(), "me");
     }
 
     #[test]
-    #[should_panic(expected = "invalid account type")]
     fn into_invalid_info() {
         let mut response = default_info_response();
-        response.account_type = "UNKNOWN".to_string();
-        Info::from(response).unwrap();
+        response.account_type = "UNKNOWN".into();
+        let error = Info::from(response).err().unwrap();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ParseTypeError { type_name: "account type", value: "UNKNOWN".to_string() }),
+        );
+    }
+
+    #[test]
+    fn enum_strings() {
+        for media_type in [MediaType::Image, MediaType::Video, MediaType::CarouselAlbum] {
+            assert_eq!(media_type.to_string().parse::<MediaType>(), Ok(media_type));
+        }
+        assert_eq!(AccountType::MediaCreator.to_string(), "MEDIA_CREATOR");
+        assert_eq!(
+            serde_json::to_string(&AccountType::MediaCreator).unwrap(),
+            "\"MEDIA_CREATOR\""
+        );
+        assert_eq!(
+            "image".parse::<MediaType>().unwrap_err().to_string(),
+            "invalid media type: \"image\""
+        );
     }
 
     #[test]
     fn into_media() {
-        assert!(Media::from(default_media_response()).is_ok());
+        assert!(Media::from(default_media_response(), UrlPolicy::Strict).is_ok());
+    }
+
+    #[test]
+    fn caption_entities() {
+        let item = captioned(1, "Hi @jane.doe #sea");
+        assert_eq!(item.hashtags(), ["sea"]);
+        assert_eq!(item.mentions(), ["jane.doe"]);
+        assert!(media(2).hashtags().is_empty());
+    }
+
+    #[test]
+    fn media_url_expiry() {
+        let mut response = default_media_response();
+        response.media_url = "https://cdninstagram.com/v/1_n.jpg?oh=00_AT&oe=626B4F00".into();
+        let expiring = Media::from(response, UrlPolicy::Strict).unwrap();
+        assert_eq!(expiring.media_url_expiry(), Utc.timestamp_opt(0x626B4F00, 0).single());
+        assert!(expiring.media_url_expired());
+
+        assert_eq!(media(1).media_url_expiry(), None);
+        assert!(!media(1).media_url_expired());
+    }
+
+    #[test]
+    fn file_extension() {
+        assert_eq!(media(1).file_extension(Some("image/png")), Some("png".to_string()));
+
+        let mut response = default_media_response();
+        response.media_url = "https://cdninstagram.com/v/1_n.JPG?ccb=1-7".into();
+        let media = Media::from(response, UrlPolicy::Strict).unwrap();
+        assert_eq!(media.file_extension(Some("image/png")), Some("jpg".to_string()));
+    }
+
+    #[test]
+    fn embedded_children() {
+        let body = serde_json::json!({
+            "id": "1",
+            "media_type": "CAROUSEL_ALBUM",
+            "media_url": "https://example.com",
+            "timestamp": "1970-01-01T00:00:00+0000",
+            "children": {"data": [{
+                "id": "2",
+                "media_type": "IMAGE",
+                "media_url": "https://example.com",
+                "timestamp": "1970-01-01T00:00:00+0000",
+            }]},
+        }).to_string();
+        let response: response::Media = serde_json::from_str(&body).unwrap();
+
+        let media = Media::from(response, UrlPolicy::Strict).unwrap();
+        assert_eq!(media.children_ids(), Some(vec![2]));
+        assert!(media.embedded_children().unwrap()[0].embedded_children().is_none());
+    }
+
+    #[test]
+    fn parse_timestamp() {
+        let mut warnings = Vec::new();
+        for timestamp in [
+            "2022-05-01T12:00:00+0000",
+            "2022-05-01T12:00:00+00:00",
+            "2022-05-01T12:00:00.123+0000",
+            "2022-05-01T12:00:00Z",
+        ] {
+            let parsed = super::parse_timestamp(timestamp, &mut warnings);
+            assert_eq!(parsed.unwrap().timestamp(), 1651406400, "{}", timestamp);
+        }
+        assert!(warnings.is_empty());
+
+        let mut response = default_media_response();
+        response.timestamp = "yesterday".into();
+        let media = Media::from(response, UrlPolicy::Strict).unwrap();
+        assert!(media.timestamp().is_none());
+        assert_eq!(media.raw_timestamp(), "yesterday");
+        assert_eq!(media.warnings().len(), 1);
+    }
+
+    #[test]
+    fn convert_timezone() {
+        let mut response = default_media_response();
+        response.timestamp = "2022-05-01T12:00:00+0300".into();
+        let media = Media::from(response, UrlPolicy::Strict).unwrap();
+        assert_eq!(media.timestamp().unwrap().offset().local_minus_utc(), 3 * 60 * 60);
+
+        let media = media.with_timezone(Timezone::Utc);
+        assert_eq!(media.timestamp().unwrap().offset().local_minus_utc(), 0);
+        assert_eq!(media.timestamp_utc().unwrap().timestamp(), 1651395600);
+    }
+
+    #[test]
+    fn media_product_type() {
+        let mut response = default_media_response();
+        response.media_product_type = Some("REELS".into());
+        let media = Media::from(response, UrlPolicy::Strict).unwrap();
+        assert_eq!(media.media_product_type(), Some(&MediaProductType::Reels));
+
+        let mut response = default_media_response();
+        response.media_product_type = Some("LIVE".into());
+        let media = Media::from(response, UrlPolicy::Strict).unwrap();
+        let live = MediaProductType::Unknown("LIVE".to_string());
+        assert_eq!(media.media_product_type(), Some(&live));
+
+        let product_type: MediaProductType = serde_json::from_str(r#""LIVE""#).unwrap();
+        assert_eq!(product_type, live);
+        assert_eq!(serde_json::to_string(&product_type).unwrap(), r#""LIVE""#);
+        assert_eq!(serde_json::to_string(&MediaProductType::Reels).unwrap(), r#""REELS""#);
+    }
+
+    #[test]
+    fn lenient_url_policy() {
+        let mut response = default_media_response();
+        response.permalink = Some("//www.instagram.com/p/0".into());
+        response.thumbnail_url = Some("not a URL".into());
+
+        let media = Media::from(response, UrlPolicy::Lenient).unwrap();
+        assert_eq!(media.permalink().unwrap().scheme(), "https");
+        assert_eq!(media.shortcode(), Some("0"));
+        assert!(media.thumbnail_url().is_none());
+        assert_eq!(media.warnings().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't use https")]
+    fn strict_url_policy() {
+        let mut response = default_media_response();
+        response.media_url = "http://example.com".into();
+        Media::from(response, UrlPolicy::Strict).unwrap();
     }
 
     #[test]
-    #[should_panic(expected = "invalid media type")]
     fn into_invalid_media() {
         let mut response = default_media_response();
-        response.media_type = "UNKNOWN".to_string();
-        Media::from(response).unwrap();
+        response.media_type = "UNKNOWN".into();
+        let error = Media::from(response, UrlPolicy::Strict).err().unwrap();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ParseTypeError { type_name: "media type", value: "UNKNOWN".to_string() }),
+        );
     }
 
     fn default_info_response() -> response::Info {
         response::Info {
+            id: "1".to_string(),
             account_type: "BUSINESS".to_string(),
             media_count: 0,
             username: String::new(),
         }
     }
 
-    fn default_media_response() -> response::Media {
+    /// Returns a media container that consists of items with the given IDs and media types.
+    fn page_body<I: IntoIterator<Item = (u64, &'static str)>>(items: I) -> String {
+        let data: Vec<_> = items
+            .into_iter()
+            .map(|(id, media_type)| {
+                serde_json::json!({
+                    "id": id.to_string(),
+                    "media_type": media_type,
+                    "media_url": format!("https://cdninstagram.com/v/{}_n.jpg?ccb=1-7", id),
+                    "permalink": format!("https://www.instagram.com/p/{}/", id),
+                    "timestamp": "2022-05-01T12:00:00+0000",
+                    "username": "jane.doe",
+                    "caption": "Sunset #travel",
+                })
+            })
+            .collect();
+        serde_json::json!({"data": data, "paging": {}}).to_string()
+    }
+
+    fn default_media_response() -> response::Media<'static> {
         response::Media {
             caption: None,
-            id: '0'.to_string(),
-            media_type: "IMAGE".to_string(),
-            media_url: "test:".to_string(),
+            id: "0".into(),
+            media_type: "IMAGE".into(),
+            media_url: "https://example.com".into(),
             permalink: None,
             thumbnail_url: None,
-            timestamp: "1970-01-01T00:00:00+0000".to_string(),
-            username: String::new(),
+            timestamp: "1970-01-01T00:00:00+0000".into(),
+            username: None,
+            media_product_type: None,
+            children: None,
         }
     }
 }
@@ -0,0 +1,59 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! In-memory cache of API responses.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+/// Stores bodies of responses by request URLs. Shared between clones of a profile.
+/// Responses are stored before parsing, so profile settings are applied to cached ones too.
+#[derive(Default)]
+pub(super) struct ResponseCache {
+    entries: Mutex<HashMap<Url, (Instant, String)>>,
+}
+
+impl ResponseCache {
+    /// Returns the response to `url` if it has been stored less than `ttl` ago.
+    pub(super) fn get(&self, url: &Url, ttl: Duration) -> Option<String> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(url) {
+            Some((stored, body)) if stored.elapsed() < ttl => Some(body.clone()),
+            _ => None,
+        }
+    }
+
+    /// Stores the response to `url`, forgetting the ones that are older than `ttl`.
+    pub(super) fn insert(&self, url: Url, body: String, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (stored, _)| stored.elapsed() < ttl);
+        entries.insert(url, (Instant::now(), body));
+    }
+
+    pub(super) fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expire() {
+        let cache = ResponseCache::default();
+        let url = Url::parse("https://example.com/me").unwrap();
+        cache.insert(url.clone(), "{}".to_string(), Duration::from_secs(60));
+        assert_eq!(cache.get(&url, Duration::from_secs(60)).as_deref(), Some("{}"));
+        assert_eq!(cache.get(&url, Duration::from_secs(0)), None);
+
+        cache.clear();
+        assert_eq!(cache.get(&url, Duration::from_secs(60)), None);
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Extraction of hashtags and mentions from captions.
+
+/// Caption of a media item.
+///
+/// # Examples
+/// ```
+/// use instapi::user::Caption;
+///
+/// let caption = Caption::new("Sunset with @jane.doe #travel #sea_view #travel");
+/// assert_eq!(caption.hashtags(), ["travel", "sea_view"]);
+/// assert_eq!(caption.mentions(), ["jane.doe"]);
+/// ```
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Caption<'a> {
+    text: &'a str,
+}
+
+impl<'a> Caption<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text }
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns hashtags without the `#` sign in order of appearance, without duplicates.
+    pub fn hashtags(&self) -> Vec<&'a str> {
+        self.tokens('#', |c| c.is_alphanumeric() || c == '_')
+    }
+
+    /// Returns mentioned usernames without the `@` sign in order of appearance,
+    /// without duplicates.
+    pub fn mentions(&self) -> Vec<&'a str> {
+        self.tokens('@', |c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    }
+
+    /// Finds words that start with `prefix` and consist of characters accepted by `is_part`.
+    /// A prefix that follows a word character (like in an email address) is ignored.
+    fn tokens<F: Fn(char) -> bool>(&self, prefix: char, is_part: F) -> Vec<&'a str> {
+        let mut tokens = Vec::new();
+        let mut previous = None;
+        for (start, c) in self.text.char_indices() {
+            let follows_word = previous.is_some_and(|p: char| p.is_alphanumeric() || p == '_');
+            previous = Some(c);
+            if c != prefix || follows_word {
+                continue;
+            }
+
+            let rest = &self.text[start + c.len_utf8()..];
+            let end = rest.find(|c| !is_part(c)).unwrap_or(rest.len());
+            // Usernames can't end with a period, it's a punctuation mark then.
+            let token = rest[..end].trim_end_matches('.');
+            if !token.is_empty() && !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract() {
+        let caption = Caption::new("#first Привет, @user_1. Mail me: a@b.com #тег#next @");
+        assert_eq!(caption.hashtags(), ["first", "тег"]);
+        assert_eq!(caption.mentions(), ["user_1"]);
+    }
+}
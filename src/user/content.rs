@@ -0,0 +1,92 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Metadata of media files that retrieved without downloading them.
+
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE};
+
+/// Size and type of a media file reported by the CDN.
+/// Returned by [Media::head_metadata][super::Media::head_metadata].
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ContentInfo {
+    length: Option<u64>,
+    mime: Option<String>,
+}
+
+impl ContentInfo {
+    pub(super) fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        Self {
+            length: header(CONTENT_LENGTH).and_then(|length| length.parse().ok()),
+            mime: header(CONTENT_TYPE).and_then(essence),
+        }
+    }
+
+    /// Returns the file size in bytes.
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    /// Returns the MIME type without parameters, like `image/jpeg`.
+    pub fn mime(&self) -> Option<&str> {
+        self.mime.as_deref()
+    }
+
+    /// Returns the usual file extension of the MIME type, like `jpg`.
+    pub fn extension(&self) -> Option<&'static str> {
+        extension_from_mime(self.mime()?)
+    }
+}
+
+/// Maps the value of a `Content-Type` header to the usual file extension, like `jpg`.
+/// Returns `None` for types that media files don't have.
+///
+/// # Examples
+/// ```
+/// use instapi::user::extension_from_mime;
+///
+/// assert_eq!(extension_from_mime("image/jpeg"), Some("jpg"));
+/// assert_eq!(extension_from_mime("video/MP4; codecs=avc1"), Some("mp4"));
+/// assert_eq!(extension_from_mime("text/html"), None);
+/// ```
+pub fn extension_from_mime(content_type: &str) -> Option<&'static str> {
+    Some(match essence(content_type)?.as_str() {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/webp" => "webp",
+        "image/heic" => "heic",
+        "video/mp4" => "mp4",
+        "video/quicktime" => "mov",
+        _ => return None,
+    })
+}
+
+/// Returns the MIME type without parameters like `charset` in lower case.
+fn essence(content_type: &str) -> Option<String> {
+    let mime = content_type.split(';').next()?.trim();
+    if mime.is_empty() {
+        None
+    } else {
+        Some(mime.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, "1024".parse().unwrap());
+        headers.insert(CONTENT_TYPE, "Video/MP4; codecs=avc1".parse().unwrap());
+        let info = ContentInfo::from_headers(&headers);
+        assert_eq!(info.length(), Some(1024));
+        assert_eq!(info.mime(), Some("video/mp4"));
+        assert_eq!(info.extension(), Some("mp4"));
+
+        let info = ContentInfo::from_headers(&HeaderMap::new());
+        assert_eq!((info.length(), info.mime(), info.extension()), (None, None, None));
+    }
+}
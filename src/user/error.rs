@@ -0,0 +1,70 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Errors of media requests and of parsing values returned by the API.
+
+use std::fmt;
+
+/// Describes why a media request can't be performed. Detected before sending the request.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum MediaError {
+    /// Album contents are requested for an item that isn't an album. Contains the item ID.
+    NotAnAlbum(u64),
+    /// The `user_media` permission hasn't been granted.
+    MissingMediaScope,
+}
+
+impl fmt::Display for MediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnAlbum(id) => write!(f, "media {} isn't an album", id),
+            Self::MissingMediaScope => f.write_str("user_media permission hasn't been granted"),
+        }
+    }
+}
+
+impl std::error::Error for MediaError {}
+
+/// Returned when a string doesn't match any API value of an enumeration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseTypeError {
+    /// Human-readable name of the enumeration, like `media type`.
+    pub(crate) type_name: &'static str,
+    pub(crate) value: String,
+}
+
+impl fmt::Display for ParseTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: \"{}\"", self.type_name, self.value)
+    }
+}
+
+impl std::error::Error for ParseTypeError {}
+
+/// Describes a media item that has been skipped, because it couldn't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemError {
+    pub(super) id: String,
+    pub(super) message: String,
+}
+
+impl ItemError {
+    /// Returns the item ID as reported by the API.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "media {}: {}", self.id, self.message)
+    }
+}
+
+impl std::error::Error for ItemError {}
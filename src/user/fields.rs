@@ -0,0 +1,201 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Selection of fields that requested for media items.
+
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+/// Field of a media item that can be requested.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum MediaField {
+    Caption,
+    Id,
+    MediaType,
+    MediaUrl,
+    Permalink,
+    ThumbnailUrl,
+    Timestamp,
+    Username,
+    /// Surface where an item has been published. Isn't available in the Basic Display API,
+    /// so it isn't requested by default.
+    MediaProductType,
+    /// Contents of albums, embedded into the parent response with the other requested fields.
+    /// It saves a request per album, but isn't requested by default.
+    Children,
+}
+
+/// Set of fields requested by [Profile][super::Profile].
+/// Fields that aren't requested are reported as `None` by [Media][super::Media].
+///
+/// # Examples
+/// Don't request captions and owner usernames:
+/// ```
+/// use instapi::user::{MediaField, MediaFields};
+///
+/// let fields = MediaFields::required().with(MediaField::Permalink);
+/// assert!(!fields.contains(MediaField::Caption));
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct MediaFields {
+    /// Bit mask indexed by [MediaField] discriminants.
+    bits: u16,
+}
+
+impl MediaField {
+    const ALL: [Self; 10] = [
+        Self::Caption,
+        Self::Id,
+        Self::MediaType,
+        Self::MediaUrl,
+        Self::Permalink,
+        Self::ThumbnailUrl,
+        Self::Timestamp,
+        Self::Username,
+        Self::MediaProductType,
+        Self::Children,
+    ];
+
+    /// Fields that are always requested, as a media item can't be constructed without them.
+    const REQUIRED: [Self; 4] = [Self::Id, Self::MediaType, Self::MediaUrl, Self::Timestamp];
+
+    /// Returns the name used by the API.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Caption => "caption",
+            Self::Id => "id",
+            Self::MediaType => "media_type",
+            Self::MediaUrl => "media_url",
+            Self::Permalink => "permalink",
+            Self::ThumbnailUrl => "thumbnail_url",
+            Self::Timestamp => "timestamp",
+            Self::Username => "username",
+            Self::MediaProductType => "media_product_type",
+            Self::Children => "children",
+        }
+    }
+
+    /// Returns the field by its [name][MediaField::name].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|field| field.name() == name)
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+impl MediaFields {
+    /// Contains only the fields that are always requested:
+    /// ID, media type, media URL and timestamp.
+    pub fn required() -> Self {
+        MediaField::REQUIRED.iter().fold(Self { bits: 0 }, |fields, field| fields.with(*field))
+    }
+
+    /// Contains all fields.
+    pub fn all() -> Self {
+        MediaField::ALL.iter().fold(Self { bits: 0 }, |fields, field| fields.with(*field))
+    }
+
+    /// Adds `field` to the set.
+    pub fn with(self, field: MediaField) -> Self {
+        Self { bits: self.bits | field.bit() }
+    }
+
+    /// Removes `field` from the set. Required fields can't be removed.
+    pub fn without(self, field: MediaField) -> Self {
+        if MediaField::REQUIRED.contains(&field) {
+            self
+        } else {
+            Self { bits: self.bits & !field.bit() }
+        }
+    }
+
+    pub fn contains(self, field: MediaField) -> bool {
+        self.bits & field.bit() != 0
+    }
+
+    /// Returns the value of the `fields` parameter.
+    /// Children are requested using the field expansion: `children{<other fields>}`.
+    pub(crate) fn to_param(self) -> String {
+        let fields = self.without(MediaField::Children);
+        let param = MediaField::ALL
+            .iter()
+            .filter(|field| fields.contains(**field))
+            .map(|field| field.name())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        if self.contains(MediaField::Children) {
+            format!("{},{}{{{}}}", param, MediaField::Children.name(), param)
+        } else {
+            param
+        }
+    }
+}
+
+/// Fields are serialized as a list of their names. Required fields are added
+/// when deserializing, even if they're omitted.
+impl TryFrom<Vec<String>> for MediaFields {
+    type Error = String;
+
+    fn try_from(names: Vec<String>) -> Result<Self, Self::Error> {
+        names.iter().try_fold(Self::required(), |fields, name| {
+            let field = MediaField::from_name(name).ok_or(format!("unknown field \"{}\"", name))?;
+            Ok(fields.with(field))
+        })
+    }
+}
+
+impl From<MediaFields> for Vec<String> {
+    fn from(fields: MediaFields) -> Self {
+        MediaField::ALL
+            .iter()
+            .filter(|field| fields.contains(**field))
+            .map(|field| field.name().to_string())
+            .collect()
+    }
+}
+
+/// Contains all fields that supported by the Basic Display API, except for embedded children.
+/// Used by [Profile][super::Profile].
+impl Default for MediaFields {
+    fn default() -> Self {
+        Self::all().without(MediaField::MediaProductType).without(MediaField::Children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_param() {
+        assert_eq!(
+            MediaFields::default().to_param(),
+            "caption,id,media_type,media_url,permalink,thumbnail_url,timestamp,username"
+        );
+        assert_eq!(
+            MediaFields::required().with(MediaField::Children).to_param(),
+            "id,media_type,media_url,timestamp,children{id,media_type,media_url,timestamp}"
+        );
+
+        let fields = MediaFields::required().with(MediaField::Caption);
+        assert_eq!(fields.to_param(), "caption,id,media_type,media_url,timestamp");
+        assert_eq!(fields.without(MediaField::Id), fields);
+        assert_eq!(fields.without(MediaField::Caption), MediaFields::required());
+    }
+
+    #[test]
+    fn serialize() {
+        let fields = MediaFields::required().with(MediaField::Caption);
+        let json = serde_json::to_string(&fields).unwrap();
+        assert_eq!(json, r#"["caption","id","media_type","media_url","timestamp"]"#);
+        assert_eq!(serde_json::from_str::<MediaFields>(&json).unwrap(), fields);
+        assert_eq!(serde_json::from_str::<MediaFields>(r#"["caption"]"#).unwrap(), fields);
+        assert!(serde_json::from_str::<MediaFields>(r#"["unknown"]"#).is_err());
+    }
+}
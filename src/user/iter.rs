@@ -0,0 +1,74 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Lazy iteration over pages of media items.
+
+use super::{Media, MediaOrder, MediaQuery, Profile};
+use crate::auth::Token;
+use std::vec;
+
+use url::Url;
+
+/// Iterator that fetches media items page by page, so processing can start
+/// before the whole history is loaded. Constructed by [Profile::media_iter].
+///
+/// If a page can't be retrieved, the error is yielded and the iteration stops.
+/// An item that fails to parse yields an error without stopping the iteration.
+/// If items are requested [oldest first][MediaOrder::OldestFirst], all pages are retrieved
+/// before the first item is yielded.
+pub struct MediaIter<T> {
+    profile: Profile<T>,
+    query: MediaQuery,
+    /// Parsed items of the current page that haven't been yielded yet.
+    page: vec::IntoIter<crate::Result<Media>>,
+    next: Option<crate::Result<Url>>,
+}
+
+impl<T: Token> MediaIter<T> {
+    pub(super) fn new(profile: Profile<T>, url: crate::Result<Url>, query: MediaQuery) -> Self {
+        Self { profile, query, page: Vec::new().into_iter(), next: Some(url) }
+    }
+
+    /// Retrieves the next page or, if items are requested oldest first, all remaining pages.
+    fn fetch(&mut self, url: Url) -> crate::Result<()> {
+        let (url_policy, timezone) = (self.profile.url_policy, self.profile.timezone);
+        let mut page = self.profile.fetch_page(url)?;
+        let mut next = page.next.take();
+        let mut media = page.into_media(url_policy, timezone)?;
+        if self.query.order == MediaOrder::OldestFirst {
+            while let Some(url) = next {
+                let mut page = self.profile.fetch_page(url)?;
+                next = page.next.take();
+                media.extend(page.into_media(url_policy, timezone)?);
+            }
+            media.reverse();
+        }
+        self.page = media.into_iter();
+        self.next = next.map(Ok);
+        Ok(())
+    }
+}
+
+impl<T: Token> Iterator for MediaIter<T> {
+    type Item = crate::Result<Media>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.page.next() {
+                match result {
+                    Ok(media) if !self.query.matches(&media) => continue,
+                    result => return Some(result),
+                }
+            }
+
+            let url = match self.next.take()? {
+                Ok(url) => url,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Err(e) = self.fetch(url) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Page-level pagination that can be resumed later.
+
+use super::response;
+
+use serde::{Deserialize, Serialize};
+
+/// Single page of items returned by [Profile::media_page][super::Profile::media_page].
+pub struct Page<T> {
+    items: Vec<T>,
+    before: Option<Cursor>,
+    after: Option<Cursor>,
+    has_previous: bool,
+    has_next: bool,
+}
+
+/// Position of the next page. It doesn't contain the access token,
+/// so it's safe to persist it and resume a sync after a restart.
+///
+/// # Examples
+/// ```
+/// use instapi::user::Cursor;
+///
+/// let cursor = Cursor::new("QVFIUk1");
+/// let json = serde_json::to_string(&cursor)?;
+/// assert_eq!(serde_json::from_str::<Cursor>(&json)?, cursor);
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+impl<T> Page<T> {
+    pub(super) fn new(items: Vec<T>, paging: response::Paging) -> Self {
+        let (before, after) = match paging.cursors {
+            Some(cursors) => (cursors.before.map(Cursor), cursors.after.map(Cursor)),
+            None => (None, None),
+        };
+        Self {
+            items,
+            before,
+            after,
+            has_previous: paging.previous.is_some(),
+            has_next: paging.next.is_some(),
+        }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
+    /// Returns the cursor of the next page or `None` if this page is the last one.
+    pub fn next(&self) -> Option<&Cursor> {
+        self.after.as_ref().filter(|_| self.has_next)
+    }
+
+    /// Returns the cursor of the previous page or `None` if this page is the first one.
+    pub fn previous(&self) -> Option<&Cursor> {
+        self.before.as_ref().filter(|_| self.has_previous)
+    }
+
+    /// Returns the cursor that points to the start of this page as reported by the API,
+    /// even if there are no previous pages.
+    pub fn before(&self) -> Option<&Cursor> {
+        self.before.as_ref()
+    }
+
+    /// Returns the cursor that points to the end of this page as reported by the API,
+    /// even if there are no next pages.
+    pub fn after(&self) -> Option<&Cursor> {
+        self.after.as_ref()
+    }
+}
+
+impl Cursor {
+    /// Wraps a `before` or `after` cursor reported by the API.
+    pub fn new(cursor: &str) -> Self {
+        Self(cursor.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors() {
+        let page = |next: Option<&str>| {
+            let paging = serde_json::from_value::<response::Paging>(serde_json::json!({
+                "cursors": {"before": "QVFIUA", "after": "QVFIUk1"},
+                "next": next,
+            })).unwrap();
+            Page::<()>::new(Vec::new(), paging)
+        };
+
+        let first = page(Some("https://example.org"));
+        assert_eq!(first.next(), Some(&Cursor::new("QVFIUk1")));
+        assert_eq!(first.previous(), None);
+        assert_eq!(first.before(), Some(&Cursor::new("QVFIUA")));
+
+        // The API reports the `after` cursor for the last page too.
+        let last = page(None);
+        assert_eq!(last.next(), None);
+        assert_eq!(last.after(), Some(&Cursor::new("QVFIUk1")));
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Parameters that narrow down media requests.
+
+use super::{Media, MediaType};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+/// Parameters of [Profile::media_with][super::Profile::media_with] and
+/// [Profile::media_iter_with][super::Profile::media_iter_with].
+/// A missing parameter isn't sent.
+///
+/// # Examples
+/// Gather images published during the last month only, oldest first:
+/// ```
+/// use chrono::{Duration, Utc};
+/// use instapi::user::{MediaOrder, MediaQuery, MediaType};
+///
+/// let query = MediaQuery {
+///     since: Some(Utc::now() - Duration::days(30)),
+///     media_type: Some(MediaType::Image),
+///     order: MediaOrder::OldestFirst,
+///     ..MediaQuery::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MediaQuery {
+    /// Maximum number of items per page.
+    pub limit: Option<u32>,
+    /// Only items published at or after this date are returned.
+    pub since: Option<DateTime<Utc>>,
+    /// Only items published before this date are returned.
+    pub until: Option<DateTime<Utc>>,
+    /// Only items of this type are returned. The API can't filter by type,
+    /// so other items are still retrieved, but dropped after parsing.
+    pub media_type: Option<MediaType>,
+    pub order: MediaOrder,
+}
+
+/// Order of gathered media items by publish date.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum MediaOrder {
+    /// The order returned by the API.
+    #[default]
+    NewestFirst,
+    /// All pages have to be retrieved before the first item is available.
+    OldestFirst,
+}
+
+impl MediaQuery {
+    /// Appends the parameters to `url`.
+    pub(super) fn apply(&self, url: &mut Url) {
+        let mut pairs = url.query_pairs_mut();
+        if let Some(limit) = self.limit {
+            pairs.append_pair("limit", &limit.to_string());
+        }
+        if let Some(since) = self.since {
+            pairs.append_pair("since", &since.timestamp().to_string());
+        }
+        if let Some(until) = self.until {
+            pairs.append_pair("until", &until.timestamp().to_string());
+        }
+    }
+
+    /// Checks if `media` passes the filters that can't be applied by the API.
+    pub(super) fn matches(&self, media: &Media) -> bool {
+        self.media_type.map_or(true, |media_type| media.media_type() == media_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn apply() {
+        let mut url = Url::parse("https://example.com/media?fields=id").unwrap();
+        MediaQuery {
+            limit: Some(25),
+            since: Utc.timestamp_opt(1650000000, 0).single(),
+            ..MediaQuery::default()
+        }.apply(&mut url);
+        assert_eq!(url.query(), Some("fields=id&limit=25&since=1650000000"));
+
+        let mut url = Url::parse("https://example.com/media?fields=id").unwrap();
+        MediaQuery::default().apply(&mut url);
+        assert_eq!(url.query(), Some("fields=id"));
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Asynchronous counterpart of [MediaIter][super::MediaIter].
+
+use super::{Media, Profile};
+use crate::auth::Token;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+
+use futures::{
+    channel::mpsc::{self, Receiver},
+    executor, SinkExt, Stream, StreamExt,
+};
+
+/// Stream of media items that fetched page by page on a background thread.
+/// Constructed by [Profile::media_stream][super::Profile::media_stream].
+///
+/// The thread stops when the stream is dropped.
+pub struct MediaStream {
+    /// Errors are converted to strings to pass them between threads.
+    receiver: Receiver<Result<Media, String>>,
+}
+
+impl MediaStream {
+    pub(super) fn spawn<T>(profile: Profile<T>) -> Self
+    where
+        T: Token + Send + Sync + 'static,
+    {
+        // Keep a single item buffered, so pages aren't fetched ahead of the consumer.
+        let (mut sender, receiver) = mpsc::channel(1);
+        thread::spawn(move || {
+            for item in profile.media_iter() {
+                let item = item.map_err(|e| e.to_string());
+                if executor::block_on(sender.send(item)).is_err() {
+                    // The stream has been dropped.
+                    break;
+                }
+            }
+        });
+        Self { receiver }
+    }
+}
+
+impl Stream for MediaStream {
+    type Item = crate::Result<Media>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver
+            .poll_next_unpin(cx)
+            .map(|item| item.map(|result| result.map_err(Into::into)))
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Results of gatherings that tolerate malformed items.
+
+use super::{ItemError, Media, MediaOrder, MediaQuery};
+
+/// Media items gathered by [Profile::media_summary][super::Profile::media_summary]
+/// alongside items that couldn't be parsed. The order of items is preserved.
+pub struct MediaSummary {
+    media: Vec<Media>,
+    errors: Vec<ItemError>,
+}
+
+impl MediaSummary {
+    pub(super) fn new(media: Vec<Media>, errors: Vec<ItemError>) -> Self {
+        Self { media, errors }
+    }
+
+    pub fn media(&self) -> &[Media] {
+        &self.media
+    }
+
+    pub fn into_media(self) -> Vec<Media> {
+        self.media
+    }
+
+    /// Returns items that have been skipped, because they couldn't be parsed.
+    pub fn errors(&self) -> &[ItemError] {
+        &self.errors
+    }
+
+    /// Returns `true` if all items have been parsed.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Applies the filters and the order of `query` that can't be applied by the API.
+    pub(super) fn arrange(mut self, query: &MediaQuery) -> Self {
+        self.media.retain(|media| query.matches(media));
+        if query.order == MediaOrder::OldestFirst {
+            self.media.reverse();
+            self.errors.reverse();
+        }
+        self
+    }
+
+    /// Returns the gathered items or the first error if some items have been skipped.
+    pub fn into_result(mut self) -> crate::Result<Vec<Media>> {
+        if self.errors.is_empty() {
+            Ok(self.media)
+        } else {
+            Err(Box::new(self.errors.swap_remove(0)))
+        }
+    }
+}
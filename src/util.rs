@@ -0,0 +1,172 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Helpers for applications that store gathered media.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Platform whose file name rules are applied by [sanitize_filename_with].
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Platform {
+    /// The strictest rules: some characters and device names (`CON`, `COM1`, etc.) are reserved,
+    /// names can't end with a dot or a space.
+    Windows,
+    /// Slashes and colons aren't allowed, as the Finder shows colons as slashes.
+    MacOs,
+    /// Only slashes and null characters aren't allowed.
+    Unix,
+}
+
+/// Options of [sanitize_filename_with].
+#[derive(Clone, Copy, Debug)]
+pub struct SanitizeOptions {
+    pub platform: Platform,
+    /// Maximum length of a name in bytes. Most file systems allow 255 bytes.
+    pub max_length: usize,
+    /// Character that replaces invalid ones. It must be valid itself.
+    pub replacement: char,
+}
+
+impl Platform {
+    /// Returns the platform the library is compiled for.
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Unix
+        }
+    }
+
+    fn is_invalid(self, c: char) -> bool {
+        match self {
+            Self::Windows => c.is_control() || r#"<>:"/\|?*"#.contains(c),
+            Self::MacOs => c == '\0' || c == '/' || c == ':',
+            Self::Unix => c == '\0' || c == '/',
+        }
+    }
+}
+
+/// Uses the rules of [Windows][Platform::Windows], so names are valid on all platforms,
+/// and limits names to 255 bytes.
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self { platform: Platform::Windows, max_length: 255, replacement: '_' }
+    }
+}
+
+/// Makes `name` a valid file name on all platforms using the [default][SanitizeOptions::default]
+/// options. Useful for names that include usernames or captions.
+///
+/// # Examples
+/// ```
+/// use instapi::util::sanitize_filename;
+///
+/// assert_eq!(sanitize_filename("sunset: day 1/2?"), "sunset_ day 1_2_");
+/// assert_eq!(sanitize_filename("con.jpg"), "_con.jpg");
+/// ```
+pub fn sanitize_filename(name: &str) -> String {
+    sanitize_filename_with(name, &SanitizeOptions::default())
+}
+
+/// Replaces characters of `name` that are invalid on the platform, avoids reserved names
+/// and truncates the name to the maximum length without splitting characters.
+pub fn sanitize_filename_with(name: &str, options: &SanitizeOptions) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| if options.platform.is_invalid(c) { options.replacement } else { c })
+        .collect();
+
+    let mut sanitized = match replaced.as_str() {
+        "" | "." | ".." => options.replacement.to_string(),
+        _ if options.platform == Platform::Windows && is_reserved_on_windows(&replaced) => {
+            format!("{}{}", options.replacement, replaced)
+        }
+        _ => replaced,
+    };
+
+    let mut length = sanitized.len().min(options.max_length);
+    while !sanitized.is_char_boundary(length) {
+        length -= 1;
+    }
+    sanitized.truncate(length);
+
+    if options.platform == Platform::Windows {
+        // Windows silently drops trailing dots and spaces.
+        let trimmed = sanitized.trim_end_matches(['.', ' ']).len();
+        sanitized.truncate(trimmed);
+    }
+    if sanitized.is_empty() {
+        sanitized.push(options.replacement);
+    }
+    sanitized
+}
+
+/// Returns the path to `target` relative to the directory of the file at `from`,
+/// e.g. for links that keep working after the whole directory is moved.
+/// Both paths are expected to have the same base.
+pub(crate) fn relative_path(target: &Path, from: &Path) -> PathBuf {
+    let from_dir: Vec<Component> = from.parent().map_or_else(Vec::new, |dir| {
+        dir.components().filter(|c| *c != Component::CurDir).collect()
+    });
+    let target: Vec<Component> = target.components().filter(|c| *c != Component::CurDir).collect();
+    let common = from_dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    (common..from_dir.len())
+        .map(|_| Component::ParentDir)
+        .chain(target[common..].iter().copied())
+        .collect()
+}
+
+/// Checks if `name` is a device name, which is reserved even with an extension.
+fn is_reserved_on_windows(name: &str) -> bool {
+    const RESERVED: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+    let stem = name.split('.').next().unwrap_or_default().trim_end().to_ascii_uppercase();
+    if RESERVED.contains(&stem.as_str()) {
+        return true;
+    }
+    match (stem.get(..3), stem.get(3..)) {
+        (Some("COM" | "LPT"), Some(number)) => {
+            number.len() == 1 && number.chars().all(|c| ('1'..='9').contains(&c))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        let path = |target: &str, from: &str| relative_path(Path::new(target), Path::new(from));
+        assert_eq!(path("dir/a.jpg", "dir/album/b.jpg"), Path::new("..").join("a.jpg"));
+        assert_eq!(path("dir/album/a.jpg", "dir/b.jpg"), Path::new("album").join("a.jpg"));
+        assert_eq!(path("./a.jpg", "b.jpg"), Path::new("a.jpg"));
+    }
+
+    #[test]
+    fn sanitize() {
+        assert_eq!(sanitize_filename("a<b>c\td"), "a_b_c_d");
+        assert_eq!(sanitize_filename("lpt1"), "_lpt1");
+        assert_eq!(sanitize_filename("com10"), "com10");
+        assert_eq!(sanitize_filename("name. ."), "name");
+        assert_eq!(sanitize_filename(".."), "_");
+        assert_eq!(sanitize_filename(""), "_");
+
+        let unix = SanitizeOptions { platform: Platform::Unix, ..SanitizeOptions::default() };
+        assert_eq!(sanitize_filename_with("a:b/c.", &unix), "a:b_c.");
+        let mac = SanitizeOptions { platform: Platform::MacOs, ..SanitizeOptions::default() };
+        assert_eq!(sanitize_filename_with("a:b/c", &mac), "a_b_c");
+    }
+
+    #[test]
+    fn truncate() {
+        let options = SanitizeOptions { max_length: 5, ..SanitizeOptions::default() };
+        // "ё" takes two bytes, so it isn't split.
+        assert_eq!(sanitize_filename_with("abcdё", &options), "abcd");
+        assert_eq!(sanitize_filename_with("abc .x", &options), "abc");
+    }
+}
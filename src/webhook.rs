@@ -0,0 +1,113 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Utilities for apps that receive Instagram webhooks.
+
+mod dispatcher;
+mod event;
+
+pub use dispatcher::{DispatchError, Dispatcher};
+pub use event::{parse_events, Change, Event, Mention, NewComment, StoryInsights};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Name of the header that contains the payload signature.
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+
+/// Verification request that sent when a webhook subscription is configured.
+///
+/// # Examples
+/// ```
+/// use instapi::webhook::Verification;
+///
+/// let query = "hub.mode=subscribe&hub.challenge=1158201444&hub.verify_token=meatyhamhock";
+/// let verification = Verification::from_query(query).unwrap();
+/// // Respond with the challenge to confirm the subscription.
+/// assert_eq!(verification.respond("meatyhamhock"), Some("1158201444"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    /// Always `subscribe` for valid requests.
+    pub mode: String,
+    /// Token that specified when configuring the subscription.
+    pub verify_token: String,
+    /// Value that must be sent back to confirm the subscription.
+    pub challenge: String,
+}
+
+impl Verification {
+    /// Parses the `hub.*` parameters of a query string.
+    /// Returns `None` if some of them are missing.
+    pub fn from_query(query: &str) -> Option<Self> {
+        let (mut mode, mut verify_token, mut challenge) = (None, None, None);
+        for (key, value) in url::form_urlencoded::parse(query.trim_start_matches('?').as_bytes()) {
+            match key.as_ref() {
+                "hub.mode" => mode = Some(value.into_owned()),
+                "hub.verify_token" => verify_token = Some(value.into_owned()),
+                "hub.challenge" => challenge = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        Some(Self { mode: mode?, verify_token: verify_token?, challenge: challenge? })
+    }
+
+    /// Returns the challenge to respond with if the request is a subscription
+    /// and it contains the expected `verify_token`. Otherwise, respond with an error status.
+    pub fn respond(&self, verify_token: &str) -> Option<&str> {
+        if self.mode == "subscribe" && self.verify_token == verify_token {
+            Some(&self.challenge)
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks if the `X-Hub-Signature-256` `header` value (`sha256=<hex digest>`)
+/// matches the HMAC-SHA256 of the raw request `body` calculated using the app secret.
+///
+/// # Examples
+/// ```
+/// let body = br#"{"object":"instagram"}"#;
+/// assert!(!instapi::webhook::verify_signature("sha256=00", body, "app_secret"));
+/// ```
+pub fn verify_signature(header: &str, body: &[u8], app_secret: &str) -> bool {
+    let signature = match header.trim().strip_prefix("sha256=").map(hex::decode) {
+        Some(Ok(signature)) => signature,
+        _ => return false,
+    };
+
+    match Hmac::<Sha256>::new_from_slice(app_secret.as_bytes()) {
+        Ok(mut mac) => {
+            mac.update(body);
+            mac.verify_slice(&signature).is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_signature() {
+        let body = br#"{"object":"instagram"}"#;
+        let header = "sha256=399bc1437fac542698bd254076e927c9bca445e84d36fca4da8e2b5cf5cc59c4";
+        assert!(super::verify_signature(header, body, "secret"));
+        assert!(!super::verify_signature(header, body, "other"));
+        assert!(!super::verify_signature("sha1=00", body, "secret"));
+    }
+
+    #[test]
+    fn verification() {
+        assert!(Verification::from_query("hub.mode=subscribe").is_none());
+
+        let verification = Verification::from_query(
+            "?hub.mode=subscribe&hub.verify_token=token&hub.challenge=42"
+        ).unwrap();
+        assert_eq!(verification.respond("token"), Some("42"));
+        assert_eq!(verification.respond("other"), None);
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Routing of webhook events to handlers.
+
+use super::{Change, Event, Mention, NewComment, StoryInsights};
+use std::fmt;
+
+type Handler = dyn Fn(&Event) + Send + Sync;
+
+/// Verifies payloads of webhooks and passes their events to the registered handlers.
+/// Handlers of all matching types are called in the order of registration.
+///
+/// # Examples
+/// ```
+/// use instapi::webhook::Dispatcher;
+///
+/// let dispatcher = Dispatcher::new("app_secret")
+///     .on_comment(|account_id, comment| println!("{}: {}", account_id, comment.text))
+///     .on_mention(|account_id, mention| println!("{}: {}", account_id, mention.media_id));
+/// // Respond with 401 Unauthorized to a payload with an invalid signature.
+/// assert!(dispatcher.dispatch("sha256=00", b"{}").is_err());
+/// ```
+pub struct Dispatcher {
+    app_secret: String,
+    handlers: Vec<Box<Handler>>,
+}
+
+/// Describes why a payload hasn't been dispatched.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DispatchError {
+    /// The signature doesn't match the payload, so it must be rejected.
+    InvalidSignature,
+    /// The payload can't be parsed. Contains the parsing error.
+    Malformed(String),
+}
+
+impl Dispatcher {
+    /// `app_secret` is used to verify signatures of payloads.
+    pub fn new(app_secret: &str) -> Self {
+        Self { app_secret: app_secret.to_string(), handlers: Vec::new() }
+    }
+
+    /// Registers a handler of events of any type.
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Registers a handler of new comments. It receives the account ID and the comment.
+    pub fn on_comment<F>(self, handler: F) -> Self
+    where
+        F: Fn(u64, &NewComment) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Change::Comment(comment) = &event.change {
+                handler(event.account_id, comment);
+            }
+        })
+    }
+
+    /// Registers a handler of mentions. It receives the account ID and the mention.
+    pub fn on_mention<F>(self, handler: F) -> Self
+    where
+        F: Fn(u64, &Mention) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Change::Mention(mention) = &event.change {
+                handler(event.account_id, mention);
+            }
+        })
+    }
+
+    /// Registers a handler of story insights. It receives the account ID and the metrics.
+    pub fn on_story_insights<F>(self, handler: F) -> Self
+    where
+        F: Fn(u64, &StoryInsights) + Send + Sync + 'static,
+    {
+        self.on_event(move |event| {
+            if let Change::StoryInsights(insights) = &event.change {
+                handler(event.account_id, insights);
+            }
+        })
+    }
+
+    /// Verifies the `X-Hub-Signature-256` `signature` of the raw `body`, then passes
+    /// its events to the handlers. Returns the number of events.
+    pub fn dispatch(&self, signature: &str, body: &[u8]) -> Result<usize, DispatchError> {
+        if !super::verify_signature(signature, body, &self.app_secret) {
+            return Err(DispatchError::InvalidSignature);
+        }
+        let events =
+            super::parse_events(body).map_err(|e| DispatchError::Malformed(e.to_string()))?;
+        for event in &events {
+            for handler in &self.handlers {
+                handler(event);
+            }
+        }
+        Ok(events.len())
+    }
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSignature => f.write_str("payload signature is invalid"),
+            Self::Malformed(e) => write!(f, "payload is malformed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DispatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    #[test]
+    fn dispatch() {
+        let body = br#"{"object": "instagram", "entry": [{"id": "1", "changes": [
+            {"field": "mentions", "value": {"media_id": "2"}},
+            {"field": "mentions", "value": {"media_id": "3"}},
+            {"field": "comments", "value": {"id": "4", "media": {"id": "2"}}}
+        ]}]}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        let mentioned = Arc::new(AtomicU64::new(0));
+        let dispatcher = Dispatcher::new("secret").on_mention({
+            let mentioned = Arc::clone(&mentioned);
+            move |_, mention| {
+                mentioned.fetch_add(mention.media_id, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(dispatcher.dispatch(&signature, body), Ok(3));
+        assert_eq!(mentioned.load(Ordering::SeqCst), 5);
+
+        assert_eq!(dispatcher.dispatch("sha256=00", body), Err(DispatchError::InvalidSignature));
+        assert!(matches!(
+            Dispatcher::new("").dispatch(&signature, body),
+            Err(DispatchError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright © 2022 Nikita Dudko. All rights reserved.
+// Contacts: <nikita.dudko.95@gmail.com>
+// Licensed under the MIT License.
+
+//! Typed events of webhook payloads.
+
+use crate::{json, user::MediaProductType};
+
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Change of a business account delivered by a webhook.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Event {
+    /// Instagram user ID of the account that the change belongs to.
+    pub account_id: u64,
+    pub time: Option<DateTime<Utc>>,
+    pub change: Change,
+}
+
+/// Change of a subscribed field.
+#[derive(Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum Change {
+    /// A comment has been added to media of the account (the `comments` field).
+    Comment(NewComment),
+    /// The account has been mentioned in a caption or a comment (the `mentions` field).
+    Mention(Mention),
+    /// A story of the account has expired (the `story_insights` field).
+    StoryInsights(StoryInsights),
+    /// Any other field, its value is kept as is.
+    Other { field: String, value: serde_json::Value },
+}
+
+/// Comment that has been added to media of the account.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NewComment {
+    pub id: u64,
+    pub text: String,
+    /// ID of the comment that this one replies to.
+    pub parent_id: Option<u64>,
+    pub user_id: Option<u64>,
+    pub username: Option<String>,
+    pub media_id: u64,
+    pub media_product_type: Option<MediaProductType>,
+}
+
+/// Mention of the account. Use [Account::mentioned_comment] or [Account::mentioned_media]
+/// to retrieve it.
+///
+/// [Account::mentioned_comment]: crate::graph::Account::mentioned_comment
+/// [Account::mentioned_media]: crate::graph::Account::mentioned_media
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Mention {
+    pub media_id: u64,
+    /// Contains `None` if the account is mentioned in the caption.
+    pub comment_id: Option<u64>,
+}
+
+/// Metrics of an expired story.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub struct StoryInsights {
+    pub media_id: u64,
+    pub impressions: u64,
+    pub reach: u64,
+    pub taps_forward: u64,
+    pub taps_back: u64,
+    pub exits: u64,
+    pub replies: u64,
+}
+
+/// Serde models of payloads.
+mod payload {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub(super) struct Payload {
+        #[serde(default)]
+        pub(super) entry: Vec<Entry>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Entry {
+        pub(super) id: String,
+        pub(super) time: Option<i64>,
+        #[serde(default)]
+        pub(super) changes: Vec<Change>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Change {
+        pub(super) field: String,
+        pub(super) value: serde_json::Value,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Comment {
+        pub(super) id: String,
+        #[serde(default)]
+        pub(super) text: String,
+        pub(super) parent_id: Option<String>,
+        pub(super) from: Option<User>,
+        pub(super) media: Media,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct User {
+        pub(super) id: Option<String>,
+        pub(super) username: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Media {
+        pub(super) id: String,
+        pub(super) media_product_type: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Mention {
+        pub(super) media_id: String,
+        pub(super) comment_id: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct StoryInsights {
+        pub(super) media_id: String,
+        #[serde(default)]
+        pub(super) impressions: u64,
+        #[serde(default)]
+        pub(super) reach: u64,
+        #[serde(default)]
+        pub(super) taps_forward: u64,
+        #[serde(default)]
+        pub(super) taps_back: u64,
+        #[serde(default)]
+        pub(super) exits: u64,
+        #[serde(default)]
+        pub(super) replies: u64,
+    }
+}
+
+/// Parses events of a webhook payload `body`. Verify the signature of the body first,
+/// see [verify_signature][super::verify_signature].
+///
+/// # Examples
+/// ```
+/// use instapi::webhook::{self, Change};
+///
+/// let body = br#"{"object": "instagram", "entry": [{"id": "1", "time": 1569262486,
+///     "changes": [{"field": "mentions", "value": {"media_id": "2", "comment_id": "3"}}]}]}"#;
+/// for event in webhook::parse_events(body)? {
+///     if let Change::Mention(mention) = event.change {
+///         assert_eq!(mention.comment_id, Some(3));
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_events(body: &[u8]) -> crate::Result<Vec<Event>> {
+    // The body may be modified by parsing.
+    let payload: payload::Payload = json::from_body(&mut body.to_vec())?;
+    let mut events = Vec::new();
+    for entry in payload.entry {
+        let account_id = entry.id.parse()?;
+        let time = entry.time.and_then(|time| Utc.timestamp_opt(time, 0).single());
+        for change in entry.changes {
+            events.push(Event { account_id, time, change: Change::from(change)? });
+        }
+    }
+    Ok(events)
+}
+
+impl Change {
+    fn from(change: payload::Change) -> crate::Result<Self> {
+        Ok(match change.field.as_str() {
+            "comments" => {
+                let comment: payload::Comment = serde_json::from_value(change.value)?;
+                let from = comment.from.unwrap_or(payload::User { id: None, username: None });
+                Self::Comment(NewComment {
+                    id: comment.id.parse()?,
+                    text: comment.text,
+                    parent_id: crate::parse_opt(comment.parent_id)?,
+                    user_id: crate::parse_opt(from.id)?,
+                    username: from.username,
+                    media_id: comment.media.id.parse()?,
+                    media_product_type: crate::parse_opt(comment.media.media_product_type)?,
+                })
+            }
+            "mentions" => {
+                let mention: payload::Mention = serde_json::from_value(change.value)?;
+                Self::Mention(Mention {
+                    media_id: mention.media_id.parse()?,
+                    comment_id: crate::parse_opt(mention.comment_id)?,
+                })
+            }
+            "story_insights" => {
+                let insights: payload::StoryInsights = serde_json::from_value(change.value)?;
+                Self::StoryInsights(StoryInsights {
+                    media_id: insights.media_id.parse()?,
+                    impressions: insights.impressions,
+                    reach: insights.reach,
+                    taps_forward: insights.taps_forward,
+                    taps_back: insights.taps_back,
+                    exits: insights.exits,
+                    replies: insights.replies,
+                })
+            }
+            _ => Self::Other { field: change.field, value: change.value },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_events() {
+        let body = br#"{"object": "instagram", "entry": [{
+            "id": "17841405822304914",
+            "time": 1569262486,
+            "changes": [
+                {"field": "comments", "value": {
+                    "from": {"id": "232323232", "username": "john"},
+                    "media": {"id": "123123123", "media_product_type": "FEED"},
+                    "id": "17865799348089039",
+                    "text": "This is an example."
+                }},
+                {"field": "story_insights", "value": {
+                    "media_id": "17887498072083520", "impressions": 444, "reach": 44,
+                    "taps_forward": 4, "taps_back": 3, "exits": 3, "replies": 0
+                }},
+                {"field": "live_comments", "value": {}}
+            ]
+        }]}"#;
+        let events = super::parse_events(body).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].account_id, 17841405822304914);
+        assert_eq!(events[0].time.unwrap().timestamp(), 1569262486);
+        assert_eq!(
+            events[0].change,
+            Change::Comment(NewComment {
+                id: 17865799348089039,
+                text: "This is an example.".to_string(),
+                parent_id: None,
+                user_id: Some(232323232),
+                username: Some("john".to_string()),
+                media_id: 123123123,
+                media_product_type: Some(MediaProductType::Feed),
+            })
+        );
+        assert!(matches!(
+            events[1].change,
+            Change::StoryInsights(StoryInsights { reach: 44, taps_back: 3, .. })
+        ));
+        assert!(
+            matches!(&events[2].change, Change::Other { field, .. } if field == "live_comments")
+        );
+        assert!(super::parse_events(b"{\"entry\": [{\"id\": \"x\"}]}").is_err());
+    }
+}